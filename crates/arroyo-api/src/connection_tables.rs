@@ -6,6 +6,7 @@ use axum::Json;
 use axum_extra::extract::WithRejection;
 use futures_util::stream::Stream;
 use serde_json::{json, Value};
+use std::collections::BTreeMap;
 use std::convert::Infallible;
 use tokio::sync::mpsc::channel;
 use tokio_stream::wrappers::ReceiverStream;
@@ -514,14 +515,16 @@ async fn expand_avro_schema(
         };
     };
 
+    let mut mappings = BTreeMap::new();
     if let Some(Format::Avro(format)) = &mut schema.format {
         format.add_reader_schema(
             apache_avro::Schema::parse_str(definition)
                 .map_err(|e| bad_request(format!("Avro schema is invalid: {:?}", e)))?,
         );
+        mappings = format.logical_type_mappings.clone();
     }
 
-    let fields: Result<_, String> = avro::schema::to_arrow(definition)
+    let fields: Result<_, String> = avro::schema::to_arrow_with_mappings(definition, &mappings)
         .map_err(|e| bad_request(format!("Invalid avro schema: {}", e)))?
         .fields
         .into_iter()
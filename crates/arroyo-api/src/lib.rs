@@ -286,6 +286,19 @@ impl IntoResponse for HttpError {
         RawStringFormat,
         RawBytesFormat,
         TimestampFormat,
+        OversizedArrayPolicy,
+        NestingOverflowPolicy,
+        FieldNameCollisionPolicy,
+        MapNullValuePolicy,
+        FieldTransform,
+        UnframedMessagePolicy,
+        IntegerOverflowPolicy,
+        OutOfRangeTimePolicy,
+        UnknownEnumSymbolPolicy,
+        PrecisionLossPolicy,
+        NonFiniteDecimalPolicy,
+        DecimalSeparator,
+        LogicalTypeMapping,
         Framing,
         FramingMethod,
         NewlineDelimitedFraming,
@@ -9,6 +9,7 @@ use arroyo_operator::SourceFinishType;
 use arroyo_types::*;
 use async_trait::async_trait;
 use bincode::{Decode, Encode};
+use futures::FutureExt;
 use governor::{Quota, RateLimiter as GovernorRateLimiter};
 use rdkafka::consumer::{CommitMode, Consumer, StreamConsumer};
 use rdkafka::{ClientConfig, Message as KMessage, Offset, TopicPartitionList};
@@ -20,6 +21,10 @@ use tokio::select;
 use tokio::time::MissedTickBehavior;
 use tracing::{debug, error, info, warn};
 
+/// The most messages that are taken from the consumer, once one has arrived, to deserialize
+/// together.
+const MAX_MESSAGES_PER_POLL: usize = 512;
+
 #[cfg(test)]
 mod test;
 
@@ -165,17 +170,37 @@ impl KafkaSourceFunc {
                 message = consumer.recv() => {
                     match message {
                         Ok(msg) => {
-                            if let Some(v) = msg.payload() {
-                                let timestamp = msg.timestamp().to_millis()
-                                    .ok_or_else(|| UserError::new("Failed to read timestamp from Kafka record",
-                                        "The message read from Kafka did not contain a message timestamp"))?;
-
-                                ctx.deserialize_slice(v, from_millis(timestamp as u64)).await?;
+                            // take whatever else has already arrived, so that the messages can be
+                            // deserialized together (and in parallel, for some formats)
+                            let mut msgs = vec![msg];
+                            while msgs.len() < MAX_MESSAGES_PER_POLL {
+                                match consumer.recv().now_or_never() {
+                                    Some(Ok(msg)) => msgs.push(msg),
+                                    Some(Err(err)) => {
+                                        error!("encountered error {}", err);
+                                        break;
+                                    }
+                                    None => break,
+                                }
+                            }
 
-                                if ctx.should_flush() {
-                                    ctx.flush_buffer().await?;
+                            let mut payloads = Vec::with_capacity(msgs.len());
+                            for msg in &msgs {
+                                if let Some(v) = msg.payload() {
+                                    let timestamp = msg.timestamp().to_millis()
+                                        .ok_or_else(|| UserError::new("Failed to read timestamp from Kafka record",
+                                            "The message read from Kafka did not contain a message timestamp"))?;
+                                    payloads.push((v, from_millis(timestamp as u64)));
                                 }
+                            }
+
+                            ctx.deserialize_slices(&payloads).await?;
+
+                            if ctx.should_flush() {
+                                ctx.flush_buffer().await?;
+                            }
 
+                            for msg in msgs.iter().filter(|msg| msg.payload().is_some()) {
                                 offsets.insert(msg.partition(), msg.offset());
                                 rate_limiter.until_ready().await;
                             }
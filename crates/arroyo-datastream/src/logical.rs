@@ -34,6 +34,32 @@ pub enum OperatorName {
     SlidingWindowAggregate,
     SessionWindowAggregate,
     UpdatingAggregate,
+    Delta,
+    Heartbeat,
+    CountWindow,
+    Union,
+    AsyncBatchTransform,
+    MaterializedView,
+    WatermarkStall,
+    WindowAssigner,
+    RollingAverage,
+    Retract,
+    BloomFilter,
+    Split,
+    FirstLast,
+    BoundaryFlush,
+    Ema,
+    ZScore,
+    HoppingDedup,
+    Histogram,
+    TemporalJoin,
+    EarlyResults,
+    SnapshotDiff,
+    ProjectFilter,
+    Throughput,
+    Leaderboard,
+    Correlation,
+    LateDataFilter,
     ConnectorSource,
     ConnectorSink,
 }
@@ -278,6 +304,32 @@ impl LogicalProgram {
                 OperatorName::SlidingWindowAggregate => "sql-sliding-window-aggregate".to_string(),
                 OperatorName::SessionWindowAggregate => "sql-session-window-aggregate".to_string(),
                 OperatorName::UpdatingAggregate => "sql-updating-aggregate".to_string(),
+                OperatorName::Delta => "delta".to_string(),
+                OperatorName::Heartbeat => "heartbeat".to_string(),
+                OperatorName::CountWindow => "count-window".to_string(),
+                OperatorName::Union => "union".to_string(),
+                OperatorName::AsyncBatchTransform => "async-batch-transform".to_string(),
+                OperatorName::MaterializedView => "materialized-view".to_string(),
+                OperatorName::WatermarkStall => "watermark-stall".to_string(),
+                OperatorName::WindowAssigner => "window-assigner".to_string(),
+                OperatorName::RollingAverage => "rolling-average".to_string(),
+                OperatorName::Retract => "retract".to_string(),
+                OperatorName::BloomFilter => "bloom-filter".to_string(),
+                OperatorName::Split => "split".to_string(),
+                OperatorName::FirstLast => "first-last".to_string(),
+                OperatorName::BoundaryFlush => "boundary-flush".to_string(),
+                OperatorName::Ema => "ema".to_string(),
+                OperatorName::ZScore => "z-score".to_string(),
+                OperatorName::HoppingDedup => "hopping-dedup".to_string(),
+                OperatorName::Histogram => "histogram".to_string(),
+                OperatorName::TemporalJoin => "temporal-join".to_string(),
+                OperatorName::EarlyResults => "early-results".to_string(),
+                OperatorName::SnapshotDiff => "snapshot-diff".to_string(),
+                OperatorName::ProjectFilter => "project-filter".to_string(),
+                OperatorName::Throughput => "throughput".to_string(),
+                OperatorName::Leaderboard => "leaderboard".to_string(),
+                OperatorName::Correlation => "correlation".to_string(),
+                OperatorName::LateDataFilter => "late-data-filter".to_string(),
                 OperatorName::ConnectorSource => {
                     let Ok(connector_op) = ConnectorOp::decode(&t.operator_config[..]) else {
                         continue;
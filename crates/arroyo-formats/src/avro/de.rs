@@ -1,28 +1,158 @@
+use apache_avro::rabin::Rabin;
 use apache_avro::types::{Value, Value as AvroValue};
 use apache_avro::{from_avro_datum, AvroResult, Reader, Schema};
-use arroyo_rpc::formats::AvroFormat;
+use arrow::array::{ArrayData, ArrayDataBuilder};
+use arrow::buffer::{Buffer, NullBuffer};
+use arrow::compute::cast;
+use arrow::compute::kernels::take::take;
+use arrow::datatypes::{i256, ArrowNativeType};
+use arrow_array::builder::{FixedSizeBinaryBuilder, StringBuilder};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{
+    Decimal128Type, Decimal256Type, DecimalType, Int64Type, Int8Type, IntervalMonthDayNanoType,
+    UInt32Type,
+};
+use arrow_array::{
+    make_array, Array, ArrayRef, ArrowNativeTypeOp, BinaryArray, IntervalMonthDayNanoArray,
+    StructArray, UInt32Array, UnionArray,
+};
+use arrow_schema::{DataType, Field, Fields, IntervalUnit, TimeUnit, UnionFields, UnionMode};
+use arroyo_rpc::formats::{
+    AvroFormat, DecimalSeparator, FieldNameCollisionPolicy, FieldTransform, IntegerOverflowPolicy,
+    MapNullValuePolicy, NestingOverflowPolicy, NonFiniteDecimalPolicy, OutOfRangeTimePolicy,
+    OversizedArrayPolicy, PrecisionLossPolicy, UnframedMessagePolicy, UnknownEnumSymbolPolicy,
+};
 use arroyo_rpc::schema_resolver::SchemaResolver;
 use arroyo_types::SourceError;
+use chrono::NaiveDate;
 use serde_json::{json, Value as JsonValue};
-use std::collections::HashMap;
+use sha2::{Digest, Sha256};
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
 use std::sync::Arc;
+use std::time::{Duration, Instant};
 use tokio::sync::Mutex;
 use tracing::info;
+use uuid::Uuid;
+
+pub(crate) async fn resolve_schema(
+    resolver: &Arc<dyn SchemaResolver + Sync>,
+    id: u32,
+) -> Result<Schema, SourceError> {
+    let schema = resolver
+        .resolve_schema(id)
+        .await
+        .map_err(|e| SourceError::other("schema registry error", e))?
+        .ok_or_else(|| {
+            SourceError::bad_data(format!(
+                "could not resolve schema for message with id {}",
+                id
+            ))
+        })?;
+
+    Schema::parse_str(&schema).map_err(|e| {
+        SourceError::other(
+            "schema registry error",
+            format!(
+                "schema from Confluent Schema registry is not valid: {:?}",
+                e
+            ),
+        )
+    })
+}
+
+/// Decodes a message whose schema couldn't be resolved against the fallback schema. A fallback
+/// that is a record with a single `bytes` field captures the entire undecoded message in that
+/// field, including any schema registry header, preserving it for reprocessing once the real
+/// schema is available.
+pub(crate) fn decode_with_fallback(
+    schema: &Schema,
+    message: &[u8],
+    mut datum: &[u8],
+) -> AvroResult<Value> {
+    if let Schema::Record(record) = schema {
+        if let [field] = record.fields.as_slice() {
+            if field.schema == Schema::Bytes {
+                return Ok(Value::Record(vec![(
+                    field.name.clone(),
+                    Value::Bytes(message.to_vec()),
+                )]));
+            }
+        }
+    }
+
+    from_avro_datum(schema, &mut datum, None)
+}
+
+/// The marker at the start of messages in Avro's single-object encoding, which is followed by the
+/// 8-byte CRC-64-AVRO (Rabin) fingerprint of the writer schema and then the datum
+const SINGLE_OBJECT_MARKER: [u8; 2] = [0xC3, 0x01];
+
+/// The fingerprint that identifies a schema in single-object encoded messages
+pub(crate) fn single_object_fingerprint(schema: &Schema) -> [u8; 8] {
+    schema
+        .fingerprint::<Rabin>()
+        .bytes
+        .try_into()
+        .expect("Rabin fingerprints are 8 bytes")
+}
 
 pub(crate) async fn avro_messages(
     format: &AvroFormat,
     schema_registry: &Arc<Mutex<HashMap<u32, Schema>>>,
     resolver: &Arc<dyn SchemaResolver + Sync>,
+    fingerprints: &HashMap<[u8; 8], Schema>,
     mut msg: &[u8],
-) -> Result<Vec<AvroResult<Value>>, SourceError> {
+) -> Result<(u32, Vec<AvroResult<Value>>), SourceError> {
+    let message = msg;
+
+    if format.single_object_encoding {
+        let Some(header) = msg
+            .get(..10)
+            .filter(|h| h.starts_with(&SINGLE_OBJECT_MARKER))
+        else {
+            return Err(SourceError::bad_data(
+                "data was not encoded with Avro single-object encoding; \
+                message does not start with the C3 01 marker",
+            ));
+        };
+
+        let fingerprint: [u8; 8] = header[2..].try_into().unwrap();
+        let schema = fingerprints.get(&fingerprint).ok_or_else(|| {
+            SourceError::bad_data(format!(
+                "unknown schema fingerprint {:016x} in single-object encoded message",
+                u64::from_le_bytes(fingerprint)
+            ))
+        })?;
+
+        let mut datum = &msg[10..];
+        return Ok((0, vec![from_avro_datum(schema, &mut datum, None)]));
+    }
+    // framed messages start with a zero magic byte followed by a 4-byte schema id
+    let framed = msg.len() >= 5 && msg[0] == 0;
+    if format.confluent_schema_registry
+        && !framed
+        && format.unframed_messages == UnframedMessagePolicy::ReaderSchema
+    {
+        let schema = format.reader_schema.as_ref().ok_or_else(|| {
+            SourceError::bad_data("no reader schema to decode message without schema id")
+        })?;
+        let mut buf = msg;
+        return Ok((0, vec![from_avro_datum(&schema.0, &mut buf, None)]));
+    }
+
     let id = if format.confluent_schema_registry {
-        let magic_byte = msg[0];
-        if magic_byte != 0 {
-            return Err(SourceError::bad_data(format!(
-                "data was not encoded with schema registry wire format; \
-                magic byte has unexpected value: {}",
-                magic_byte
-            )));
+        if !framed {
+            return Err(SourceError::bad_data(match msg.first() {
+                Some(0) | None => "data was not encoded with schema registry wire format; \
+                    message is too short to contain a schema id"
+                    .to_string(),
+                Some(magic_byte) => format!(
+                    "data was not encoded with schema registry wire format; \
+                    magic byte has unexpected value: {}",
+                    magic_byte
+                ),
+            }));
         }
 
         let id = u32::from_be_bytes([msg[1], msg[2], msg[3], msg[4]]);
@@ -37,34 +167,22 @@ pub(crate) async fn avro_messages(
     let mut registry = schema_registry.lock().await;
 
     let messages = if format.raw_datums || format.confluent_schema_registry {
-        let schema = if let std::collections::hash_map::Entry::Vacant(e) = registry.entry(id) {
-            let new_schema = resolver
-                .resolve_schema(id)
-                .await
-                .map_err(|e| SourceError::other("schema registry error", e))?
-                .ok_or_else(|| {
-                    SourceError::bad_data(format!(
-                        "could not resolve schema for message with id {}",
-                        id
-                    ))
-                })?;
-
-            let new_schema = Schema::parse_str(&new_schema).map_err(|e| {
-                SourceError::other(
-                    "schema registry error",
-                    format!(
-                        "schema from Confluent Schema registry is not valid: {:?}",
-                        e
-                    ),
-                )
-            })?;
-
-            info!("Loaded new schema with id {} from Schema Registry", id);
-            e.insert(new_schema);
-
-            registry.get(&id).unwrap()
-        } else {
-            registry.get(&id).unwrap()
+        let schema = match registry.entry(id) {
+            Entry::Occupied(e) => e.into_mut(),
+            Entry::Vacant(e) => match resolve_schema(resolver, id).await {
+                Ok(schema) => {
+                    info!("Loaded new schema with id {} from Schema Registry", id);
+                    e.insert(schema)
+                }
+                Err(err) => {
+                    let Some(fallback) = &format.fallback_schema else {
+                        return Err(err);
+                    };
+                    // the fallback isn't cached, so that the real schema is used once it can be
+                    // resolved again
+                    return Ok((id, vec![decode_with_fallback(&fallback.0, message, msg)]));
+                }
+            },
         };
 
         let mut buf = msg;
@@ -74,459 +192,5282 @@ pub(crate) async fn avro_messages(
             format.reader_schema.as_ref().map(|t| t.into()),
         )]
     } else {
-        Reader::new(msg)
-            .map_err(|e| SourceError::bad_data(format!("invalid Avro schema in message: {:?}", e)))?
-            .collect()
+        // the message is an object container file, which embeds its writer schema; its records
+        // are resolved against the reader schema like those of other messages
+        match &format.reader_schema {
+            Some(schema) => Reader::with_schema(&schema.0, msg),
+            None => Reader::new(msg),
+        }
+        .map_err(|e| SourceError::bad_data(format!("invalid Avro schema in message: {:?}", e)))?
+        .collect()
     };
-    Ok(messages)
-}
-
-fn convert_float(f: f64) -> JsonValue {
-    match serde_json::Number::from_f64(f) {
-        Some(n) => JsonValue::Number(n),
-        None => JsonValue::String(
-            (if f.is_infinite() && f.is_sign_positive() {
-                "+Inf"
-            } else if f.is_infinite() {
-                "-Inf"
-            } else {
-                "NaN"
-            })
-            .to_string(),
-        ),
-    }
-}
-
-fn encode_vec(v: Vec<u8>) -> JsonValue {
-    JsonValue::String(v.into_iter().map(char::from).collect())
+    Ok((id, messages))
 }
 
-pub(crate) fn avro_to_json(value: AvroValue) -> JsonValue {
+/// Enforces a maximum length on every (possibly nested) array in the value, either truncating
+/// over-length arrays or rejecting the record according to the policy.
+pub(crate) fn limit_array_lengths(
+    value: &mut AvroValue,
+    max_length: usize,
+    policy: OversizedArrayPolicy,
+) -> Result<(), SourceError> {
     match value {
-        Value::Null => JsonValue::Null,
-        Value::Boolean(b) => JsonValue::Bool(b),
-        Value::Int(i) | Value::Date(i) | Value::TimeMillis(i) => {
-            JsonValue::Number(serde_json::Number::from(i))
+        Value::Array(items) => {
+            if items.len() > max_length {
+                match policy {
+                    OversizedArrayPolicy::Truncate => items.truncate(max_length),
+                    OversizedArrayPolicy::Reject => {
+                        return Err(SourceError::bad_data(format!(
+                            "array with {} elements exceeds the maximum length of {}",
+                            items.len(),
+                            max_length
+                        )));
+                    }
+                }
+            }
+
+            for item in items {
+                limit_array_lengths(item, max_length, policy)?;
+            }
         }
-        Value::Long(i)
-        | Value::TimeMicros(i)
-        | Value::TimestampMillis(i)
-        | Value::TimestampMicros(i)
-        | Value::LocalTimestampMillis(i)
-        | Value::LocalTimestampMicros(i) => JsonValue::Number(serde_json::Number::from(i)),
-        Value::Float(f) => convert_float(f as f64),
-        Value::Double(f) => convert_float(f),
-        Value::String(s) | Value::Enum(_, s) => JsonValue::String(s),
-        // this isn't the standard Avro json encoding, which just
-        Value::Bytes(b) | Value::Fixed(_, b) => encode_vec(b),
-        Value::Union(_, b) => avro_to_json(*b),
-        Value::Array(a) => JsonValue::Array(a.into_iter().map(avro_to_json).collect()),
         Value::Map(m) => {
-            JsonValue::Object(m.into_iter().map(|(k, v)| (k, avro_to_json(v))).collect())
+            for v in m.values_mut() {
+                limit_array_lengths(v, max_length, policy)?;
+            }
         }
         Value::Record(rec) => {
-            JsonValue::Object(rec.into_iter().map(|(k, v)| (k, avro_to_json(v))).collect())
+            for (_, v) in rec {
+                limit_array_lengths(v, max_length, policy)?;
+            }
         }
+        Value::Union(_, v) => limit_array_lengths(v, max_length, policy)?,
+        _ => {}
+    }
 
-        Value::Decimal(d) => {
-            let b: Vec<u8> = d.try_into().unwrap_or_else(|_| vec![]);
-            encode_vec(b)
+    Ok(())
+}
+
+/// Calls `f` on each value in the record that corresponds to a field in the Arrow schema, along
+/// with the dotted path to that column, recursing into nested records.
+pub(crate) fn for_each_field<F>(
+    value: &mut AvroValue,
+    fields: &Fields,
+    path: &str,
+    f: &mut F,
+) -> Result<(), SourceError>
+where
+    F: FnMut(&str, &Field, &mut AvroValue) -> Result<(), SourceError>,
+{
+    let Value::Record(rec) = value else {
+        return Ok(());
+    };
+
+    for (name, v) in rec.iter_mut() {
+        let Some(field) = fields.iter().find(|f| f.name() == name) else {
+            continue;
+        };
+
+        let path = if path.is_empty() {
+            name.clone()
+        } else {
+            format!("{}.{}", path, name)
+        };
+
+        f(&path, field, v)?;
+
+        if let DataType::Struct(children) = field.data_type() {
+            let inner = match v {
+                Value::Union(_, inner) => inner.as_mut(),
+                v => v,
+            };
+            for_each_field(inner, children, &path, f)?;
         }
-        Value::Duration(d) => {
-            json!({
-               "months": u32::from(d.months()),
-               "days": u32::from(d.days()),
-               "milliseconds": u32::from(d.millis())
-            })
+    }
+
+    Ok(())
+}
+
+/// Renames the fields of the record (and of nested records) whose names match a field in the Arrow
+/// schema only when ignoring case, so that they're decoded into that column. Fields that match
+/// exactly are left as is. If several fields in a record match the same column, the policy
+/// determines whether the record is rejected or the first of them is used.
+pub(crate) fn normalize_field_names(
+    value: &mut AvroValue,
+    fields: &Fields,
+    policy: FieldNameCollisionPolicy,
+) -> Result<(), SourceError> {
+    let Value::Record(rec) = value else {
+        return Ok(());
+    };
+
+    let lookup: HashMap<String, &Field> = fields
+        .iter()
+        .map(|f| (f.name().to_lowercase(), f.as_ref()))
+        .collect();
+
+    let mut matched = HashSet::new();
+    let mut normalized = Vec::with_capacity(rec.len());
+    for (name, mut v) in rec.drain(..) {
+        let field = match fields.iter().find(|f| f.name() == &name) {
+            Some(field) => Some(field.as_ref()),
+            None => lookup.get(&name.to_lowercase()).copied(),
+        };
+
+        let Some(field) = field else {
+            normalized.push((name, v));
+            continue;
+        };
+
+        if !matched.insert(field.name()) {
+            match policy {
+                FieldNameCollisionPolicy::Error => {
+                    return Err(SourceError::bad_data(format!(
+                        "field '{}' matches column '{}', which has already been matched by \
+                        another field",
+                        name,
+                        field.name()
+                    )));
+                }
+                FieldNameCollisionPolicy::FirstWins => continue,
+            }
         }
-        Value::Uuid(u) => JsonValue::String(u.to_string()),
+
+        if let DataType::Struct(children) = field.data_type() {
+            let inner = match &mut v {
+                Value::Union(_, inner) => inner.as_mut(),
+                v => v,
+            };
+            normalize_field_names(inner, children, policy)?;
+        }
+
+        normalized.push((field.name().clone(), v));
     }
+
+    *rec = normalized;
+    Ok(())
 }
 
-#[cfg(test)]
-mod tests {
-    use crate::avro::schema::to_arrow;
-    use crate::de::ArrowDeserializer;
-    use arrow_array::builder::{make_builder, ArrayBuilder};
-    use arrow_array::RecordBatch;
-    use arrow_schema::{DataType, Field, Schema, TimeUnit};
-    use arroyo_rpc::df::ArroyoSchema;
-    use arroyo_rpc::formats::{AvroFormat, BadData, Format};
-    use arroyo_rpc::schema_resolver::{FailingSchemaResolver, FixedSchemaResolver, SchemaResolver};
-    use serde_json::json;
-    use std::sync::Arc;
-    use std::time::SystemTime;
+/// Looks up a field of a record (or of a record inside a union) by name. Fields are matched by
+/// name rather than position, as the order of a record's fields after schema resolution needn't
+/// match the writer schema. Returns None if the value isn't a record or the field is absent.
+pub(crate) fn field_lookup<'a>(value: &'a AvroValue, name: &str) -> Option<&'a AvroValue> {
+    match value {
+        Value::Union(_, inner) => field_lookup(inner, name),
+        Value::Record(fields) => fields.iter().find(|(n, _)| n == name).map(|(_, v)| v),
+        _ => None,
+    }
+}
 
-    const SCHEMA: &str = r#"
-        {
-  "connect.name": "pizza_orders.pizza_orders",
-  "fields": [
-    {
-      "name": "store_id",
-      "type": "int"
-    },
-    {
-      "name": "store_order_id",
-      "type": "int"
-    },
-    {
-      "name": "coupon_code",
-      "type": "int"
-    },
-    {
-      "name": "date",
-      "type": {
-        "connect.name": "org.apache.kafka.connect.data.Date",
-        "connect.version": 1,
-        "logicalType": "date",
-        "type": "int"
-      }
-    },
-    {
-      "name": "status",
-      "type": "string"
-    },
-    {
-      "name": "order_lines",
-      "type": {
-        "items": {
-          "connect.name": "pizza_orders.order_line",
-          "fields": [
-            {
-              "name": "product_id",
-              "type": "int"
-            },
-            {
-              "name": "category",
-              "type": "string"
-            },
-            {
-              "name": "quantity",
-              "type": "int"
-            },
-            {
-              "name": "unit_price",
-              "type": "double"
-            },
-            {
-              "name": "net_price",
-              "type": "double"
+/// Fills in the fields of a record (and of its nested records) that are missing from the value but
+/// have a default in the reader schema. Records resolved against the reader schema already have
+/// them, but those decoded another way (like with the fallback schema) may not. Fields without a
+/// default are left missing.
+pub(crate) fn apply_field_defaults(value: &mut AvroValue, schema: &Schema) {
+    let value = match value {
+        Value::Union(_, inner) => inner.as_mut(),
+        v => v,
+    };
+
+    let Value::Record(fields) = value else {
+        return;
+    };
+
+    let record = match schema {
+        Schema::Record(record) => record,
+        // a nullable record; for unions of several records it isn't known which one applies
+        Schema::Union(union) => {
+            let mut records = union.variants().iter().filter_map(|v| match v {
+                Schema::Record(record) => Some(record),
+                _ => None,
+            });
+            match (records.next(), records.next()) {
+                (Some(record), None) => record,
+                _ => return,
             }
-          ],
-          "name": "order_line",
-          "type": "record"
-        },
-        "type": "array"
-      }
+        }
+        _ => return,
+    };
+
+    for field in &record.fields {
+        match fields.iter_mut().find(|(name, _)| name == &field.name) {
+            Some((_, v)) => apply_field_defaults(v, &field.schema),
+            None => {
+                if let Some(default) = &field.default {
+                    fields.push((field.name.clone(), Value::from(default.clone())));
+                }
+            }
+        }
     }
-  ],
-  "name": "pizza_orders",
-  "namespace": "pizza_orders",
-  "type": "record"
-}"#;
+}
 
-    fn deserializer_with_schema(
-        format: AvroFormat,
-        writer_schema: Option<&str>,
-    ) -> (ArrowDeserializer, Vec<Box<dyn ArrayBuilder>>, ArroyoSchema) {
-        let arrow_schema = if format.into_unstructured_json {
-            Schema::new(vec![Field::new("value", DataType::Utf8, false)])
-        } else {
-            to_arrow(
-                &format
-                    .reader_schema
-                    .as_ref()
-                    .expect("no reader schema")
-                    .0
-                    .canonical_form(),
-            )
-            .expect("invalid schema")
+/// Checks that the fields required for records written with the given schema id are present
+/// (and not null). Fields are identified by their dot-separated path through nested records.
+pub(crate) fn check_required_fields(
+    required: &BTreeMap<u32, Vec<String>>,
+    schema_id: u32,
+    value: &AvroValue,
+) -> Result<(), SourceError> {
+    let Some(fields) = required.get(&schema_id) else {
+        return Ok(());
+    };
+
+    for path in fields {
+        let mut current = Some(value);
+        for name in path.split('.') {
+            current = current.and_then(|v| field_lookup(v, name));
+        }
+
+        let present = match current {
+            Some(Value::Union(_, inner)) => **inner != Value::Null,
+            Some(v) => *v != Value::Null,
+            None => false,
         };
 
-        let arroyo_schema = {
-            let mut fields = arrow_schema.fields.to_vec();
-            fields.push(Arc::new(Field::new(
-                "_timestamp",
-                DataType::Timestamp(TimeUnit::Nanosecond, None),
-                false,
+        if !present {
+            return Err(SourceError::bad_data(format!(
+                "required field '{}' is missing in record with schema id {}",
+                path, schema_id
             )));
-            ArroyoSchema::from_schema_keys(Arc::new(Schema::new(fields)), vec![]).unwrap()
-        };
+        }
+    }
 
-        let builders: Vec<_> = arroyo_schema
-            .schema
-            .fields
-            .iter()
-            .map(|f| make_builder(f.data_type(), 8))
-            .collect();
+    Ok(())
+}
 
-        let resolver: Arc<dyn SchemaResolver + Sync> = if let Some(schema) = &writer_schema {
-            Arc::new(FixedSchemaResolver::new(
-                if format.confluent_schema_registry {
-                    1
-                } else {
-                    0
-                },
-                apache_avro::Schema::parse_str(schema).unwrap(),
-            ))
-        } else {
-            Arc::new(FailingSchemaResolver::new())
+fn integer_range(data_type: &DataType) -> Option<(i64, i64)> {
+    Some(match data_type {
+        DataType::Int8 => (i8::MIN as i64, i8::MAX as i64),
+        DataType::Int16 => (i16::MIN as i64, i16::MAX as i64),
+        DataType::Int32 => (i32::MIN as i64, i32::MAX as i64),
+        DataType::UInt8 => (0, u8::MAX as i64),
+        DataType::UInt16 => (0, u16::MAX as i64),
+        DataType::UInt32 => (0, u32::MAX as i64),
+        DataType::UInt64 => (0, i64::MAX),
+        _ => return None,
+    })
+}
+
+/// Checks that values decoded into time-of-day columns fall within a day, replacing them with
+/// null or rejecting the record otherwise.
+pub(crate) fn check_time_range(
+    path: &str,
+    field: &Field,
+    value: &mut AvroValue,
+    policy: OutOfRangeTimePolicy,
+) -> Result<(), SourceError> {
+    let units_per_day = match field.data_type() {
+        DataType::Time32(unit) | DataType::Time64(unit) => {
+            24 * 60 * 60 * 1_000_000_000 / nanos_per_unit(unit)
+        }
+        _ => return Ok(()),
+    };
+
+    let value = match value {
+        Value::Union(_, inner) => inner.as_mut(),
+        v => v,
+    };
+
+    let t = match value {
+        Value::Int(t) | Value::TimeMillis(t) => *t as i64,
+        Value::Long(t) | Value::TimeMicros(t) => *t,
+        _ => return Ok(()),
+    };
+
+    if (0..units_per_day).contains(&t) {
+        return Ok(());
+    }
+
+    match policy {
+        OutOfRangeTimePolicy::Error => Err(SourceError::bad_data(format!(
+            "value {} for column '{}' is not a valid time of day for type {}",
+            t,
+            path,
+            field.data_type()
+        ))),
+        OutOfRangeTimePolicy::Null => {
+            *value = Value::Null;
+            Ok(())
+        }
+    }
+}
+
+/// Checks that integer values fit in the integer column they're decoded into, handling
+/// out-of-range values according to the policy.
+pub(crate) fn check_integer_range(
+    path: &str,
+    field: &Field,
+    value: &mut AvroValue,
+    policy: IntegerOverflowPolicy,
+) -> Result<(), SourceError> {
+    let Some((min, max)) = integer_range(field.data_type()) else {
+        return Ok(());
+    };
+
+    let value = match value {
+        Value::Union(_, inner) => inner.as_mut(),
+        v => v,
+    };
+
+    let i = match value {
+        Value::Int(i) => *i as i64,
+        Value::Long(i) => *i,
+        _ => return Ok(()),
+    };
+
+    if i >= min && i <= max {
+        return Ok(());
+    }
+
+    match policy {
+        IntegerOverflowPolicy::Error => {
+            return Err(SourceError::bad_data(format!(
+                "value {} for column '{}' is out of range for type {}",
+                i,
+                path,
+                field.data_type()
+            )));
+        }
+        IntegerOverflowPolicy::Null => *value = Value::Null,
+        IntegerOverflowPolicy::Saturate => *value = Value::Long(i.clamp(min, max)),
+    }
+
+    Ok(())
+}
+
+/// Checks for floating point values with a fractional part being decoded into an integer column,
+/// which would otherwise be silently truncated.
+pub(crate) fn check_float_precision(
+    path: &str,
+    field: &Field,
+    value: &AvroValue,
+    policy: PrecisionLossPolicy,
+) -> Result<(), SourceError> {
+    if policy == PrecisionLossPolicy::Truncate || !field.data_type().is_integer() {
+        return Ok(());
+    }
+
+    let value = match value {
+        Value::Union(_, inner) => inner.as_ref(),
+        v => v,
+    };
+
+    let f = match value {
+        Value::Float(f) => *f as f64,
+        Value::Double(f) => *f,
+        _ => return Ok(()),
+    };
+
+    if f.fract() != 0.0 {
+        return Err(SourceError::bad_data(format!(
+            "value {} for column '{}' would lose precision when converted to {}",
+            f,
+            path,
+            field.data_type()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Handles NaN and infinite values, either as floats or numeric strings, decoded into decimal
+/// columns, which can't represent them, according to the policy.
+pub(crate) fn check_decimal_finite(
+    path: &str,
+    field: &Field,
+    value: &mut AvroValue,
+    policy: NonFiniteDecimalPolicy,
+) -> Result<(), SourceError> {
+    if !matches!(
+        field.data_type(),
+        DataType::Decimal128(..) | DataType::Decimal256(..)
+    ) {
+        return Ok(());
+    }
+
+    let inner = match &*value {
+        Value::Union(_, inner) => inner.as_ref(),
+        v => v,
+    };
+
+    let f = match inner {
+        Value::Float(f) => *f as f64,
+        Value::Double(f) => *f,
+        Value::String(s) => match s.trim().parse::<f64>() {
+            Ok(f) => f,
+            Err(_) => return Ok(()),
+        },
+        _ => return Ok(()),
+    };
+
+    if f.is_finite() {
+        return Ok(());
+    }
+
+    match policy {
+        NonFiniteDecimalPolicy::Error => Err(SourceError::bad_data(format!(
+            "value {} for column '{}' can't be represented as {}",
+            f,
+            path,
+            field.data_type()
+        ))),
+        NonFiniteDecimalPolicy::Null => {
+            *value = Value::Null;
+            Ok(())
+        }
+    }
+}
+
+/// Enforces a maximum nesting depth for records, arrays, and maps within the value, where the
+/// fields of the top-level record are at depth 1. Deeper values are replaced with null, or cause
+/// the record to be dropped or the pipeline to fail, according to the policy.
+pub(crate) fn limit_nesting_depth(
+    value: &mut AvroValue,
+    max_depth: usize,
+    policy: NestingOverflowPolicy,
+) -> Result<(), SourceError> {
+    fn walk(
+        value: &mut AvroValue,
+        depth: usize,
+        max_depth: usize,
+        policy: NestingOverflowPolicy,
+    ) -> Result<(), SourceError> {
+        match value {
+            Value::Union(_, inner) => return walk(inner, depth, max_depth, policy),
+            Value::Record(_) | Value::Array(_) | Value::Map(_) => {}
+            _ => return Ok(()),
+        }
+
+        if depth > max_depth {
+            let message = format!("value exceeds the maximum nesting depth of {}", max_depth);
+            return match policy {
+                NestingOverflowPolicy::Truncate => {
+                    *value = Value::Null;
+                    Ok(())
+                }
+                NestingOverflowPolicy::Drop => Err(SourceError::bad_data(message)),
+                NestingOverflowPolicy::Error => {
+                    Err(SourceError::other("Avro nesting depth exceeded", message))
+                }
+            };
+        }
+
+        match value {
+            Value::Record(rec) => {
+                for (_, v) in rec {
+                    walk(v, depth + 1, max_depth, policy)?;
+                }
+            }
+            Value::Array(items) => {
+                for v in items {
+                    walk(v, depth + 1, max_depth, policy)?;
+                }
+            }
+            Value::Map(m) => {
+                for v in m.values_mut() {
+                    walk(v, depth + 1, max_depth, policy)?;
+                }
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    walk(value, 0, max_depth, policy)
+}
+
+/// Finds the scale of each decimal field in an Avro schema, by the dot-separated path of the field
+/// through nested records.
+pub(crate) fn decimal_scales(schema: &Schema) -> HashMap<String, usize> {
+    fn walk(schema: &Schema, path: &str, scales: &mut HashMap<String, usize>) {
+        match schema {
+            Schema::Decimal(decimal) => {
+                scales.insert(path.to_string(), decimal.scale);
+            }
+            Schema::Union(union) => {
+                for variant in union.variants() {
+                    walk(variant, path, scales);
+                }
+            }
+            Schema::Record(record) => {
+                for field in &record.fields {
+                    let path = if path.is_empty() {
+                        field.name.clone()
+                    } else {
+                        format!("{}.{}", path, field.name)
+                    };
+                    walk(&field.schema, &path, scales);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut scales = HashMap::new();
+    walk(schema, "", &mut scales);
+    scales
+}
+
+/// Finds the names of the variants of each union field in an Avro schema, by the dot-separated path
+/// of the field through nested records. Variants that aren't records have no name.
+pub(crate) fn union_record_names(schema: &Schema) -> HashMap<String, Vec<Option<String>>> {
+    fn walk(schema: &Schema, path: &str, names: &mut HashMap<String, Vec<Option<String>>>) {
+        match schema {
+            Schema::Union(union) => {
+                names.insert(
+                    path.to_string(),
+                    union
+                        .variants()
+                        .iter()
+                        .map(|v| match v {
+                            Schema::Record(record) => Some(record.name.name.clone()),
+                            _ => None,
+                        })
+                        .collect(),
+                );
+                for variant in union.variants() {
+                    walk(variant, path, names);
+                }
+            }
+            Schema::Record(record) => {
+                for field in &record.fields {
+                    let path = if path.is_empty() {
+                        field.name.clone()
+                    } else {
+                        format!("{}.{}", path, field.name)
+                    };
+                    walk(&field.schema, &path, names);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut names = HashMap::new();
+    walk(schema, "", &mut names);
+    names
+}
+
+/// The field of a struct column that's set to the name of the Avro record a value was decoded from,
+/// for columns holding a union of records
+pub(crate) const UNION_RECORD_TAG_FIELD: &str = "__type";
+
+/// For struct columns with a [`UNION_RECORD_TAG_FIELD`], adds the name of the union variant that a
+/// record value came from to the record, so that records of different types can be decoded into
+/// one column that has the fields of all of them. Fields that a record doesn't have are null.
+pub(crate) fn tag_union_record(
+    path: &str,
+    field: &Field,
+    value: &mut AvroValue,
+    union_records: &HashMap<String, Vec<Option<String>>>,
+) {
+    let DataType::Struct(children) = field.data_type() else {
+        return;
+    };
+    if !children.iter().any(|f| f.name() == UNION_RECORD_TAG_FIELD) {
+        return;
+    }
+
+    let Value::Union(index, inner) = value else {
+        return;
+    };
+    let Some(Some(name)) = union_records
+        .get(path)
+        .and_then(|names| names.get(*index as usize))
+    else {
+        return;
+    };
+
+    if let Value::Record(fields) = inner.as_mut() {
+        fields.push((
+            UNION_RECORD_TAG_FIELD.to_string(),
+            Value::String(name.clone()),
+        ));
+    }
+}
+
+/// Finds the fields of a writer schema that have no corresponding column in the Arrow schema, by
+/// their dot-separated path through nested records.
+pub(crate) fn unexpected_fields(schema: &Schema, fields: &Fields) -> BTreeSet<String> {
+    fn walk(schema: &Schema, fields: &Fields, path: &str, unexpected: &mut BTreeSet<String>) {
+        match schema {
+            Schema::Union(union) => {
+                for variant in union.variants() {
+                    walk(variant, fields, path, unexpected);
+                }
+            }
+            Schema::Record(record) => {
+                for field in &record.fields {
+                    let path = if path.is_empty() {
+                        field.name.clone()
+                    } else {
+                        format!("{}.{}", path, field.name)
+                    };
+
+                    match fields.iter().find(|f| f.name() == &field.name) {
+                        Some(column) => {
+                            if let DataType::Struct(children) = column.data_type() {
+                                walk(&field.schema, children, &path, unexpected);
+                            }
+                        }
+                        None => {
+                            unexpected.insert(path);
+                        }
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    let mut unexpected = BTreeSet::new();
+    walk(schema, fields, "", &mut unexpected);
+    unexpected
+}
+
+const MILLIS_PER_DAY: i64 = 24 * 60 * 60 * 1000;
+
+fn nanos_per_unit(unit: &TimeUnit) -> i64 {
+    match unit {
+        TimeUnit::Second => 1_000_000_000,
+        TimeUnit::Millisecond => 1_000_000,
+        TimeUnit::Microsecond => 1_000,
+        TimeUnit::Nanosecond => 1,
+    }
+}
+
+/// Converts logical-typed Avro values into the representation expected by their column when the
+/// column type differs from the logical type's default (because of a logical type mapping, or a
+/// declared schema), for example rescaling a `timestamp-micros` into a nanosecond timestamp
+/// column, or a decimal into a float column.
+pub(crate) fn apply_logical_type_mapping(
+    path: &str,
+    field: &Field,
+    value: &mut AvroValue,
+    decimal_scales: &HashMap<String, usize>,
+) -> Result<(), SourceError> {
+    let value = match value {
+        Value::Union(_, inner) => inner.as_mut(),
+        v => v,
+    };
+
+    match (&*value, field.data_type()) {
+        (
+            Value::TimestampMillis(t) | Value::LocalTimestampMillis(t),
+            DataType::Timestamp(unit, _),
+        ) => {
+            *value = Value::Long(rescale(path, *t, &TimeUnit::Millisecond, unit)?);
+        }
+        (
+            Value::TimestampMicros(t) | Value::LocalTimestampMicros(t),
+            DataType::Timestamp(unit, _),
+        ) => {
+            *value = Value::Long(rescale(path, *t, &TimeUnit::Microsecond, unit)?);
+        }
+        (Value::TimeMillis(t), DataType::Time32(unit) | DataType::Time64(unit)) => {
+            *value = Value::Long(rescale(path, *t as i64, &TimeUnit::Millisecond, unit)?);
+        }
+        (Value::TimeMicros(t), DataType::Time32(unit) | DataType::Time64(unit)) => {
+            *value = Value::Long(rescale(path, *t, &TimeUnit::Microsecond, unit)?);
+        }
+        (Value::Date(days), DataType::Date64) => {
+            *value = Value::Long(*days as i64 * MILLIS_PER_DAY);
+        }
+        (Value::Uuid(u), DataType::FixedSizeBinary(16)) => {
+            *value = Value::Bytes(u.as_bytes().to_vec());
+        }
+        (Value::String(s), DataType::FixedSizeBinary(16)) => {
+            let u = Uuid::parse_str(s).map_err(|e| {
+                SourceError::bad_data(format!("invalid UUID '{}' for column '{}': {}", s, path, e))
+            })?;
+            *value = Value::Bytes(u.as_bytes().to_vec());
+        }
+        (Value::Decimal(_), DataType::Float64) => {
+            let unscaled = i128::from_be_bytes(unscaled_decimal(path, field, value)?);
+            let scale = decimal_scales.get(path).copied().unwrap_or_default();
+            *value = Value::Double(unscaled as f64 / 10f64.powi(scale as i32));
+        }
+        // bytes that aren't typed as a decimal in the Avro schema are taken to have the column's
+        // scale
+        (
+            Value::Decimal(_) | Value::Bytes(_) | Value::Fixed(..),
+            DataType::Decimal128(precision, scale),
+        ) => {
+            let unscaled = i128::from_be_bytes(unscaled_decimal(path, field, value)?);
+            let from = decimal_scales
+                .get(path)
+                .copied()
+                .unwrap_or((*scale).max(0) as usize);
+            *value =
+                decimal_string::<Decimal128Type>(path, field, unscaled, from, *precision, *scale)?;
+        }
+        (
+            Value::Decimal(_) | Value::Bytes(_) | Value::Fixed(..),
+            DataType::Decimal256(precision, scale),
+        ) => {
+            let unscaled = i256::from_be_bytes(unscaled_decimal(path, field, value)?);
+            let from = decimal_scales
+                .get(path)
+                .copied()
+                .unwrap_or((*scale).max(0) as usize);
+            *value =
+                decimal_string::<Decimal256Type>(path, field, unscaled, from, *precision, *scale)?;
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Returns the unscaled value of a decimal (or of the bytes or fixed value that holds one), which
+/// Avro encodes as a big-endian two's complement integer, sign-extended to `N` bytes. Values may
+/// be wider than `N` bytes (like a fixed of a larger size) as long as the extra bytes are only
+/// sign extension.
+fn unscaled_decimal<const N: usize>(
+    path: &str,
+    field: &Field,
+    value: &AvroValue,
+) -> Result<[u8; N], SourceError> {
+    let decimal: Vec<u8>;
+    let bytes: &[u8] = match value {
+        // a decimal only gives up its bytes by value, so it has to be cloned; bytes and fixed
+        // values are read in place
+        Value::Decimal(d) => {
+            decimal = d.clone().try_into().map_err(|e| {
+                SourceError::bad_data(format!("invalid decimal for column '{}': {:?}", path, e))
+            })?;
+            &decimal
+        }
+        Value::Bytes(b) | Value::Fixed(_, b) => b,
+        v => {
+            return Err(SourceError::bad_data(format!(
+                "expected a decimal for column '{}', got {:?}",
+                path, v
+            )))
+        }
+    };
+
+    let negative = bytes.first().is_some_and(|b| b & 0x80 != 0);
+    let fill = if negative { 0xff } else { 0 };
+
+    let mut bytes = bytes;
+    while bytes.len() > N && bytes[0] == fill && (bytes[1] & 0x80 != 0) == negative {
+        bytes = &bytes[1..];
+    }
+
+    if bytes.len() > N {
+        return Err(SourceError::bad_data(format!(
+            "decimal for column '{}' is too large to convert to {}",
+            path,
+            field.data_type()
+        )));
+    }
+
+    let mut buf = [fill; N];
+    buf[N - bytes.len()..].copy_from_slice(bytes);
+
+    Ok(buf)
+}
+
+/// Rescales an unscaled decimal from the Avro scale to the column's, and formats it as a string
+/// (the form the JSON decoder parses decimals from), rejecting values that exceed the column's
+/// precision.
+fn decimal_string<T: DecimalType>(
+    path: &str,
+    field: &Field,
+    unscaled: T::Native,
+    from_scale: usize,
+    precision: u8,
+    scale: i8,
+) -> Result<AvroValue, SourceError> {
+    let too_large = || {
+        SourceError::bad_data(format!(
+            "decimal for column '{}' exceeds the precision of {}",
+            path,
+            field.data_type()
+        ))
+    };
+
+    let (from, to) = (from_scale as i32, scale as i32);
+    let ten = T::Native::usize_as(10);
+    let unscaled = if to >= from {
+        ten.pow_checked((to - from) as u32)
+            .and_then(|m| unscaled.mul_checked(m))
+            .map_err(|_| too_large())?
+    } else {
+        ten.pow_checked((from - to) as u32)
+            .map_or(T::Native::ZERO, |d| unscaled.div_wrapping(d))
+    };
+
+    T::validate_decimal_precision(unscaled, precision).map_err(|_| too_large())?;
+
+    Ok(Value::String(T::format_decimal(unscaled, precision, scale)))
+}
+
+fn rescale(path: &str, t: i64, from: &TimeUnit, to: &TimeUnit) -> Result<i64, SourceError> {
+    let (from, to) = (nanos_per_unit(from), nanos_per_unit(to));
+    if from >= to {
+        t.checked_mul(from / to).ok_or_else(|| {
+            SourceError::bad_data(format!(
+                "timestamp {} for column '{}' is out of range",
+                t, path
+            ))
+        })
+    } else {
+        Ok(t.div_euclid(to / from))
+    }
+}
+
+/// Rewrites numeric strings written with a decimal comma (like `1.234,56`) into the form expected
+/// when they are parsed into a numeric column.
+pub(crate) fn normalize_numeric_string(
+    field: &Field,
+    value: &mut AvroValue,
+    separator: DecimalSeparator,
+) {
+    if separator == DecimalSeparator::Period || !field.data_type().is_numeric() {
+        return;
+    }
+
+    let value = match value {
+        Value::Union(_, inner) => inner.as_mut(),
+        v => v,
+    };
+
+    if let Value::String(s) = value {
+        *s = s
+            .trim()
+            .chars()
+            .filter(|c| !matches!(c, '.' | ' ' | '\u{a0}' | '\u{202f}'))
+            .map(|c| if c == ',' { '.' } else { c })
+            .collect();
+    }
+}
+
+/// Replaces int, long, float, double, and boolean values of string columns with their text, so that
+/// a union of those with strings can be decoded into a single string column.
+pub(crate) fn stringify_scalar(field: &Field, value: &mut AvroValue, enabled: bool) {
+    if !enabled || !matches!(field.data_type(), DataType::Utf8 | DataType::LargeUtf8) {
+        return;
+    }
+
+    let value = match value {
+        Value::Union(_, inner) => inner.as_mut(),
+        v => v,
+    };
+
+    let s = match value {
+        Value::Int(i) => i.to_string(),
+        Value::Long(i) => i.to_string(),
+        Value::Float(f) => f.to_string(),
+        Value::Double(f) => f.to_string(),
+        Value::Boolean(b) => b.to_string(),
+        _ => return,
+    };
+
+    *value = Value::String(s);
+}
+
+fn matches_sentinel(value: &AvroValue, sentinel: &str) -> bool {
+    match value {
+        Value::Int(i) => sentinel.parse::<i32>().ok() == Some(*i),
+        Value::Long(i) => sentinel.parse::<i64>().ok() == Some(*i),
+        Value::Float(f) => sentinel.parse::<f32>().ok() == Some(*f),
+        Value::Double(f) => sentinel.parse::<f64>().ok() == Some(*f),
+        Value::String(s) | Value::Enum(_, s) => s == sentinel,
+        Value::Date(days) => match NaiveDate::parse_from_str(sentinel, "%Y-%m-%d") {
+            Ok(date) => {
+                (date - NaiveDate::from_ymd_opt(1970, 1, 1).unwrap()).num_days() == *days as i64
+            }
+            Err(_) => sentinel.parse::<i32>().ok() == Some(*days),
+        },
+        _ => false,
+    }
+}
+
+/// Replaces a value with null if it matches the sentinel configured for its field, for
+/// producers that use values like `-1` or `9999-12-31` to mean null.
+pub(crate) fn apply_null_sentinel(
+    path: &str,
+    value: &mut AvroValue,
+    sentinels: &BTreeMap<String, String>,
+) {
+    let Some(sentinel) = sentinels.get(path) else {
+        return;
+    };
+
+    let inner = match value {
+        Value::Union(_, inner) => inner.as_ref(),
+        v => v,
+    };
+
+    if matches_sentinel(inner, sentinel) {
+        *value = Value::Null;
+    }
+}
+
+/// Runs the string value of a field through the transforms configured for it, in order.
+pub(crate) fn apply_field_transforms(
+    path: &str,
+    value: &mut AvroValue,
+    transforms: &BTreeMap<String, Vec<FieldTransform>>,
+) -> Result<(), SourceError> {
+    let Some(transforms) = transforms.get(path) else {
+        return Ok(());
+    };
+
+    let s = match value {
+        Value::Union(_, inner) => inner.as_mut(),
+        v => v,
+    };
+
+    let s = match s {
+        Value::String(s) => s,
+        Value::Null => return Ok(()),
+        v => {
+            return Err(SourceError::bad_data(format!(
+                "field transforms can only be applied to strings, but '{}' has value {:?}",
+                path, v
+            )));
+        }
+    };
+
+    for transform in transforms {
+        *s = match transform {
+            FieldTransform::Trim => s.trim().to_string(),
+            FieldTransform::Lowercase => s.to_lowercase(),
+            FieldTransform::Uppercase => s.to_uppercase(),
+            FieldTransform::Hash => format!("{:x}", Sha256::digest(s.as_bytes())),
+        };
+    }
+
+    Ok(())
+}
+
+/// Shifts a date or timestamp from the custom epoch configured for its field, given in seconds
+/// since the Unix epoch, to the Unix epoch. Offsets for dates are applied in whole days.
+pub(crate) fn apply_epoch_offset(
+    path: &str,
+    field: &Field,
+    value: &mut AvroValue,
+    offsets: &BTreeMap<String, i64>,
+) -> Result<(), SourceError> {
+    let Some(offset) = offsets.get(path).copied() else {
+        return Ok(());
+    };
+
+    let overflow = || {
+        SourceError::bad_data(format!(
+            "value for column '{}' is out of range after applying its epoch offset",
+            path
+        ))
+    };
+    let shift = |t: i64, units_per_second: i64| {
+        offset
+            .checked_mul(units_per_second)
+            .and_then(|o| t.checked_add(o))
+            .ok_or_else(overflow)
+    };
+    let shift_days = |days: i32| {
+        i32::try_from(days as i64 + offset.div_euclid(24 * 60 * 60)).map_err(|_| overflow())
+    };
+
+    let value = match value {
+        Value::Union(_, inner) => inner.as_mut(),
+        v => v,
+    };
+
+    match value {
+        Value::Date(days) => *days = shift_days(*days)?,
+        Value::Int(days) if field.data_type() == &DataType::Date32 => *days = shift_days(*days)?,
+        Value::TimestampMillis(t) | Value::LocalTimestampMillis(t) => *t = shift(*t, 1_000)?,
+        Value::TimestampMicros(t) | Value::LocalTimestampMicros(t) => *t = shift(*t, 1_000_000)?,
+        Value::Long(t) => {
+            if let DataType::Timestamp(unit, _) = field.data_type() {
+                let units_per_second = match unit {
+                    TimeUnit::Second => 1,
+                    TimeUnit::Millisecond => 1_000,
+                    TimeUnit::Microsecond => 1_000_000,
+                    TimeUnit::Nanosecond => 1_000_000_000,
+                };
+                *t = shift(*t, units_per_second)?;
+            }
+        }
+        _ => {}
+    }
+
+    Ok(())
+}
+
+/// Removes the entries of a map column (and of maps nested in its lists and map values) whose value
+/// is null when the policy is to drop them; otherwise they're kept, and decoded as entries with a
+/// null value.
+pub(crate) fn apply_map_null_values(
+    field: &Field,
+    value: &mut AvroValue,
+    policy: MapNullValuePolicy,
+) {
+    if policy == MapNullValuePolicy::Preserve {
+        return;
+    }
+
+    drop_null_map_values(field.data_type(), value);
+}
+
+fn drop_null_map_values(data_type: &DataType, value: &mut AvroValue) {
+    fn is_null(value: &AvroValue) -> bool {
+        match value {
+            Value::Null => true,
+            Value::Union(_, inner) => is_null(inner),
+            _ => false,
+        }
+    }
+
+    let inner = match value {
+        Value::Union(_, inner) => inner.as_mut(),
+        v => v,
+    };
+
+    match (data_type, inner) {
+        (DataType::Map(entries, _), Value::Map(map)) => {
+            map.retain(|_, v| !is_null(v));
+            if let DataType::Struct(fields) = entries.data_type() {
+                for v in map.values_mut() {
+                    drop_null_map_values(fields[1].data_type(), v);
+                }
+            }
+        }
+        (DataType::List(item) | DataType::LargeList(item), Value::Array(items)) => {
+            for v in items {
+                drop_null_map_values(item.data_type(), v);
+            }
+        }
+        _ => {}
+    }
+}
+
+/// The type that interval (month-day-nano) columns are decoded from JSON as, matching the
+/// components of an Avro duration.
+fn duration_struct() -> DataType {
+    DataType::Struct(Fields::from(vec![
+        Field::new("months", DataType::UInt32, true),
+        Field::new("days", DataType::UInt32, true),
+        Field::new("milliseconds", DataType::UInt32, true),
+    ]))
+}
+
+/// The field that holds the index of the Avro branch when a union column is decoded as a struct
+const UNION_TYPE_ID_FIELD: &str = "__type_id";
+
+/// Rewrites the value of a union column as a record holding the index of its Avro branch and the
+/// branch's value under the name of the union field with that type id, so that it can be decoded
+/// as a struct (see [`decoding_type`]) and then turned into a union array.
+pub(crate) fn tag_union_branch(
+    path: &str,
+    field: &Field,
+    value: &mut AvroValue,
+) -> Result<(), SourceError> {
+    let DataType::Union(fields, _) = field.data_type() else {
+        return Ok(());
+    };
+
+    let Value::Union(index, inner) = value else {
+        return Err(SourceError::bad_data(format!(
+            "expected a union value for column '{}'",
+            path
+        )));
+    };
+    let index = *index;
+
+    let Some((_, branch)) = fields.iter().find(|(id, _)| *id as i64 == index as i64) else {
+        return Err(SourceError::bad_data(format!(
+            "union branch {} for column '{}' has no corresponding field in {}",
+            index,
+            path,
+            field.data_type()
+        )));
+    };
+
+    let inner = std::mem::replace(inner.as_mut(), Value::Null);
+    *value = Value::Record(vec![
+        (UNION_TYPE_ID_FIELD.to_string(), Value::Int(index as i32)),
+        (branch.name().clone(), inner),
+    ]);
+
+    Ok(())
+}
+
+/// The fields that hold the symbol index and the symbol when a dictionary column of enum values
+/// is decoded as a struct
+const DICTIONARY_KEY_FIELD: &str = "__key";
+const DICTIONARY_VALUE_FIELD: &str = "__value";
+
+/// Rewrites the enum values of a dictionary column as a record of the symbol's index and the
+/// symbol, so that it can be decoded as a struct (see [`decoding_type`]) and then turned into a
+/// dictionary array keyed by the symbol index. Values that aren't enum symbols, or whose index
+/// doesn't fit in the dictionary's key type, are handled according to the policy.
+pub(crate) fn tag_enum_symbol(
+    path: &str,
+    field: &Field,
+    value: &mut AvroValue,
+    policy: UnknownEnumSymbolPolicy,
+) -> Result<(), SourceError> {
+    let DataType::Dictionary(key_type, value_type) = field.data_type() else {
+        return Ok(());
+    };
+    if **value_type != DataType::Utf8 {
+        return Ok(());
+    }
+
+    let value = match value {
+        Value::Union(_, inner) => inner.as_mut(),
+        v => v,
+    };
+
+    let (min, max) = integer_range(key_type).unwrap_or((i64::MIN, i64::MAX));
+
+    match value {
+        Value::Null => Ok(()),
+        Value::Enum(index, symbol) if (min..=max).contains(&(*index as i64)) => {
+            let record = Value::Record(vec![
+                (DICTIONARY_KEY_FIELD.to_string(), Value::Long(*index as i64)),
+                (
+                    DICTIONARY_VALUE_FIELD.to_string(),
+                    Value::String(std::mem::take(symbol)),
+                ),
+            ]);
+            *value = record;
+            Ok(())
+        }
+        _ => match policy {
+            UnknownEnumSymbolPolicy::Error => Err(SourceError::bad_data(format!(
+                "value {:?} for column '{}' is not an enum symbol that can be stored as {}",
+                value,
+                path,
+                field.data_type()
+            ))),
+            UnknownEnumSymbolPolicy::Null => {
+                *value = Value::Null;
+                Ok(())
+            }
+        },
+    }
+}
+
+fn decoding_type(data_type: &DataType) -> DataType {
+    match data_type {
+        DataType::Interval(IntervalUnit::MonthDayNano) => duration_struct(),
+        DataType::FixedSizeBinary(_) => DataType::Binary,
+        DataType::Dictionary(key_type, value_type) if **value_type == DataType::Utf8 => {
+            DataType::Struct(Fields::from(vec![
+                Field::new(DICTIONARY_KEY_FIELD, key_type.as_ref().clone(), true),
+                Field::new(DICTIONARY_VALUE_FIELD, DataType::Utf8, true),
+            ]))
+        }
+        DataType::Union(fields, _) => DataType::Struct(
+            std::iter::once(Field::new(UNION_TYPE_ID_FIELD, DataType::Int8, false))
+                .chain(fields.iter().map(|(_, f)| {
+                    f.as_ref()
+                        .clone()
+                        .with_data_type(decoding_type(f.data_type()))
+                        .with_nullable(true)
+                }))
+                .collect(),
+        ),
+        DataType::Struct(fields) => DataType::Struct(decoding_fields(fields)),
+        data_type => data_type.clone(),
+    }
+}
+
+/// Returns the fields that the JSON decoder should decode into, which differ from the schema's
+/// in that columns of types the JSON decoder doesn't support are decoded as a supported type:
+/// intervals, unions, and dictionaries as structs and fixed-size binary as binary. They're converted by
+/// [`build_struct_array`] once decoded.
+pub(crate) fn decoding_fields(fields: &Fields) -> Fields {
+    fields
+        .iter()
+        .map(|f| {
+            Arc::new(
+                f.as_ref()
+                    .clone()
+                    .with_data_type(decoding_type(f.data_type())),
+            )
+        })
+        .collect()
+}
+
+/// Converts a column decoded as a [`duration_struct`] into an interval column.
+fn duration_array(array: &StructArray) -> ArrayRef {
+    let months = array.column(0).as_primitive::<UInt32Type>();
+    let days = array.column(1).as_primitive::<UInt32Type>();
+    let millis = array.column(2).as_primitive::<UInt32Type>();
+
+    let intervals: IntervalMonthDayNanoArray = (0..array.len())
+        .map(|i| {
+            (array.is_valid(i) && months.is_valid(i) && days.is_valid(i) && millis.is_valid(i))
+                .then(|| {
+                    IntervalMonthDayNanoType::make_value(
+                        months.value(i) as i32,
+                        days.value(i) as i32,
+                        millis.value(i) as i64 * 1_000_000,
+                    )
+                })
+        })
+        .collect();
+
+    Arc::new(intervals)
+}
+
+/// Converts a column decoded as binary into a fixed-size binary column; values of the wrong size
+/// are null.
+fn fixed_size_binary_array(array: &BinaryArray, size: i32) -> ArrayRef {
+    let mut builder = FixedSizeBinaryBuilder::with_capacity(array.len(), size);
+    for value in array.iter() {
+        match value {
+            Some(v) if v.len() == size as usize => builder.append_value(v).unwrap(),
+            _ => builder.append_null(),
+        }
+    }
+
+    Arc::new(builder.finish())
+}
+
+/// Converts a column decoded as a struct of symbol index and symbol (see [`tag_enum_symbol`]) into
+/// a dictionary array keyed by the symbol index. Each index's entry in the dictionary is the
+/// symbol first seen with it, and indices that no row has are null. Only enum symbols get this
+/// far, so the dictionary can't outgrow the enum however many distinct values the column has;
+/// there's no need to fall back to a plain string column (which the batch's schema wouldn't
+/// allow anyway).
+fn dictionary_array(array: &StructArray, data_type: &DataType) -> ArrayRef {
+    let keys = array.column(0);
+    let symbols = array.column(1).as_string::<i32>();
+    let indices = cast(keys, &DataType::Int64).expect("dictionary keys should be integers");
+    let indices = indices.as_primitive::<Int64Type>();
+
+    let mut values: Vec<Option<&str>> = vec![];
+    for i in 0..array.len() {
+        if array.is_valid(i) && indices.is_valid(i) && symbols.is_valid(i) {
+            let index = indices.value(i) as usize;
+            if values.len() <= index {
+                values.resize(index + 1, None);
+            }
+            values[index].get_or_insert(symbols.value(i));
+        }
+    }
+
+    let mut dictionary =
+        StringBuilder::with_capacity(values.len(), values.iter().flatten().map(|v| v.len()).sum());
+    dictionary.extend(values);
+
+    let keys = keys.to_data();
+    let data = ArrayDataBuilder::new(data_type.clone())
+        .len(keys.len())
+        .offset(keys.offset())
+        .buffers(keys.buffers().to_vec())
+        .nulls(NullBuffer::union(keys.nulls(), array.nulls()))
+        .child_data(vec![dictionary.finish().to_data()])
+        .build()
+        .expect("dictionary keys should be within its values");
+
+    make_array(data)
+}
+
+/// Converts a column decoded as a struct of the branch index and one field per branch (see
+/// [`tag_union_branch`]) into a union array. For dense unions, each child only keeps the rows of
+/// its own branch.
+fn union_array(array: &StructArray, fields: &UnionFields, mode: UnionMode, len: usize) -> ArrayRef {
+    let type_ids: Vec<i8> = array.column(0).as_primitive::<Int8Type>().values().to_vec();
+
+    let mut children: Vec<ArrayRef> = fields
+        .iter()
+        .zip(&array.columns()[1..])
+        .map(|((_, f), c)| build_struct_array(c, f.data_type(), len))
+        .collect();
+
+    let offsets = match mode {
+        UnionMode::Sparse => None,
+        UnionMode::Dense => {
+            let branches: Vec<usize> = type_ids
+                .iter()
+                .map(|type_id| {
+                    fields
+                        .iter()
+                        .position(|(id, _)| id == *type_id)
+                        .expect("union branch should have a field")
+                })
+                .collect();
+
+            // size each child's row list up front, so that large batches don't repeatedly regrow
+            let mut counts = vec![0; children.len()];
+            for child in &branches {
+                counts[*child] += 1;
+            }
+            let mut rows: Vec<Vec<u32>> = counts.into_iter().map(Vec::with_capacity).collect();
+
+            let mut offsets = Vec::with_capacity(type_ids.len());
+            for (row, child) in branches.into_iter().enumerate() {
+                offsets.push(rows[child].len() as i32);
+                rows[child].push(row as u32);
+            }
+
+            children = children
+                .iter()
+                .zip(rows)
+                .map(|(c, rows)| take(c, &UInt32Array::from(rows), None).unwrap())
+                .collect();
+            Some(Buffer::from_vec(offsets))
+        }
+    };
+
+    let field_type_ids: Vec<i8> = fields.iter().map(|(id, _)| id).collect();
+    let child_arrays = fields
+        .iter()
+        .map(|(_, f)| f.as_ref().clone())
+        .zip(children)
+        .collect();
+
+    Arc::new(
+        UnionArray::try_new(
+            &field_type_ids,
+            Buffer::from_vec(type_ids),
+            offsets,
+            child_arrays,
+        )
+        .expect("union children should match the union's fields"),
+    )
+}
+
+/// Rebuilds a (possibly nested) struct column as a struct array of `len` rows with the given
+/// type. A struct array normally takes its length from its first child, which a record with no
+/// fields doesn't have, so an empty record would otherwise decode as a zero-length column; here
+/// the length is set explicitly, and the null bitmap carried over so that null records remain
+/// null. Columns that were decoded as a different type (see [`decoding_fields`]) are converted
+/// to the given one.
+pub(crate) fn build_struct_array(array: &ArrayRef, data_type: &DataType, len: usize) -> ArrayRef {
+    let fields = match (array.data_type(), data_type) {
+        (DataType::Struct(_), DataType::Interval(IntervalUnit::MonthDayNano)) => {
+            return duration_array(array.as_struct())
+        }
+        (DataType::Binary, DataType::FixedSizeBinary(size)) => {
+            return fixed_size_binary_array(array.as_binary(), *size)
+        }
+        (DataType::Struct(_), DataType::Dictionary(_, _)) => {
+            return dictionary_array(array.as_struct(), data_type)
+        }
+        (DataType::Struct(_), DataType::Union(fields, mode)) => {
+            return union_array(array.as_struct(), fields, *mode, len)
+        }
+        (DataType::Struct(_), DataType::Struct(fields)) => fields,
+        _ => return array.clone(),
+    };
+    let array = array.as_struct();
+
+    let child_data: Vec<ArrayData> = array
+        .columns()
+        .iter()
+        .zip(fields.iter())
+        .map(|(c, f)| build_struct_array(c, f.data_type(), len).to_data())
+        .collect();
+
+    let data = ArrayDataBuilder::new(DataType::Struct(fields.clone()))
+        .len(len)
+        .nulls(array.nulls().filter(|n| n.len() == len).cloned())
+        .child_data(child_data)
+        .build()
+        .expect("struct children should have the same length as the struct");
+
+    make_array(data)
+}
+
+fn convert_float(f: f64) -> JsonValue {
+    match serde_json::Number::from_f64(f) {
+        Some(n) => JsonValue::Number(n),
+        None => JsonValue::String(
+            (if f.is_infinite() && f.is_sign_positive() {
+                "+Inf"
+            } else if f.is_infinite() {
+                "-Inf"
+            } else {
+                "NaN"
+            })
+            .to_string(),
+        ),
+    }
+}
+
+fn encode_vec(v: Vec<u8>) -> JsonValue {
+    JsonValue::String(v.into_iter().map(char::from).collect())
+}
+
+/// Estimates the in-memory size of a decoded value, counting fixed-width values by their width
+/// and variable-width values by the length of their data.
+pub(crate) fn estimated_size(value: &AvroValue) -> usize {
+    match value {
+        Value::Null => 0,
+        Value::Boolean(_) => 1,
+        Value::Int(_) | Value::Date(_) | Value::TimeMillis(_) | Value::Float(_) => 4,
+        Value::Long(_)
+        | Value::TimeMicros(_)
+        | Value::TimestampMillis(_)
+        | Value::TimestampMicros(_)
+        | Value::LocalTimestampMillis(_)
+        | Value::LocalTimestampMicros(_)
+        | Value::Double(_) => 8,
+        Value::Duration(_) => 12,
+        Value::Decimal(_) | Value::Uuid(_) => 16,
+        Value::String(s) | Value::Enum(_, s) => s.len(),
+        Value::Bytes(b) | Value::Fixed(_, b) => b.len(),
+        Value::Union(_, v) => estimated_size(v),
+        Value::Array(a) => a.iter().map(estimated_size).sum(),
+        Value::Map(m) => m.iter().map(|(k, v)| k.len() + estimated_size(v)).sum(),
+        Value::Record(rec) => rec.iter().map(|(_, v)| estimated_size(v)).sum(),
+    }
+}
+
+pub(crate) fn avro_to_json(value: AvroValue) -> JsonValue {
+    match value {
+        Value::Null => JsonValue::Null,
+        Value::Boolean(b) => JsonValue::Bool(b),
+        Value::Int(i) | Value::Date(i) | Value::TimeMillis(i) => {
+            JsonValue::Number(serde_json::Number::from(i))
+        }
+        Value::Long(i)
+        | Value::TimeMicros(i)
+        | Value::TimestampMillis(i)
+        | Value::TimestampMicros(i)
+        | Value::LocalTimestampMillis(i)
+        | Value::LocalTimestampMicros(i) => JsonValue::Number(serde_json::Number::from(i)),
+        Value::Float(f) => convert_float(f as f64),
+        Value::Double(f) => convert_float(f),
+        Value::String(s) | Value::Enum(_, s) => JsonValue::String(s),
+        // this isn't the standard Avro json encoding, which just
+        Value::Bytes(b) | Value::Fixed(_, b) => encode_vec(b),
+        Value::Union(_, b) => avro_to_json(*b),
+        Value::Array(a) => JsonValue::Array(a.into_iter().map(avro_to_json).collect()),
+        Value::Map(m) => {
+            JsonValue::Object(m.into_iter().map(|(k, v)| (k, avro_to_json(v))).collect())
+        }
+        Value::Record(rec) => {
+            JsonValue::Object(rec.into_iter().map(|(k, v)| (k, avro_to_json(v))).collect())
+        }
+
+        Value::Decimal(d) => {
+            let b: Vec<u8> = d.try_into().unwrap_or_else(|_| vec![]);
+            encode_vec(b)
+        }
+        Value::Duration(d) => {
+            json!({
+               "months": u32::from(d.months()),
+               "days": u32::from(d.days()),
+               "milliseconds": u32::from(d.millis())
+            })
+        }
+        Value::Uuid(u) => JsonValue::String(u.to_string()),
+    }
+}
+
+/// Like [`avro_to_json`], but records the time spent converting each top-level field of a record,
+/// accumulated into `timings` by field name.
+pub(crate) fn avro_to_json_timed(
+    value: AvroValue,
+    timings: &mut HashMap<String, Duration>,
+) -> JsonValue {
+    match value {
+        Value::Record(rec) => JsonValue::Object(
+            rec.into_iter()
+                .map(|(k, v)| {
+                    let start = Instant::now();
+                    let json = avro_to_json(v);
+                    *timings.entry(k.clone()).or_default() += start.elapsed();
+                    (k, json)
+                })
+                .collect(),
+        ),
+        Value::Union(_, b) => avro_to_json_timed(*b, timings),
+        value => avro_to_json(value),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::avro::de::{
+        apply_epoch_offset, apply_field_transforms, apply_logical_type_mapping,
+        apply_null_sentinel, avro_to_json, check_decimal_finite, check_float_precision,
+        check_integer_range, check_required_fields, field_lookup, for_each_field,
+        limit_array_lengths, limit_nesting_depth, normalize_field_names,
+    };
+    use crate::avro::schema::{to_arrow, to_arrow_with_mappings, to_avro};
+    use crate::avro::ser::serialize;
+    use crate::de::ArrowDeserializer;
+    use apache_avro::types::Value;
+    use arrow::datatypes::i256;
+    use arrow_array::builder::{make_builder, ArrayBuilder};
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::IntervalMonthDayNanoType;
+    use arrow_array::{Array, RecordBatch, UnionArray};
+    use arrow_schema::{
+        DataType, Field, Fields, IntervalUnit, Schema, TimeUnit, UnionFields, UnionMode,
+    };
+    use arroyo_rpc::df::ArroyoSchema;
+    use arroyo_rpc::formats::{
+        AvroFormat, BadData, DecimalSeparator, FieldNameCollisionPolicy, FieldTransform, Format,
+        IntegerOverflowPolicy, LogicalTypeMapping, MapNullValuePolicy, NestingOverflowPolicy,
+        NonFiniteDecimalPolicy, OutOfRangeTimePolicy, OversizedArrayPolicy, PrecisionLossPolicy,
+        SerializableAvroSchema, UnframedMessagePolicy, UnknownEnumSymbolPolicy,
+    };
+    use arroyo_rpc::schema_resolver::{FailingSchemaResolver, FixedSchemaResolver, SchemaResolver};
+    use arroyo_types::SourceError;
+    use serde_json::json;
+    use std::collections::{BTreeMap, HashMap};
+    use std::sync::Arc;
+    use std::time::SystemTime;
+
+    const SCHEMA: &str = r#"
+        {
+  "connect.name": "pizza_orders.pizza_orders",
+  "fields": [
+    {
+      "name": "store_id",
+      "type": "int"
+    },
+    {
+      "name": "store_order_id",
+      "type": "int"
+    },
+    {
+      "name": "coupon_code",
+      "type": "int"
+    },
+    {
+      "name": "date",
+      "type": {
+        "connect.name": "org.apache.kafka.connect.data.Date",
+        "connect.version": 1,
+        "logicalType": "date",
+        "type": "int"
+      }
+    },
+    {
+      "name": "status",
+      "type": "string"
+    },
+    {
+      "name": "order_lines",
+      "type": {
+        "items": {
+          "connect.name": "pizza_orders.order_line",
+          "fields": [
+            {
+              "name": "product_id",
+              "type": "int"
+            },
+            {
+              "name": "category",
+              "type": "string"
+            },
+            {
+              "name": "quantity",
+              "type": "int"
+            },
+            {
+              "name": "unit_price",
+              "type": "double"
+            },
+            {
+              "name": "net_price",
+              "type": "double"
+            }
+          ],
+          "name": "order_line",
+          "type": "record"
+        },
+        "type": "array"
+      }
+    }
+  ],
+  "name": "pizza_orders",
+  "namespace": "pizza_orders",
+  "type": "record"
+}"#;
+
+    fn deserializer_with_schema(
+        format: AvroFormat,
+        writer_schema: Option<&str>,
+    ) -> (ArrowDeserializer, Vec<Box<dyn ArrayBuilder>>, ArroyoSchema) {
+        let arrow_schema = if format.into_unstructured_json {
+            Schema::new(vec![Field::new("value", DataType::Utf8, false)])
+        } else {
+            to_arrow(
+                &format
+                    .reader_schema
+                    .as_ref()
+                    .expect("no reader schema")
+                    .0
+                    .canonical_form(),
+            )
+            .expect("invalid schema")
+        };
+
+        let arroyo_schema = {
+            let mut fields = arrow_schema.fields.to_vec();
+            fields.push(Arc::new(Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            )));
+            ArroyoSchema::from_schema_keys(Arc::new(Schema::new(fields)), vec![]).unwrap()
+        };
+
+        let builders: Vec<_> = arroyo_schema
+            .schema
+            .fields
+            .iter()
+            .map(|f| make_builder(f.data_type(), 8))
+            .collect();
+
+        let resolver: Arc<dyn SchemaResolver + Sync> = if let Some(schema) = &writer_schema {
+            Arc::new(FixedSchemaResolver::new(
+                if format.confluent_schema_registry {
+                    1
+                } else {
+                    0
+                },
+                apache_avro::Schema::parse_str(schema).unwrap(),
+            ))
+        } else {
+            Arc::new(FailingSchemaResolver::new())
+        };
+
+        (
+            ArrowDeserializer::with_schema_resolver(
+                Format::Avro(format),
+                None,
+                arroyo_schema.clone(),
+                BadData::Fail {},
+                resolver,
+            ),
+            builders,
+            arroyo_schema,
+        )
+    }
+
+    async fn deserialize_with_schema(
+        format: AvroFormat,
+        writer_schema: Option<&str>,
+        message: &[u8],
+    ) -> Vec<serde_json::Map<String, serde_json::Value>> {
+        let (mut deserializer, mut builders, arroyo_schema) =
+            deserializer_with_schema(format.clone(), writer_schema);
+
+        let errors = deserializer
+            .deserialize_slice(&mut builders, message, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        let batch = if format.into_unstructured_json {
+            RecordBatch::try_new(
+                arroyo_schema.schema,
+                builders.into_iter().map(|mut b| b.finish()).collect(),
+            )
+            .unwrap()
+        } else {
+            deserializer.flush_buffer().unwrap().unwrap()
+        };
+
+        #[allow(deprecated)]
+        arrow_json::writer::record_batches_to_json_rows(&[&batch])
+            .unwrap()
+            .into_iter()
+            .map(|mut r| {
+                r.remove("_timestamp");
+                r
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_avro_deserialization() {
+        let message = [
+            0u8, 0, 0, 0, 1, 8, 200, 223, 1, 144, 31, 186, 159, 2, 16, 97, 99, 99, 101, 112, 116,
+            101, 100, 4, 156, 1, 10, 112, 105, 122, 122, 97, 4, 102, 102, 102, 102, 102, 230, 38,
+            64, 102, 102, 102, 102, 102, 230, 54, 64, 84, 14, 100, 101, 115, 115, 101, 114, 116, 2,
+            113, 61, 10, 215, 163, 112, 26, 64, 113, 61, 10, 215, 163, 112, 26, 64, 0, 10,
+        ];
+
+        let mut format = AvroFormat::new(true, false, false);
+        format.add_reader_schema(apache_avro::Schema::parse_str(SCHEMA).unwrap());
+        let row = deserialize_with_schema(format, Some(SCHEMA), &message)
+            .await
+            .remove(0);
+        assert_eq!(*row.get("store_id").unwrap(), json!(4));
+        assert_eq!(*row.get("store_order_id").unwrap(), json!(14308));
+        assert_eq!(*row.get("coupon_code").unwrap(), json!(1992));
+        assert_eq!(*row.get("date").unwrap(), json!(18397));
+        assert_eq!(*row.get("status").unwrap(), json!("accepted"));
+
+        let order_line_expected = json!(
+            [{"category":"pizza","net_price":22.9,"product_id":78,"quantity":2,"unit_price":11.45},{"category":"dessert","net_price":6.61,"product_id":42,"quantity":1,"unit_price":6.61}]
+        );
+
+        assert_eq!(*row.get("order_lines").unwrap(), order_line_expected);
+    }
+
+    #[tokio::test]
+    async fn test_reader_schema_resolution() {
+        let writer_schema = r#"{"type": "record", "name": "User", "fields": [
+            {"name": "name", "type": "string"},
+            {"name": "visits", "type": "int"},
+            {"name": "legacy", "type": "string"}
+        ]}"#;
+        // adds a field with a default, drops one, and promotes int to long
+        let reader_schema = r#"{"type": "record", "name": "User", "fields": [
+            {"name": "name", "type": "string"},
+            {"name": "visits", "type": "long"},
+            {"name": "country", "type": "string", "default": "unknown"}
+        ]}"#;
+
+        let mut datum = vec![0, 0, 0, 0, 1];
+        datum.extend(
+            apache_avro::to_avro_datum(
+                &apache_avro::Schema::parse_str(writer_schema).unwrap(),
+                Value::Record(vec![
+                    ("name".to_string(), Value::String("ada".to_string())),
+                    ("visits".to_string(), Value::Int(3)),
+                    ("legacy".to_string(), Value::String("x".to_string())),
+                ]),
+            )
+            .unwrap(),
+        );
+
+        let mut format = AvroFormat::new(true, false, false);
+        format.add_reader_schema(apache_avro::Schema::parse_str(reader_schema).unwrap());
+        let vs = deserialize_with_schema(format, Some(writer_schema), &datum).await;
+        assert_eq!(
+            serde_json::Value::Object(vs[0].clone()),
+            json!({"name": "ada", "visits": 3, "country": "unknown"})
+        );
+
+        // a type change that can't be resolved is reported as bad data
+        let incompatible = r#"{"type": "record", "name": "User", "fields": [
+            {"name": "name", "type": "long"}
+        ]}"#;
+        let mut format = AvroFormat::new(true, false, false);
+        format.add_reader_schema(apache_avro::Schema::parse_str(incompatible).unwrap());
+        let (mut deserializer, mut builders, _) =
+            deserializer_with_schema(format, Some(writer_schema));
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &datum, SystemTime::now())
+            .await;
+        assert_eq!(errors.len(), 1);
+        assert!(matches!(errors[0], SourceError::BadData { .. }));
+    }
+
+    #[tokio::test]
+    async fn test_add_field() {
+        // test schema evolution for adding a field (new_field)
+        let reader_schema = r#"{"namespace": "example.avro",
+            "type": "record",
+            "name": "User",
+            "fields": [
+                {"name": "name", "type": "string"},
+                {"name": "favorite_number", "type": "int"},
+                {"name": "favorite_color", "type": ["string", "null"]}
+            ]
+        }"#;
+
+        let writer_schema = r#"{"namespace": "example.avro",
+            "type": "record",
+            "name": "User",
+            "fields": [
+            {"name": "name", "type": "string"},
+            {"name": "favorite_number", "type": "int"},
+            {"name": "favorite_color", "type": ["string", "null"]},
+            {"name": "new_field", "type": "string", "default": "hello!"}
+            ]
+        }"#;
+
+        let mut format = AvroFormat::new(true, false, false);
+        format.add_reader_schema(apache_avro::Schema::parse_str(reader_schema).unwrap());
+
+        let schema = apache_avro::Schema::parse_str(writer_schema).unwrap();
+        let mut value = apache_avro::types::Record::new(&schema).unwrap();
+        value.put(
+            "name",
+            apache_avro::types::Value::String("Alyssa".to_string()),
+        );
+        value.put("favorite_number", apache_avro::types::Value::Int(256));
+        value.put(
+            "favorite_color",
+            apache_avro::types::Value::Union(1, Box::new(apache_avro::types::Value::Null)),
+        );
+        value.put(
+            "new_field",
+            apache_avro::types::Value::String("new".to_string()),
+        );
+
+        let mut bytes = vec![0, 0, 0, 0, 1];
+        bytes.extend_from_slice(
+            &apache_avro::to_avro_datum(
+                &apache_avro::Schema::parse_str(writer_schema).unwrap(),
+                value,
+            )
+            .unwrap(),
+        );
+
+        let v = deserialize_with_schema(format, Some(writer_schema), &bytes[..]).await;
+        assert_eq!(
+            serde_json::to_value(v).unwrap(),
+            json!([{
+                "name": "Alyssa",
+                "favorite_number": 256,
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_remove_field() {
+        // test schema evolution for removing a field with a default value
+        let writer_schema = r#"{"namespace": "example.avro",
+            "type": "record",
+            "name": "User",
+            "fields": [
+            {"name": "name", "type": "string"},
+            {"name": "favorite_number", "type": "int"},
+            {"name": "favorite_color", "type": ["string", "null"]}
+            ]
+        }"#;
+
+        let reader_schema = r#"{"namespace": "example.avro",
+            "type": "record",
+            "name": "User",
+            "fields": [
+                {"name": "name", "type": "string"},
+                {"name": "favorite_number", "type": "int"},
+                {"name": "favorite_color", "type": ["string", "null"]},
+                {"name": "removed_field", "type": "string", "default": "hello!"}
+            ]
+        }"#;
+
+        let schema = apache_avro::Schema::parse_str(writer_schema).unwrap();
+        let mut value = apache_avro::types::Record::new(&schema).unwrap();
+        value.put(
+            "name",
+            apache_avro::types::Value::String("Alyssa".to_string()),
+        );
+        value.put("favorite_number", apache_avro::types::Value::Int(256));
+
+        let mut bytes = vec![0, 0, 0, 0, 1];
+        bytes.extend_from_slice(
+            &apache_avro::to_avro_datum(
+                &apache_avro::Schema::parse_str(writer_schema).unwrap(),
+                value,
+            )
+            .unwrap(),
+        );
+
+        let mut format = AvroFormat::new(true, false, false);
+        format.add_reader_schema(apache_avro::Schema::parse_str(reader_schema).unwrap());
+
+        let v = deserialize_with_schema(format, Some(writer_schema), bytes.as_slice()).await;
+        assert_eq!(
+            serde_json::to_value(v).unwrap(),
+            json!([{
+                "name": "Alyssa",
+                "favorite_number": 256,
+                "removed_field": "hello!"
+            }])
+        );
+    }
+
+    #[tokio::test]
+    async fn test_embedded() {
+        let data = [
+            79u8, 98, 106, 1, 4, 20, 97, 118, 114, 111, 46, 99, 111, 100, 101, 99, 8, 110, 117,
+            108, 108, 22, 97, 118, 114, 111, 46, 115, 99, 104, 101, 109, 97, 186, 3, 123, 34, 116,
+            121, 112, 101, 34, 58, 32, 34, 114, 101, 99, 111, 114, 100, 34, 44, 32, 34, 110, 97,
+            109, 101, 34, 58, 32, 34, 85, 115, 101, 114, 34, 44, 32, 34, 110, 97, 109, 101, 115,
+            112, 97, 99, 101, 34, 58, 32, 34, 101, 120, 97, 109, 112, 108, 101, 46, 97, 118, 114,
+            111, 34, 44, 32, 34, 102, 105, 101, 108, 100, 115, 34, 58, 32, 91, 123, 34, 116, 121,
+            112, 101, 34, 58, 32, 34, 115, 116, 114, 105, 110, 103, 34, 44, 32, 34, 110, 97, 109,
+            101, 34, 58, 32, 34, 110, 97, 109, 101, 34, 125, 44, 32, 123, 34, 116, 121, 112, 101,
+            34, 58, 32, 91, 34, 105, 110, 116, 34, 44, 32, 34, 110, 117, 108, 108, 34, 93, 44, 32,
+            34, 110, 97, 109, 101, 34, 58, 32, 34, 102, 97, 118, 111, 114, 105, 116, 101, 95, 110,
+            117, 109, 98, 101, 114, 34, 125, 44, 32, 123, 34, 116, 121, 112, 101, 34, 58, 32, 91,
+            34, 115, 116, 114, 105, 110, 103, 34, 44, 32, 34, 110, 117, 108, 108, 34, 93, 44, 32,
+            34, 110, 97, 109, 101, 34, 58, 32, 34, 102, 97, 118, 111, 114, 105, 116, 101, 95, 99,
+            111, 108, 111, 114, 34, 125, 93, 125, 0, 52, 104, 70, 176, 108, 101, 199, 71, 44, 76,
+            126, 49, 211, 19, 204, 87, 4, 44, 12, 65, 108, 121, 115, 115, 97, 0, 128, 4, 2, 6, 66,
+            101, 110, 0, 14, 0, 6, 114, 101, 100, 52, 104, 70, 176, 108, 101, 199, 71, 44, 76, 126,
+            49, 211, 19, 204, 87,
+        ];
+
+        let format = AvroFormat::new(false, false, true);
+        let vs = deserialize_with_schema(format, None, &data).await;
+
+        let expected = vec![
+            json!({ "name": "Alyssa", "favorite_number": 256, "favorite_color": null }),
+            json!({ "name": "Ben", "favorite_number": 7, "favorite_color": "red" }),
+        ];
+
+        for (a, b) in vs.iter().zip(expected) {
+            let v: serde_json::Value =
+                serde_json::from_str(a.get("value").unwrap().as_str().unwrap()).unwrap();
+            assert_eq!(v, b);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_container_file_blocks() {
+        let writer_schema = r#"{"type": "record", "name": "User", "fields": [
+            {"name": "name", "type": "string"}
+        ]}"#;
+        let reader_schema = r#"{"type": "record", "name": "User", "fields": [
+            {"name": "name", "type": "string"},
+            {"name": "visits", "type": "long", "default": 0}
+        ]}"#;
+
+        // each flush ends a block, so the file has a block (and sync marker) per record
+        let schema = apache_avro::Schema::parse_str(writer_schema).unwrap();
+        let mut writer = apache_avro::Writer::new(&schema, vec![]);
+        for name in ["alice", "bob", "carol"] {
+            writer
+                .append(Value::Record(vec![(
+                    "name".to_string(),
+                    Value::String(name.to_string()),
+                )]))
+                .unwrap();
+            writer.flush().unwrap();
+        }
+        let data = writer.into_inner().unwrap();
+        assert_eq!(&data[..4], b"Obj\x01");
+
+        let mut format = AvroFormat::new(false, false, false);
+        format.add_reader_schema(apache_avro::Schema::parse_str(reader_schema).unwrap());
+        let vs = deserialize_with_schema(format, None, &data).await;
+
+        assert_eq!(
+            vs.into_iter()
+                .map(serde_json::Value::Object)
+                .collect::<Vec<_>>(),
+            vec![
+                json!({"name": "alice", "visits": 0}),
+                json!({"name": "bob", "visits": 0}),
+                json!({"name": "carol", "visits": 0}),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_deflate_container_file() {
+        let schema = r#"{"type": "record", "name": "User", "fields": [
+            {"name": "name", "type": "string"}
+        ]}"#;
+
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+        let mut writer =
+            apache_avro::Writer::with_codec(&avro_schema, vec![], apache_avro::Codec::Deflate);
+        for name in ["alice", "bob"] {
+            writer
+                .append(Value::Record(vec![(
+                    "name".to_string(),
+                    Value::String(name.to_string()),
+                )]))
+                .unwrap();
+        }
+        let data = writer.into_inner().unwrap();
+        assert!(data.windows(7).any(|w| w == b"deflate"));
+
+        let mut format = AvroFormat::new(false, false, false);
+        format.add_reader_schema(avro_schema);
+        let vs = deserialize_with_schema(format, None, &data).await;
+
+        assert_eq!(
+            vs.into_iter()
+                .map(serde_json::Value::Object)
+                .collect::<Vec<_>>(),
+            vec![json!({"name": "alice"}), json!({"name": "bob"})]
+        );
+    }
+
+    fn snappy_container_file() -> Vec<u8> {
+        let schema = apache_avro::Schema::parse_str(
+            r#"{"type": "record", "name": "User", "fields": [{"name": "name", "type": "string"}]}"#,
+        )
+        .unwrap();
+        let mut writer =
+            apache_avro::Writer::with_codec(&schema, vec![], apache_avro::Codec::Snappy);
+        for name in ["alice", "bob"] {
+            writer
+                .append(Value::Record(vec![(
+                    "name".to_string(),
+                    Value::String(name.to_string()),
+                )]))
+                .unwrap();
+        }
+        writer.into_inner().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_snappy_container_file() {
+        let data = snappy_container_file();
+        assert!(data.windows(6).any(|w| w == b"snappy"));
+
+        let vs = deserialize_with_schema(AvroFormat::new(false, false, true), None, &data).await;
+        let names: Vec<serde_json::Value> = vs
+            .iter()
+            .map(|v| serde_json::from_str(v.get("value").unwrap().as_str().unwrap()).unwrap())
+            .collect();
+        assert_eq!(
+            names,
+            vec![json!({"name": "alice"}), json!({"name": "bob"})]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_snappy_crc_mismatch() {
+        // a block ends with the CRC32 of its uncompressed data, followed by the 16-byte sync
+        // marker
+        let mut data = snappy_container_file();
+        let crc = data.len() - 17;
+        data[crc] ^= 0xff;
+
+        let (mut deserializer, mut builders, _) =
+            deserializer_with_schema(AvroFormat::new(false, false, true), None);
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &data, SystemTime::now())
+            .await;
+
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].details().contains("SnappyCrc32"), "{:?}", errors);
+    }
+
+    #[tokio::test]
+    async fn test_zstd_container_file() {
+        let schema = r#"{"type": "record", "name": "Event", "fields": [
+            {"name": "id", "type": "long"}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let write = |codec| {
+            let mut writer = apache_avro::Writer::with_codec(&avro_schema, vec![], codec);
+            for id in 0..1000 {
+                writer
+                    .append(Value::Record(vec![("id".to_string(), Value::Long(id))]))
+                    .unwrap();
+                if id % 100 == 99 {
+                    writer.flush().unwrap();
+                }
+            }
+            writer.into_inner().unwrap()
+        };
+
+        let zstd = write(apache_avro::Codec::Zstandard);
+        assert!(zstd.windows(9).any(|w| w == b"zstandard"));
+
+        let mut format = AvroFormat::new(false, false, false);
+        format.add_reader_schema(avro_schema.clone());
+        let plain =
+            deserialize_with_schema(format.clone(), None, &write(apache_avro::Codec::Null)).await;
+        let compressed = deserialize_with_schema(format, None, &zstd).await;
+
+        assert_eq!(compressed.len(), 1000);
+        assert_eq!(compressed, plain);
+    }
+
+    #[tokio::test]
+    async fn test_confluent_wire_format() {
+        let schema = r#"{"type": "record", "name": "User", "fields": [
+            {"name": "name", "type": "string"}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(true, false, false);
+        format.add_reader_schema(avro_schema.clone());
+
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer = ArrowDeserializer::with_schema_resolver(
+            Format::Avro(format),
+            None,
+            arroyo_schema,
+            BadData::Fail {},
+            Arc::new(FixedSchemaResolver::new(7, avro_schema.clone())),
+        );
+        let mut builders = vec![];
+
+        // magic byte, then the schema id as a big-endian u32, then the datum
+        let mut frame = vec![0, 0, 0, 0, 7];
+        frame.extend(
+            apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![("name".to_string(), Value::String("ada".to_string()))]),
+            )
+            .unwrap(),
+        );
+
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &frame, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        frame[0] = 1;
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &frame, SystemTime::now())
+            .await;
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0]
+            .details()
+            .contains("magic byte has unexpected value: 1"));
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(batch.column(0).as_string::<i32>().value(0), "ada");
+    }
+
+    /// Resolves every id to the same schema, counting the lookups
+    struct CountingSchemaResolver {
+        schema: String,
+        calls: std::sync::atomic::AtomicUsize,
+    }
+
+    #[async_trait::async_trait]
+    impl SchemaResolver for CountingSchemaResolver {
+        async fn resolve_schema(&self, _id: u32) -> Result<Option<String>, String> {
+            self.calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            Ok(Some(self.schema.clone()))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_schema_registry_cache() {
+        let schema = r#"{"type": "record", "name": "User", "fields": [
+            {"name": "name", "type": "string"}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let resolver = Arc::new(CountingSchemaResolver {
+            schema: avro_schema.canonical_form(),
+            calls: Default::default(),
+        });
+
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("name", DataType::Utf8, false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer = ArrowDeserializer::with_schema_resolver(
+            Format::Avro(AvroFormat::new(true, false, false)),
+            None,
+            arroyo_schema,
+            BadData::Fail {},
+            resolver.clone(),
+        );
+        let mut builders = vec![];
+
+        for (id, name) in [(1, "a"), (1, "b"), (2, "c"), (1, "d"), (2, "e")] {
+            let mut frame = vec![0];
+            frame.extend(u32::to_be_bytes(id));
+            frame.extend(
+                apache_avro::to_avro_datum(
+                    &avro_schema,
+                    Value::Record(vec![("name".to_string(), Value::String(name.to_string()))]),
+                )
+                .unwrap(),
+            );
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &frame, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        // one lookup per schema id
+        assert_eq!(resolver.calls.load(std::sync::atomic::Ordering::SeqCst), 2);
+        assert_eq!(deserializer.flush_buffer().unwrap().unwrap().num_rows(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_deserialize_avro_reader() {
+        let schema = r#"{"type": "record", "name": "Event", "fields": [
+            {"name": "id", "type": "long"}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut stream = vec![];
+        for id in 0..5 {
+            stream.extend(
+                apache_avro::to_avro_datum(
+                    &avro_schema,
+                    Value::Record(vec![("id".to_string(), Value::Long(id))]),
+                )
+                .unwrap(),
+            );
+        }
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema);
+        let (mut deserializer, mut builders, _) = deserializer_with_schema(format, Some(schema));
+
+        let (batches, errors) = deserializer
+            .deserialize_avro_reader(
+                &mut builders,
+                std::io::Cursor::new(stream),
+                2,
+                SystemTime::now(),
+            )
+            .await;
+        assert_eq!(errors, vec![]);
+
+        let ids = |batch: &RecordBatch| {
+            batch
+                .column(0)
+                .as_primitive::<arrow_array::types::Int64Type>()
+                .values()
+                .to_vec()
+        };
+        assert_eq!(
+            batches.iter().map(ids).collect::<Vec<_>>(),
+            vec![vec![0, 1], vec![2, 3]]
+        );
+
+        // the last row stays buffered until the next flush
+        assert_eq!(ids(&deserializer.flush_buffer().unwrap().unwrap()), vec![4]);
+    }
+
+    #[tokio::test]
+    async fn test_single_object_encoding() {
+        let schema = r#"{"type": "record", "name": "User", "fields": [
+            {"name": "name", "type": "string"}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, false, false);
+        format.single_object_encoding = true;
+        format.add_reader_schema(avro_schema.clone());
+        let (mut deserializer, mut builders, _) = deserializer_with_schema(format, None);
+
+        // marker, then the schema's fingerprint, then the datum
+        let mut frame = vec![0xC3, 0x01];
+        frame.extend(avro_schema.fingerprint::<apache_avro::rabin::Rabin>().bytes);
+        frame.extend(
+            apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![("name".to_string(), Value::String("ada".to_string()))]),
+            )
+            .unwrap(),
+        );
+
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &frame, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        frame[2] ^= 0xff;
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &frame, SystemTime::now())
+            .await;
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].details().contains("unknown schema fingerprint"));
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(batch.column(0).as_string::<i32>().value(0), "ada");
+    }
+
+    #[tokio::test]
+    async fn test_datum_static_schema() {
+        let data = [12, 65, 108, 121, 115, 115, 97, 0, 128, 4, 2];
+
+        let schema_str = r#"{"namespace": "example.avro",
+            "type": "record",
+            "name": "User",
+            "fields": [
+            {"name": "name", "type": "string"},
+            {"name": "favorite_number",  "type": ["int", "null"]},
+            {"name": "favorite_color", "type": ["string", "null"]}
+            ]
+        }"#;
+
+        let mut format = AvroFormat::new(false, true, true);
+        format.add_reader_schema(apache_avro::Schema::parse_str(schema_str).unwrap());
+        let vs = deserialize_with_schema(format, Some(schema_str), &data).await;
+
+        let expected = json!({ "name": "Alyssa", "favorite_number": 256, "favorite_color": null });
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(
+                vs[0].get("value").unwrap().as_str().unwrap()
+            )
+            .unwrap(),
+            expected
+        );
+    }
+
+    fn record_with_array(len: usize) -> Value {
+        Value::Record(vec![(
+            "items".to_string(),
+            Value::Array((0..len as i32).map(Value::Int).collect()),
+        )])
+    }
+
+    fn array_len(value: &Value) -> usize {
+        let Value::Record(fields) = value else {
+            panic!("not a record");
+        };
+        let Value::Array(items) = &fields[0].1 else {
+            panic!("not an array");
+        };
+        items.len()
+    }
+
+    #[test]
+    fn test_array_under_limit() {
+        for policy in [OversizedArrayPolicy::Truncate, OversizedArrayPolicy::Reject] {
+            let mut value = record_with_array(5);
+            limit_array_lengths(&mut value, 5, policy).unwrap();
+            assert_eq!(array_len(&value), 5);
+        }
+    }
+
+    #[test]
+    fn test_array_truncated() {
+        let mut value = record_with_array(10);
+        limit_array_lengths(&mut value, 3, OversizedArrayPolicy::Truncate).unwrap();
+        assert_eq!(array_len(&value), 3);
+    }
+
+    #[test]
+    fn test_array_rejected() {
+        let mut value = Value::Union(1, Box::new(record_with_array(10)));
+        assert!(limit_array_lengths(&mut value, 3, OversizedArrayPolicy::Reject).is_err());
+    }
+
+    const LINKED_LIST_SCHEMA: &str = r#"{"type": "record", "name": "Node", "fields": [
+        {"name": "value", "type": "long"},
+        {"name": "next", "type": ["null", "Node"]}
+    ]}"#;
+
+    /// A linked list of `len` nodes, each nested one level deeper than the last
+    fn linked_list(len: i64) -> Value {
+        let mut next = Value::Union(0, Box::new(Value::Null));
+        for i in (0..len).rev() {
+            next = Value::Record(vec![
+                ("value".to_string(), Value::Long(i)),
+                ("next".to_string(), next),
+            ]);
+            if i > 0 {
+                next = Value::Union(1, Box::new(next));
+            }
+        }
+        next
+    }
+
+    #[test]
+    fn test_nesting_within_limit() {
+        for policy in [
+            NestingOverflowPolicy::Truncate,
+            NestingOverflowPolicy::Drop,
+            NestingOverflowPolicy::Error,
+        ] {
+            let mut value = linked_list(3);
+            limit_nesting_depth(&mut value, 2, policy).unwrap();
+            assert_eq!(value, linked_list(3));
+        }
+    }
+
+    #[test]
+    fn test_nesting_overflow_truncated() {
+        let mut value = linked_list(5);
+        limit_nesting_depth(&mut value, 2, NestingOverflowPolicy::Truncate).unwrap();
+
+        assert_eq!(
+            avro_to_json(value),
+            json!({"value": 0, "next": {"value": 1, "next": {"value": 2, "next": null}}})
+        );
+    }
+
+    #[test]
+    fn test_nesting_overflow_dropped() {
+        let mut value = linked_list(5);
+        let err = limit_nesting_depth(&mut value, 2, NestingOverflowPolicy::Drop).unwrap_err();
+        assert!(matches!(err, SourceError::BadData { .. }), "{:?}", err);
+    }
+
+    #[test]
+    fn test_nesting_overflow_error() {
+        let mut value = linked_list(5);
+        let err = limit_nesting_depth(&mut value, 2, NestingOverflowPolicy::Error).unwrap_err();
+        assert!(matches!(err, SourceError::Other { .. }), "{:?}", err);
+    }
+
+    #[tokio::test]
+    async fn test_nesting_overflow_decoded() {
+        let avro_schema = apache_avro::Schema::parse_str(LINKED_LIST_SCHEMA).unwrap();
+        let mut format = AvroFormat::new(false, true, true);
+        format.add_reader_schema(avro_schema.clone());
+        format.max_nesting_depth = Some(1);
+
+        let datum = apache_avro::to_avro_datum(&avro_schema, linked_list(4)).unwrap();
+        let vs = deserialize_with_schema(format, Some(LINKED_LIST_SCHEMA), &datum).await;
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(
+                vs[0].get("value").unwrap().as_str().unwrap()
+            )
+            .unwrap(),
+            json!({"value": 0, "next": {"value": 1, "next": null}})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_recursive_schema() {
+        let schema = to_arrow(LINKED_LIST_SCHEMA).unwrap();
+        assert_eq!(schema.field(0).data_type(), &DataType::Int64);
+        assert_eq!(schema.field(1).data_type(), &DataType::Utf8);
+        assert!(schema.field(1).is_nullable());
+
+        let avro_schema = apache_avro::Schema::parse_str(LINKED_LIST_SCHEMA).unwrap();
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema.clone());
+
+        let datum = apache_avro::to_avro_datum(&avro_schema, linked_list(3)).unwrap();
+        let vs = deserialize_with_schema(format, Some(LINKED_LIST_SCHEMA), &datum).await;
+
+        assert_eq!(*vs[0].get("value").unwrap(), json!(0));
+        assert_eq!(
+            *vs[0].get("next").unwrap(),
+            json!({"value": 1, "next": {"value": 2, "next": null}})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_truncated_list_column() {
+        let message = [
+            0u8, 0, 0, 0, 1, 8, 200, 223, 1, 144, 31, 186, 159, 2, 16, 97, 99, 99, 101, 112, 116,
+            101, 100, 4, 156, 1, 10, 112, 105, 122, 122, 97, 4, 102, 102, 102, 102, 102, 230, 38,
+            64, 102, 102, 102, 102, 102, 230, 54, 64, 84, 14, 100, 101, 115, 115, 101, 114, 116, 2,
+            113, 61, 10, 215, 163, 112, 26, 64, 113, 61, 10, 215, 163, 112, 26, 64, 0, 10,
+        ];
+
+        let mut format = AvroFormat::new(true, false, false);
+        format.add_reader_schema(apache_avro::Schema::parse_str(SCHEMA).unwrap());
+        format.max_array_length = Some(1);
+
+        let row = deserialize_with_schema(format, Some(SCHEMA), &message)
+            .await
+            .remove(0);
+
+        assert_eq!(
+            *row.get("order_lines").unwrap(),
+            json!([{"category":"pizza","net_price":22.9,"product_id":78,"quantity":2,"unit_price":11.45}])
+        );
+    }
+
+    fn overflowing_record() -> (Value, Fields) {
+        let fields: Fields = vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(
+                "inner",
+                DataType::Struct(vec![Field::new("count", DataType::Int32, true)].into()),
+                false,
+            ),
+        ]
+        .into();
+
+        let value = Value::Record(vec![
+            ("id".to_string(), Value::Long(i64::MAX)),
+            (
+                "inner".to_string(),
+                Value::Record(vec![(
+                    "count".to_string(),
+                    Value::Union(1, Box::new(Value::Long(i32::MAX as i64 + 1))),
+                )]),
+            ),
+        ]);
+
+        (value, fields)
+    }
+
+    fn apply_overflow_policy(
+        value: &mut Value,
+        fields: &Fields,
+        policy: IntegerOverflowPolicy,
+    ) -> Result<(), SourceError> {
+        for_each_field(value, fields, "", &mut |path, field, v| {
+            check_integer_range(path, field, v, policy)
+        })
+    }
+
+    fn inner_count(value: &Value) -> &Value {
+        let Value::Record(fields) = value else {
+            panic!("not a record");
+        };
+        let Value::Record(inner) = &fields[1].1 else {
+            panic!("not a record");
+        };
+        match &inner[0].1 {
+            Value::Union(_, v) => v,
+            v => v,
+        }
+    }
+
+    #[test]
+    fn test_integer_overflow_null() {
+        let (mut value, fields) = overflowing_record();
+        apply_overflow_policy(&mut value, &fields, IntegerOverflowPolicy::Null).unwrap();
+        assert_eq!(*inner_count(&value), Value::Null);
+    }
+
+    #[test]
+    fn test_integer_overflow_error() {
+        let (mut value, fields) = overflowing_record();
+        let err =
+            apply_overflow_policy(&mut value, &fields, IntegerOverflowPolicy::Error).unwrap_err();
+        assert!(err.details().contains("inner.count"), "{}", err.details());
+        assert!(err.details().contains("2147483648"), "{}", err.details());
+    }
+
+    #[test]
+    fn test_integer_overflow_saturate() {
+        let (mut value, fields) = overflowing_record();
+        apply_overflow_policy(&mut value, &fields, IntegerOverflowPolicy::Saturate).unwrap();
+        assert_eq!(*inner_count(&value), Value::Long(i32::MAX as i64));
+
+        // in-range values for wider columns are untouched
+        let Value::Record(top) = &value else {
+            panic!("not a record");
+        };
+        assert_eq!(top[0].1, Value::Long(i64::MAX));
+    }
+
+    fn deserializer_with_bad_rows(suppress_empty_batches: bool) -> ArrowDeserializer {
+        let writer_schema =
+            r#"{"type": "record", "name": "r", "fields": [{"name": "x", "type": "string"}]}"#;
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(apache_avro::Schema::parse_str(writer_schema).unwrap());
+        format.suppress_empty_batches = suppress_empty_batches;
+
+        // the column is declared as an integer, so every string value fails to decode
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Int64, true),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+
+        ArrowDeserializer::new(
+            Format::Avro(format),
+            ArroyoSchema::from_schema_unkeyed(schema).unwrap(),
+            None,
+            BadData::Drop {},
+        )
+    }
+
+    async fn deserialize_bad_rows(deserializer: &mut ArrowDeserializer) {
+        let mut builders = vec![];
+        for s in ["hello", "world"] {
+            let mut message = vec![(s.len() * 2) as u8];
+            message.extend_from_slice(s.as_bytes());
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &message, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_empty_batch_emitted_by_default() {
+        let mut deserializer = deserializer_with_bad_rows(false);
+        deserialize_bad_rows(&mut deserializer).await;
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_empty_batch_suppressed() {
+        let mut deserializer = deserializer_with_bad_rows(true);
+        deserialize_bad_rows(&mut deserializer).await;
+
+        assert!(deserializer.flush_buffer().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_field_timing() {
+        let message = [
+            0u8, 0, 0, 0, 1, 8, 200, 223, 1, 144, 31, 186, 159, 2, 16, 97, 99, 99, 101, 112, 116,
+            101, 100, 4, 156, 1, 10, 112, 105, 122, 122, 97, 4, 102, 102, 102, 102, 102, 230, 38,
+            64, 102, 102, 102, 102, 102, 230, 54, 64, 84, 14, 100, 101, 115, 115, 101, 114, 116, 2,
+            113, 61, 10, 215, 163, 112, 26, 64, 113, 61, 10, 215, 163, 112, 26, 64, 0, 10,
+        ];
+
+        let mut format = AvroFormat::new(true, false, false);
+        format.add_reader_schema(apache_avro::Schema::parse_str(SCHEMA).unwrap());
+
+        let (mut deserializer, mut builders, _) =
+            deserializer_with_schema(format.clone(), Some(SCHEMA));
+        deserializer
+            .deserialize_slice(&mut builders, &message, SystemTime::now())
+            .await;
+        assert!(deserializer.field_decode_times().is_none());
+
+        format.field_timing = true;
+        let (mut deserializer, mut builders, _) = deserializer_with_schema(format, Some(SCHEMA));
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &message, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        let mut fields: Vec<_> = deserializer
+            .field_decode_times()
+            .unwrap()
+            .keys()
+            .cloned()
+            .collect();
+        fields.sort();
+        assert_eq!(
+            fields,
+            vec![
+                "coupon_code",
+                "date",
+                "order_lines",
+                "status",
+                "store_id",
+                "store_order_id"
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_out_of_range_union_branch() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [{"name": "x", "type": ["null", "long"]}]}"#;
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(apache_avro::Schema::parse_str(schema).unwrap());
+
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Int64, true),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Drop {});
+        let mut builders = vec![];
+
+        // branch index 5 (zig-zag encoded) for a union with only two variants
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &[10, 2], SystemTime::now())
+            .await;
+        assert_eq!(errors.len(), 1);
+        assert!(
+            matches!(errors[0], SourceError::BadData { .. }),
+            "{:?}",
+            errors[0]
+        );
+
+        // the corrupt record doesn't affect subsequent valid ones
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &[2, 84], SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<arrow_array::types::Int64Type>()
+                .value(0),
+            42
+        );
+    }
+
+    async fn decode_numeric_strings(
+        separator: DecimalSeparator,
+        values: &[&str],
+    ) -> Result<RecordBatch, SourceError> {
+        let schema =
+            r#"{"type": "record", "name": "r", "fields": [{"name": "x", "type": "string"}]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.decimal_separator = separator;
+        format.add_reader_schema(avro_schema.clone());
+
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Float64, true),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        for v in values {
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![("x".to_string(), Value::String(v.to_string()))]),
+            )
+            .unwrap();
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        deserializer.flush_buffer().unwrap()
+    }
+
+    #[tokio::test]
+    #[allow(clippy::approx_constant)]
+    async fn test_decimal_comma() {
+        let batch = decode_numeric_strings(DecimalSeparator::Comma, &["3,14", "1.234,56", "42"])
+            .await
+            .unwrap();
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<arrow_array::types::Float64Type>()
+                .values()
+                .to_vec(),
+            vec![3.14, 1234.56, 42.0]
+        );
+
+        // without the locale config, comma-separated values are not valid numbers
+        assert!(decode_numeric_strings(DecimalSeparator::Period, &["3,14"])
+            .await
+            .is_err());
+    }
+
+    const LOGICAL_SCHEMA: &str = r#"{"type": "record", "name": "r", "fields": [
+        {"name": "ts", "type": {"type": "long", "logicalType": "timestamp-micros"}},
+        {"name": "amount", "type": ["null", {"type": "bytes", "logicalType": "decimal", "precision": 10, "scale": 2}]}
+    ]}"#;
+
+    #[test]
+    fn test_logical_type_mapping_schema() {
+        let schema = to_arrow(LOGICAL_SCHEMA).unwrap();
+        assert_eq!(
+            schema.field(0).data_type(),
+            &DataType::Timestamp(TimeUnit::Microsecond, None)
+        );
+        assert_eq!(schema.field(1).data_type(), &DataType::Binary);
+
+        let mappings = BTreeMap::from([
+            (
+                "timestamp-micros".to_string(),
+                LogicalTypeMapping::TimestampNanos,
+            ),
+            ("decimal".to_string(), LogicalTypeMapping::Float64),
+        ]);
+        let schema = to_arrow_with_mappings(LOGICAL_SCHEMA, &mappings).unwrap();
+        assert_eq!(
+            schema.field(0).data_type(),
+            &DataType::Timestamp(TimeUnit::Nanosecond, None)
+        );
+        assert_eq!(schema.field(1).data_type(), &DataType::Float64);
+        assert!(schema.field(1).is_nullable());
+    }
+
+    #[tokio::test]
+    async fn test_logical_type_mapping_decode() {
+        let avro_schema = apache_avro::Schema::parse_str(LOGICAL_SCHEMA).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.logical_type_mappings = BTreeMap::from([
+            (
+                "timestamp-micros".to_string(),
+                LogicalTypeMapping::TimestampNanos,
+            ),
+            ("decimal".to_string(), LogicalTypeMapping::Float64),
+        ]);
+        format.add_reader_schema(avro_schema.clone());
+
+        let mut fields = to_arrow_with_mappings(LOGICAL_SCHEMA, &format.logical_type_mappings)
+            .unwrap()
+            .fields
+            .to_vec();
+        fields.push(Arc::new(Field::new(
+            "_timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+        let arroyo_schema =
+            ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(fields))).unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        for (ts, amount) in [(1_700_000_000_123_456i64, 12345i64), (-1, -250)] {
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![
+                    ("ts".to_string(), Value::TimestampMicros(ts)),
+                    (
+                        "amount".to_string(),
+                        Value::Union(
+                            1,
+                            Box::new(Value::Decimal(apache_avro::Decimal::from(
+                                amount.to_be_bytes().to_vec(),
+                            ))),
+                        ),
+                    ),
+                ]),
+            )
+            .unwrap();
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<arrow_array::types::TimestampNanosecondType>()
+                .values()
+                .to_vec(),
+            vec![1_700_000_000_123_456_000, -1_000]
+        );
+        assert_eq!(
+            batch
+                .column(1)
+                .as_primitive::<arrow_array::types::Float64Type>()
+                .values()
+                .to_vec(),
+            vec![123.45, -2.5]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_decimal128_mapping() {
+        let avro_schema = apache_avro::Schema::parse_str(LOGICAL_SCHEMA).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.logical_type_mappings =
+            BTreeMap::from([("decimal".to_string(), LogicalTypeMapping::Decimal)]);
+        format.add_reader_schema(avro_schema.clone());
+
+        let mut fields = to_arrow_with_mappings(LOGICAL_SCHEMA, &format.logical_type_mappings)
+            .unwrap()
+            .fields
+            .to_vec();
+        assert_eq!(fields[1].data_type(), &DataType::Decimal128(10, 2));
+        fields.push(Arc::new(Field::new(
+            "_timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+        let arroyo_schema =
+            ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(fields))).unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        for amount in [12345i64, -250, 0] {
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![
+                    ("ts".to_string(), Value::TimestampMicros(0)),
+                    (
+                        "amount".to_string(),
+                        Value::Union(
+                            1,
+                            Box::new(Value::Decimal(apache_avro::Decimal::from(
+                                amount.to_be_bytes().to_vec(),
+                            ))),
+                        ),
+                    ),
+                ]),
+            )
+            .unwrap();
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        let amounts = batch
+            .column(1)
+            .as_primitive::<arrow_array::types::Decimal128Type>();
+        assert_eq!(amounts.data_type(), &DataType::Decimal128(10, 2));
+        assert_eq!(amounts.values().to_vec(), vec![12345, -250, 0]);
+    }
+
+    #[tokio::test]
+    async fn test_decimal256_mapping() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "amount", "type": {"type": "bytes", "logicalType": "decimal", "precision": 50, "scale": 10}}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.logical_type_mappings =
+            BTreeMap::from([("decimal".to_string(), LogicalTypeMapping::Decimal)]);
+        format.add_reader_schema(avro_schema.clone());
+
+        let mut fields = to_arrow_with_mappings(schema, &format.logical_type_mappings)
+            .unwrap()
+            .fields
+            .to_vec();
+        assert_eq!(fields[0].data_type(), &DataType::Decimal256(50, 10));
+        fields.push(Arc::new(Field::new(
+            "_timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+        let arroyo_schema =
+            ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(fields))).unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        // 50 digits, which doesn't fit in an i128
+        let amounts = [
+            "1234567890123456789012345678901234567890.1234567890",
+            "-1234567890123456789012345678901234567890.1234567890",
+        ];
+        for amount in amounts {
+            let unscaled = i256::from_string(&amount.replace('.', "")).unwrap();
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![(
+                    "amount".to_string(),
+                    Value::Decimal(apache_avro::Decimal::from(unscaled.to_be_bytes())),
+                )]),
+            )
+            .unwrap();
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        let column = batch
+            .column(0)
+            .as_primitive::<arrow_array::types::Decimal256Type>();
+        for (i, amount) in amounts.iter().enumerate() {
+            assert_eq!(&column.value_as_string(i), amount);
+        }
+        assert!(column.value(1) < i256::ZERO);
+    }
+
+    #[tokio::test]
+    async fn test_uuid_fixed_size_binary_mapping() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "id", "type": {"type": "string", "logicalType": "uuid"}}
+        ]}"#;
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.logical_type_mappings =
+            BTreeMap::from([("uuid".to_string(), LogicalTypeMapping::FixedSizeBinary)]);
+        format.add_reader_schema(apache_avro::Schema::parse_str(schema).unwrap());
+
+        let mut fields = to_arrow_with_mappings(schema, &format.logical_type_mappings)
+            .unwrap()
+            .fields
+            .to_vec();
+        assert_eq!(fields[0].data_type(), &DataType::FixedSizeBinary(16));
+        fields.push(Arc::new(Field::new(
+            "_timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+        let arroyo_schema =
+            ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(fields))).unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        // a string of length 36
+        let mut datum = vec![72];
+        datum.extend_from_slice(b"550e8400-e29b-41d4-a716-446655440000");
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &datum, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(
+            batch.column(0).as_fixed_size_binary().value(0),
+            [
+                0x55, 0x0e, 0x84, 0x00, 0xe2, 0x9b, 0x41, 0xd4, 0xa7, 0x16, 0x44, 0x66, 0x55, 0x44,
+                0x00, 0x00
+            ]
+        );
+    }
+
+    #[test]
+    fn test_invalid_uuid_string() {
+        let field = Field::new("id", DataType::FixedSizeBinary(16), false);
+        let mut value = Value::String("550e8400-not-a-uuid".to_string());
+        let err =
+            apply_logical_type_mapping("id", &field, &mut value, &HashMap::new()).unwrap_err();
+        assert!(err.details().contains("column 'id'"));
+    }
+
+    #[tokio::test]
+    async fn test_fixed_decimal() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "amount", "type": {"type": "fixed", "name": "F", "size": 8, "logicalType": "decimal", "precision": 10, "scale": 2}}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.logical_type_mappings =
+            BTreeMap::from([("decimal".to_string(), LogicalTypeMapping::Decimal)]);
+        format.add_reader_schema(avro_schema.clone());
+
+        let mut fields = to_arrow_with_mappings(schema, &format.logical_type_mappings)
+            .unwrap()
+            .fields
+            .to_vec();
+        assert_eq!(fields[0].data_type(), &DataType::Decimal128(10, 2));
+        fields.push(Arc::new(Field::new(
+            "_timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+        let arroyo_schema =
+            ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(fields))).unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        for amount in [12345i64, -250] {
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![(
+                    "amount".to_string(),
+                    Value::Decimal(apache_avro::Decimal::from(amount.to_be_bytes())),
+                )]),
+            )
+            .unwrap();
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        let amounts = batch
+            .column(0)
+            .as_primitive::<arrow_array::types::Decimal128Type>();
+        assert_eq!(amounts.values().to_vec(), vec![12345, -250]);
+    }
+
+    #[test]
+    fn test_wide_fixed_decimal() {
+        // a fixed wider than an i128, holding -250 sign-extended across all 20 bytes
+        let field = Field::new("amount", DataType::Decimal128(10, 2), false);
+        let mut bytes = vec![0xff; 20];
+        bytes[18..].copy_from_slice(&(-250i16).to_be_bytes());
+
+        let mut value = Value::Fixed(20, bytes);
+        apply_logical_type_mapping("amount", &field, &mut value, &HashMap::new()).unwrap();
+        assert_eq!(value, Value::String("-2.50".to_string()));
+
+        // but not one that actually uses the extra width
+        let mut bytes = vec![0; 20];
+        bytes[0] = 1;
+        let mut value = Value::Fixed(20, bytes);
+        assert!(apply_logical_type_mapping("amount", &field, &mut value, &HashMap::new()).is_err());
+    }
+
+    #[test]
+    fn test_decimal128_exceeds_precision() {
+        let field = Field::new("amount", DataType::Decimal128(4, 2), true);
+        let scales = HashMap::from([("amount".to_string(), 2)]);
+        let decimal =
+            |unscaled: i64| Value::Decimal(apache_avro::Decimal::from(unscaled.to_be_bytes()));
+
+        let mut value = decimal(-9999);
+        apply_logical_type_mapping("amount", &field, &mut value, &scales).unwrap();
+        assert_eq!(value, Value::String("-99.99".to_string()));
+
+        let mut value = decimal(10000);
+        let err = apply_logical_type_mapping("amount", &field, &mut value, &scales).unwrap_err();
+        assert!(err.details().contains("exceeds the precision"));
+    }
+
+    #[tokio::test]
+    async fn test_timestamps_rescaled_to_column_unit() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "millis", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+            {"name": "micros", "type": {"type": "long", "logicalType": "timestamp-micros"}}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema.clone());
+
+        // the column types differ from the Avro units, as they might for a declared schema
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new(
+                "millis",
+                DataType::Timestamp(TimeUnit::Microsecond, None),
+                false,
+            ),
+            Field::new(
+                "micros",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let datum = apache_avro::to_avro_datum(
+            &avro_schema,
+            Value::Record(vec![
+                (
+                    "millis".to_string(),
+                    Value::TimestampMillis(1_700_000_000_123),
+                ),
+                (
+                    "micros".to_string(),
+                    Value::TimestampMicros(1_700_000_000_123_456),
+                ),
+            ]),
+        )
+        .unwrap();
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &datum, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<arrow_array::types::TimestampMicrosecondType>()
+                .value(0),
+            1_700_000_000_123_000
+        );
+        assert_eq!(
+            batch
+                .column(1)
+                .as_primitive::<arrow_array::types::TimestampNanosecondType>()
+                .value(0),
+            1_700_000_000_123_456_000
+        );
+    }
+
+    #[tokio::test]
+    async fn test_timestamp_time_zone_kept() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "millis", "type": {"type": "long", "logicalType": "timestamp-millis"}},
+            {"name": "micros", "type": {"type": "long", "logicalType": "timestamp-micros"}}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema.clone());
+
+        let utc = Some(Arc::from("UTC"));
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new(
+                "millis",
+                DataType::Timestamp(TimeUnit::Nanosecond, utc.clone()),
+                false,
+            ),
+            Field::new(
+                "micros",
+                DataType::Timestamp(TimeUnit::Microsecond, utc.clone()),
+                false,
+            ),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let datum = apache_avro::to_avro_datum(
+            &avro_schema,
+            Value::Record(vec![
+                (
+                    "millis".to_string(),
+                    Value::TimestampMillis(1_700_000_000_123),
+                ),
+                (
+                    "micros".to_string(),
+                    Value::TimestampMicros(1_700_000_000_123_456),
+                ),
+            ]),
+        )
+        .unwrap();
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &datum, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(
+            batch.schema().field(0).data_type(),
+            &DataType::Timestamp(TimeUnit::Nanosecond, utc.clone())
+        );
+        assert_eq!(
+            batch.column(0).data_type(),
+            &DataType::Timestamp(TimeUnit::Nanosecond, utc.clone())
+        );
+        assert_eq!(
+            batch.column(1).data_type(),
+            &DataType::Timestamp(TimeUnit::Microsecond, utc)
+        );
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<arrow_array::types::TimestampNanosecondType>()
+                .value(0),
+            1_700_000_000_123_000_000
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_timestamps() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "millis", "type": {"type": "long", "logicalType": "local-timestamp-millis"}},
+            {"name": "micros", "type": {"type": "long", "logicalType": "local-timestamp-micros"}}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut fields = to_arrow(schema).unwrap().fields.to_vec();
+        assert_eq!(
+            fields[0].data_type(),
+            &DataType::Timestamp(TimeUnit::Millisecond, None)
+        );
+        assert_eq!(
+            fields[1].data_type(),
+            &DataType::Timestamp(TimeUnit::Microsecond, None)
+        );
+        fields.push(Arc::new(Field::new(
+            "_timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+        let arroyo_schema =
+            ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(fields))).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema.clone());
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let datum = apache_avro::to_avro_datum(
+            &avro_schema,
+            Value::Record(vec![
+                (
+                    "millis".to_string(),
+                    Value::LocalTimestampMillis(1_700_000_000_123),
+                ),
+                (
+                    "micros".to_string(),
+                    Value::LocalTimestampMicros(1_700_000_000_123_456),
+                ),
+            ]),
+        )
+        .unwrap();
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &datum, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        // the wall-clock values are kept as is, with no offset applied
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<arrow_array::types::TimestampMillisecondType>()
+                .value(0),
+            1_700_000_000_123
+        );
+        assert_eq!(
+            batch
+                .column(1)
+                .as_primitive::<arrow_array::types::TimestampMicrosecondType>()
+                .value(0),
+            1_700_000_000_123_456
+        );
+    }
+
+    #[tokio::test]
+    async fn test_date_columns() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "days", "type": {"type": "int", "logicalType": "date"}},
+            {"name": "millis", "type": {"type": "int", "logicalType": "date"}}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema.clone());
+
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("days", DataType::Date32, false),
+            Field::new("millis", DataType::Date64, false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let datum = apache_avro::to_avro_datum(
+            &avro_schema,
+            Value::Record(vec![
+                ("days".to_string(), Value::Date(100)),
+                ("millis".to_string(), Value::Date(100)),
+            ]),
+        )
+        .unwrap();
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &datum, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<arrow_array::types::Date32Type>()
+                .value(0),
+            100
+        );
+        assert_eq!(
+            batch
+                .column(1)
+                .as_primitive::<arrow_array::types::Date64Type>()
+                .value(0),
+            100 * 86_400_000
+        );
+    }
+
+    const TIME_SCHEMA: &str = r#"{"type": "record", "name": "r", "fields": [
+        {"name": "t", "type": {"type": "int", "logicalType": "time-millis"}}
+    ]}"#;
+
+    async fn decode_times(
+        data_type: DataType,
+        policy: OutOfRangeTimePolicy,
+        times: &[i32],
+    ) -> (Vec<SourceError>, Option<RecordBatch>) {
+        let avro_schema = apache_avro::Schema::parse_str(TIME_SCHEMA).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.out_of_range_times = policy;
+        format.add_reader_schema(avro_schema.clone());
+
+        // time-millis is inferred as a plain Int32, so time columns have to be declared
+        let fields = vec![
+            Field::new("t", data_type, false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ];
+        let arroyo_schema =
+            ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(fields))).unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let mut errors = vec![];
+        for t in times {
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![("t".to_string(), Value::TimeMillis(*t))]),
+            )
+            .unwrap();
+            errors.extend(
+                deserializer
+                    .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                    .await,
+            );
+        }
+
+        (errors, deserializer.flush_buffer().map(|b| b.unwrap()))
+    }
+
+    #[tokio::test]
+    async fn test_time_millis() {
+        let noon = 12 * 60 * 60 * 1000;
+
+        let (errors, batch) = decode_times(
+            DataType::Time32(TimeUnit::Millisecond),
+            OutOfRangeTimePolicy::Null,
+            &[0, noon, 86_400_000],
+        )
+        .await;
+        assert_eq!(errors, vec![]);
+        let times = batch
+            .unwrap()
+            .column(0)
+            .as_primitive::<arrow_array::types::Time32MillisecondType>()
+            .clone();
+        assert_eq!(times.value(0), 0);
+        assert_eq!(times.value(1), noon);
+        assert!(times.is_null(2));
+
+        let (errors, batch) = decode_times(
+            DataType::Time64(TimeUnit::Microsecond),
+            OutOfRangeTimePolicy::Error,
+            &[noon],
+        )
+        .await;
+        assert_eq!(errors, vec![]);
+        let times = batch
+            .unwrap()
+            .column(0)
+            .as_primitive::<arrow_array::types::Time64MicrosecondType>()
+            .clone();
+        assert_eq!(times.value(0), noon as i64 * 1000);
+
+        let (errors, _) = decode_times(
+            DataType::Time32(TimeUnit::Millisecond),
+            OutOfRangeTimePolicy::Error,
+            &[0, -1],
+        )
+        .await;
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].details().contains("not a valid time of day"));
+    }
+
+    fn apply_sentinels(value: &mut Value, fields: &Fields, sentinels: &[(&str, &str)]) {
+        let sentinels: BTreeMap<String, String> = sentinels
+            .iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect();
+        for_each_field(value, fields, "", &mut |path, _, v| {
+            apply_null_sentinel(path, v, &sentinels);
+            Ok(())
+        })
+        .unwrap();
+    }
+
+    fn sentinel_record(id: i64, name: &str, score: f64, day: i32) -> Value {
+        Value::Record(vec![
+            ("id".to_string(), Value::Long(id)),
+            (
+                "info".to_string(),
+                Value::Record(vec![
+                    (
+                        "name".to_string(),
+                        Value::Union(1, Box::new(Value::String(name.to_string()))),
+                    ),
+                    ("score".to_string(), Value::Double(score)),
+                ]),
+            ),
+            ("expires".to_string(), Value::Date(day)),
+        ])
+    }
+
+    fn sentinel_fields() -> Fields {
+        vec![
+            Field::new("id", DataType::Int64, true),
+            Field::new(
+                "info",
+                DataType::Struct(
+                    vec![
+                        Field::new("name", DataType::Utf8, true),
+                        Field::new("score", DataType::Float64, true),
+                    ]
+                    .into(),
+                ),
+                false,
+            ),
+            Field::new("expires", DataType::Date32, true),
+        ]
+        .into()
+    }
+
+    const SENTINELS: [(&str, &str); 4] = [
+        ("id", "-1"),
+        ("info.name", ""),
+        ("info.score", "-999.5"),
+        ("expires", "9999-12-31"),
+    ];
+
+    #[test]
+    fn test_null_sentinels() {
+        // 2932896 is 9999-12-31 as days since the epoch
+        let mut value = sentinel_record(-1, "", -999.5, 2932896);
+        apply_sentinels(&mut value, &sentinel_fields(), &SENTINELS);
+
+        assert_eq!(
+            avro_to_json(value),
+            json!({"id": null, "info": {"name": null, "score": null}, "expires": null})
+        );
+    }
+
+    #[test]
+    fn test_non_sentinel_values_untouched() {
+        let mut value = sentinel_record(5, "bob", 1.5, 19000);
+        apply_sentinels(&mut value, &sentinel_fields(), &SENTINELS);
+
+        assert_eq!(
+            avro_to_json(value),
+            json!({"id": 5, "info": {"name": "bob", "score": 1.5}, "expires": 19000})
+        );
+
+        // sentinels only apply to the field they're configured for
+        let mut value = sentinel_record(7, "-1", -1.0, 0);
+        apply_sentinels(&mut value, &sentinel_fields(), &[("id", "-1")]);
+        assert_eq!(
+            avro_to_json(value),
+            json!({"id": 7, "info": {"name": "-1", "score": -1.0}, "expires": 0})
+        );
+    }
+
+    fn check_precision(value: f64, policy: PrecisionLossPolicy) -> Result<(), SourceError> {
+        let fields: Fields = vec![Field::new("count", DataType::Int64, true)].into();
+        let mut record = Value::Record(vec![(
+            "count".to_string(),
+            Value::Union(1, Box::new(Value::Double(value))),
+        )]);
+
+        for_each_field(&mut record, &fields, "", &mut |path, field, v| {
+            check_float_precision(path, field, v, policy)
+        })
+    }
+
+    #[test]
+    fn test_float_precision_truncate() {
+        assert!(check_precision(3.0, PrecisionLossPolicy::Truncate).is_ok());
+        assert!(check_precision(3.5, PrecisionLossPolicy::Truncate).is_ok());
+    }
+
+    #[test]
+    fn test_float_precision_error() {
+        assert!(check_precision(3.0, PrecisionLossPolicy::Error).is_ok());
+
+        let err = check_precision(3.5, PrecisionLossPolicy::Error).unwrap_err();
+        assert!(err.details().contains("count"), "{}", err.details());
+        assert!(err.details().contains("3.5"), "{}", err.details());
+    }
+
+    fn user(email: Option<&str>) -> Value {
+        Value::Record(vec![
+            ("name".to_string(), Value::String("bob".to_string())),
+            (
+                "contact".to_string(),
+                Value::Record(vec![(
+                    "email".to_string(),
+                    match email {
+                        Some(email) => Value::Union(1, Box::new(Value::String(email.to_string()))),
+                        None => Value::Union(0, Box::new(Value::Null)),
+                    },
+                )]),
+            ),
+        ])
+    }
+
+    #[test]
+    fn test_required_fields_by_schema_id() {
+        let required = BTreeMap::from([(2, vec!["name".to_string(), "contact.email".to_string()])]);
+
+        // v1 records may omit the email
+        assert!(check_required_fields(&required, 1, &user(None)).is_ok());
+        assert!(check_required_fields(&required, 1, &user(Some("bob@example.com"))).is_ok());
+
+        // but v2 records must have it
+        assert!(check_required_fields(&required, 2, &user(Some("bob@example.com"))).is_ok());
+        let err = check_required_fields(&required, 2, &user(None)).unwrap_err();
+        assert!(err.details().contains("contact.email"), "{}", err.details());
+
+        // fields that are absent entirely are also rejected
+        let v1_record = Value::Record(vec![("name".to_string(), Value::String("bob".to_string()))]);
+        assert!(check_required_fields(&required, 1, &v1_record).is_ok());
+        assert!(check_required_fields(&required, 2, &v1_record).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_batch_byte_budget() {
+        let schema =
+            r#"{"type": "record", "name": "r", "fields": [{"name": "s", "type": "string"}]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.max_batch_bytes = Some(100);
+        format.add_reader_schema(avro_schema.clone());
+
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("s", DataType::Utf8, false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let mut batches = vec![];
+        for i in 0..30 {
+            let s = "x".repeat((i * 7) % 45 + 1);
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![("s".to_string(), Value::String(s))]),
+            )
+            .unwrap();
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+
+            if deserializer.should_flush() {
+                batches.push(deserializer.flush_buffer().unwrap().unwrap());
+            }
+        }
+        if let Some(batch) = deserializer.flush_buffer() {
+            batches.push(batch.unwrap());
+        }
+
+        assert!(batches.len() > 1);
+        assert_eq!(batches.iter().map(|b| b.num_rows()).sum::<usize>(), 30);
+        for batch in &batches {
+            let sizes: Vec<_> = batch
+                .column(0)
+                .as_string::<i32>()
+                .iter()
+                .map(|s| s.unwrap().len())
+                .collect();
+            // a batch is flushed as soon as the record that reaches the budget is added
+            let before_last: usize = sizes[..sizes.len() - 1].iter().sum();
+            assert!(before_last < 100, "batch exceeded budget: {:?}", sizes);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_case_insensitive_fields() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "UserId", "type": "long"},
+            {"name": "Name", "type": "string"}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.case_insensitive_fields = true;
+        format.add_reader_schema(avro_schema.clone());
+
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("userid", DataType::Int64, true),
+            Field::new("Name", DataType::Utf8, true),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let datum = apache_avro::to_avro_datum(
+            &avro_schema,
+            Value::Record(vec![
+                ("UserId".to_string(), Value::Long(42)),
+                ("Name".to_string(), Value::String("bob".to_string())),
+            ]),
+        )
+        .unwrap();
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &datum, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<arrow_array::types::Int64Type>()
+                .value(0),
+            42
+        );
+        assert_eq!(batch.column(1).as_string::<i32>().value(0), "bob");
+    }
+
+    #[tokio::test]
+    async fn test_stringify_union_scalars() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "v", "type": ["null", "int", "string"]}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.stringify_scalars = true;
+        format.add_reader_schema(avro_schema.clone());
+
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("v", DataType::Utf8, true),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let rows = [
+            Value::Union(1, Box::new(Value::Int(1))),
+            Value::Union(2, Box::new(Value::String("one".to_string()))),
+            Value::Union(1, Box::new(Value::Int(-2))),
+            Value::Union(2, Box::new(Value::String("two".to_string()))),
+            Value::Union(0, Box::new(Value::Null)),
+        ];
+
+        for row in rows {
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![("v".to_string(), row)]),
+            )
+            .unwrap();
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        let values: Vec<_> = batch.column(0).as_string::<i32>().iter().collect();
+        assert_eq!(
+            values,
+            vec![Some("1"), Some("one"), Some("-2"), Some("two"), None]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_numeric_union_widening() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "i", "type": ["int", "long"]},
+            {"name": "f", "type": ["float", "double"]}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema.clone());
+
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("i", DataType::Int64, false),
+            Field::new("f", DataType::Float64, false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let rows = [
+            (
+                Value::Union(0, Box::new(Value::Int(i32::MAX))),
+                Value::Union(0, Box::new(Value::Float(1.5))),
+            ),
+            (
+                Value::Union(1, Box::new(Value::Long(i64::MAX))),
+                Value::Union(1, Box::new(Value::Double(1e300))),
+            ),
+        ];
+
+        for (i, f) in rows {
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![("i".to_string(), i), ("f".to_string(), f)]),
+            )
+            .unwrap();
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<arrow_array::types::Int64Type>()
+                .values()
+                .to_vec(),
+            vec![i32::MAX as i64, i64::MAX]
+        );
+        assert_eq!(
+            batch
+                .column(1)
+                .as_primitive::<arrow_array::types::Float64Type>()
+                .values()
+                .to_vec(),
+            vec![1.5, 1e300]
+        );
+    }
+
+    async fn decode_colors(
+        policy: UnknownEnumSymbolPolicy,
+    ) -> (Vec<SourceError>, Option<RecordBatch>) {
+        let rows = vec![
+            Value::Union(1, Box::new(Value::Enum(1, "GREEN".to_string()))),
+            Value::Union(1, Box::new(Value::Enum(2, "BLUE".to_string()))),
+            Value::Union(1, Box::new(Value::Enum(0, "RED".to_string()))),
+            Value::Union(1, Box::new(Value::Enum(1, "GREEN".to_string()))),
+            Value::Union(0, Box::new(Value::Null)),
+            Value::Union(2, Box::new(Value::String("PURPLE".to_string()))),
+        ];
+
+        decode_color_rows(policy, rows).await
+    }
+
+    async fn decode_color_rows(
+        policy: UnknownEnumSymbolPolicy,
+        rows: Vec<Value>,
+    ) -> (Vec<SourceError>, Option<RecordBatch>) {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "color", "type": [
+                "null",
+                {"type": "enum", "name": "Color", "symbols": ["RED", "GREEN", "BLUE"]},
+                "string"
+            ]}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.unknown_enum_symbols = policy;
+        format.add_reader_schema(avro_schema.clone());
+
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new(
+                "color",
+                DataType::Dictionary(Box::new(DataType::Int32), Box::new(DataType::Utf8)),
+                true,
+            ),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let mut errors = vec![];
+        for row in rows {
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![("color".to_string(), row)]),
+            )
+            .unwrap();
+            errors.extend(
+                deserializer
+                    .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                    .await,
+            );
+        }
+
+        (errors, deserializer.flush_buffer().map(|b| b.unwrap()))
+    }
+
+    #[tokio::test]
+    async fn test_enum_dictionary() {
+        let (errors, batch) = decode_colors(UnknownEnumSymbolPolicy::Null).await;
+        assert_eq!(errors, vec![]);
+
+        let batch = batch.unwrap();
+        let colors = batch
+            .column(0)
+            .as_dictionary::<arrow_array::types::Int32Type>();
+        assert_eq!(
+            colors.keys().iter().collect::<Vec<_>>(),
+            vec![Some(1), Some(2), Some(0), Some(1), None, None]
+        );
+        assert_eq!(
+            colors
+                .values()
+                .as_string::<i32>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Some("RED"), Some("GREEN"), Some("BLUE")]
+        );
+
+        let (errors, batch) = decode_colors(UnknownEnumSymbolPolicy::Error).await;
+        assert_eq!(errors.len(), 1);
+        assert!(errors[0].details().contains("PURPLE"));
+        assert_eq!(batch.unwrap().num_rows(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_high_cardinality_dictionary_is_bounded() {
+        // a column of distinct strings can't grow the dictionary past the enum's symbols
+        let rows = (0..10_000)
+            .map(|i| match i % 100 {
+                0 => Value::Union(1, Box::new(Value::Enum(2, "BLUE".to_string()))),
+                _ => Value::Union(2, Box::new(Value::String(format!("color-{i}")))),
+            })
+            .collect();
+
+        let (errors, batch) = decode_color_rows(UnknownEnumSymbolPolicy::Null, rows).await;
+        assert_eq!(errors, vec![]);
+
+        let batch = batch.unwrap();
+        let colors = batch
+            .column(0)
+            .as_dictionary::<arrow_array::types::Int32Type>();
+        assert_eq!(batch.num_rows(), 10_000);
+        assert_eq!(colors.null_count(), 9_900);
+        assert_eq!(
+            colors
+                .values()
+                .as_string::<i32>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![None, None, Some("BLUE")]
+        );
+    }
+
+    #[test]
+    fn test_field_name_collisions() {
+        let fields = Fields::from(vec![
+            Field::new("userid", DataType::Int64, true),
+            Field::new(
+                "address",
+                DataType::Struct(Fields::from(vec![Field::new("city", DataType::Utf8, true)])),
+                true,
+            ),
+        ]);
+
+        let record = || {
+            Value::Record(vec![
+                ("UserId".to_string(), Value::Long(1)),
+                ("USERID".to_string(), Value::Long(2)),
+                (
+                    "Address".to_string(),
+                    Value::Union(
+                        1,
+                        Box::new(Value::Record(vec![(
+                            "City".to_string(),
+                            Value::String("paris".to_string()),
+                        )])),
+                    ),
+                ),
+                ("other".to_string(), Value::Null),
+            ])
+        };
+
+        let mut value = record();
+        assert!(
+            normalize_field_names(&mut value, &fields, FieldNameCollisionPolicy::Error)
+                .unwrap_err()
+                .details()
+                .contains("USERID")
+        );
+
+        let mut value = record();
+        normalize_field_names(&mut value, &fields, FieldNameCollisionPolicy::FirstWins).unwrap();
+        assert_eq!(
+            avro_to_json(value),
+            json!({"userid": 1, "address": {"city": "paris"}, "other": null})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unexpected_fields_reported() {
+        let reader_schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "address", "type": {"type": "record", "name": "address", "fields": [
+                {"name": "city", "type": "string"}
+            ]}}
+        ]}"#;
+        let writer_schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "email", "type": "string"},
+            {"name": "address", "type": {"type": "record", "name": "address", "fields": [
+                {"name": "city", "type": "string"},
+                {"name": "zip", "type": "string"}
+            ]}}
+        ]}"#;
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.report_unexpected_fields = true;
+        format.add_reader_schema(apache_avro::Schema::parse_str(reader_schema).unwrap());
+
+        let (mut deserializer, mut builders, _) =
+            deserializer_with_schema(format, Some(writer_schema));
+        assert!(deserializer.unexpected_fields().unwrap().is_empty());
+
+        let datum = apache_avro::to_avro_datum(
+            &apache_avro::Schema::parse_str(writer_schema).unwrap(),
+            Value::Record(vec![
+                ("id".to_string(), Value::Long(1)),
+                ("email".to_string(), Value::String("a@b.c".to_string())),
+                (
+                    "address".to_string(),
+                    Value::Record(vec![
+                        ("city".to_string(), Value::String("paris".to_string())),
+                        ("zip".to_string(), Value::String("75001".to_string())),
+                    ]),
+                ),
+            ]),
+        )
+        .unwrap();
+
+        for _ in 0..2 {
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        let unexpected = deserializer.unexpected_fields().unwrap();
+        assert_eq!(unexpected.len(), 1);
+        assert_eq!(
+            unexpected[&0]
+                .iter()
+                .map(|s| s.as_str())
+                .collect::<Vec<_>>(),
+            vec!["address.zip", "email"]
+        );
+        assert_eq!(deserializer.flush_buffer().unwrap().unwrap().num_rows(), 2);
+    }
+
+    async fn decode_with_errors(
+        format: AvroFormat,
+        good: usize,
+        bad: usize,
+    ) -> Result<RecordBatch, SourceError> {
+        let schema =
+            r#"{"type": "record", "name": "r", "fields": [{"name": "x", "type": "long"}]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = format;
+        format.add_reader_schema(avro_schema.clone());
+        let (mut deserializer, mut builders, _) = deserializer_with_schema(format, Some(schema));
+
+        for i in 0..good + bad {
+            let datum = if i < bad {
+                // an incomplete varint
+                vec![0xff]
+            } else {
+                apache_avro::to_avro_datum(
+                    &avro_schema,
+                    Value::Record(vec![("x".to_string(), Value::Long(i as i64))]),
+                )
+                .unwrap()
+            };
+
+            deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+        }
+
+        deserializer.flush_buffer().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_batch_error_threshold() {
+        let mut format = AvroFormat::new(false, true, false);
+        format.max_batch_error_percent = Some(20);
+
+        // 2 of 10 is within the threshold, so the bad records are just dropped
+        let batch = decode_with_errors(format.clone(), 8, 2).await.unwrap();
+        assert_eq!(batch.num_rows(), 8);
+
+        let Err(SourceError::Other { details, .. }) =
+            decode_with_errors(format.clone(), 7, 3).await
+        else {
+            panic!("expected batch to fail");
+        };
+        assert_eq!(details, "3 of 10 records in the batch failed to decode");
+
+        // with every record bad, there's no batch for the decoder to produce
+        let Err(SourceError::Other { details, .. }) =
+            decode_with_errors(format.clone(), 0, 5).await
+        else {
+            panic!("expected batch to fail");
+        };
+        assert_eq!(details, "5 of 5 records in the batch failed to decode");
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.max_batch_errors = Some(1);
+        assert_eq!(
+            decode_with_errors(format.clone(), 99, 1)
+                .await
+                .unwrap()
+                .num_rows(),
+            99
+        );
+        assert!(decode_with_errors(format, 98, 2).await.is_err());
+    }
+
+    async fn decode_unresolvable(format: AvroFormat, message: &[u8]) -> Vec<SourceError> {
+        let schema = ArroyoSchema::from_schema_keys(
+            Arc::new(Schema::new(vec![
+                Field::new("raw", DataType::Utf8, false),
+                Field::new(
+                    "_timestamp",
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    false,
+                ),
+            ])),
+            vec![],
+        )
+        .unwrap();
+        let mut builders: Vec<_> = schema
+            .schema
+            .fields
+            .iter()
+            .map(|f| make_builder(f.data_type(), 8))
+            .collect();
+
+        let mut deserializer = ArrowDeserializer::with_schema_resolver(
+            Format::Avro(format),
+            None,
+            schema,
+            BadData::Fail {},
+            Arc::new(FailingSchemaResolver::new()),
+        );
+
+        let errors = deserializer
+            .deserialize_slice(&mut builders, message, SystemTime::now())
+            .await;
+        if !errors.is_empty() {
+            return errors;
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        // Avro bytes are decoded as a string with one character per byte
+        let raw: Vec<u8> = batch
+            .column(0)
+            .as_string::<i32>()
+            .value(0)
+            .chars()
+            .map(|c| c as u8)
+            .collect();
+        assert_eq!(raw, message);
+        vec![]
+    }
+
+    #[tokio::test]
+    async fn test_fallback_schema() {
+        let message = [0, 0, 0, 0, 7, 2, 0xff, 0x10];
+
+        let format = AvroFormat::new(true, false, false);
+        assert_eq!(decode_unresolvable(format.clone(), &message).await.len(), 1);
+
+        let mut format = format;
+        format.fallback_schema = Some(SerializableAvroSchema(
+            apache_avro::Schema::parse_str(
+                r#"{"type": "record", "name": "raw", "fields": [{"name": "raw", "type": "bytes"}]}"#,
+            )
+            .unwrap(),
+        ));
+        assert_eq!(decode_unresolvable(format, &message).await, vec![]);
+    }
+
+    #[tokio::test]
+    async fn test_field_defaults() {
+        let reader_schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "name", "type": "string"},
+            {"name": "count", "type": "long", "default": 42},
+            {"name": "note", "type": ["null", "string"]}
+        ]}"#;
+        let fallback_schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "name", "type": "string"}
+        ]}"#;
+        let fallback = apache_avro::Schema::parse_str(fallback_schema).unwrap();
+
+        let mut format = AvroFormat::new(true, false, false);
+        format.add_reader_schema(apache_avro::Schema::parse_str(reader_schema).unwrap());
+        format.fallback_schema = Some(SerializableAvroSchema(fallback.clone()));
+
+        // the schema id can't be resolved, so the message is decoded with the fallback schema,
+        // which doesn't have the reader schema's other fields
+        let mut message = vec![0, 0, 0, 0, 1];
+        message.extend(
+            apache_avro::to_avro_datum(
+                &fallback,
+                Value::Record(vec![("name".to_string(), Value::String("ada".to_string()))]),
+            )
+            .unwrap(),
+        );
+
+        let vs = deserialize_with_schema(format, None, &message).await;
+        assert_eq!(
+            serde_json::Value::Object(vs[0].clone()),
+            json!({"name": "ada", "count": 42})
+        );
+    }
+
+    async fn decode_records(schema: &str, records: Vec<Value>) -> RecordBatch {
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema.clone());
+        let (mut deserializer, mut builders, _) = deserializer_with_schema(format, Some(schema));
+
+        for record in records {
+            let datum = apache_avro::to_avro_datum(&avro_schema, record).unwrap();
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        // drop the _timestamp column
+        batch
+            .project(&(0..batch.num_columns() - 1).collect::<Vec<_>>())
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_serializer_round_trip() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "id", "type": "long"},
+            {"name": "count", "type": "int"},
+            {"name": "active", "type": "boolean"},
+            {"name": "name", "type": "string"},
+            {"name": "score", "type": ["null", "double"]},
+            {"name": "address", "type": ["null", {"type": "record", "name": "address", "fields": [
+                {"name": "city", "type": "string"},
+                {"name": "zip", "type": ["null", "string"]}
+            ]}]}
+        ]}"#;
+
+        let record = |id: i64, name: &str, score: Option<f64>, zip: Option<Option<&str>>| {
+            Value::Record(vec![
+                ("id".to_string(), Value::Long(id)),
+                ("count".to_string(), Value::Int(id as i32 * 10)),
+                ("active".to_string(), Value::Boolean(id % 2 == 0)),
+                ("name".to_string(), Value::String(name.to_string())),
+                (
+                    "score".to_string(),
+                    match score {
+                        Some(s) => Value::Union(1, Box::new(Value::Double(s))),
+                        None => Value::Union(0, Box::new(Value::Null)),
+                    },
+                ),
+                (
+                    "address".to_string(),
+                    match zip {
+                        Some(zip) => Value::Union(
+                            1,
+                            Box::new(Value::Record(vec![
+                                ("city".to_string(), Value::String(format!("{name}ville"))),
+                                (
+                                    "zip".to_string(),
+                                    match zip {
+                                        Some(z) => {
+                                            Value::Union(1, Box::new(Value::String(z.to_string())))
+                                        }
+                                        None => Value::Union(0, Box::new(Value::Null)),
+                                    },
+                                ),
+                            ])),
+                        ),
+                        None => Value::Union(0, Box::new(Value::Null)),
+                    },
+                ),
+            ])
+        };
+
+        let decoded = decode_records(
+            schema,
+            vec![
+                record(1, "ada", Some(1.5), Some(Some("12345"))),
+                record(2, "bob", None, Some(None)),
+                record(3, "cy", Some(-0.25), None),
+            ],
+        )
+        .await;
+        assert_eq!(decoded.num_rows(), 3);
+
+        // encode the batch back to avro with a schema generated from its arrow schema, then
+        // decode it again
+        let generated = to_avro("r", &decoded.schema().fields);
+        let records = serialize(&generated, &decoded);
+        let round_tripped = decode_records(&generated.canonical_form(), records).await;
+
+        assert_eq!(round_tripped.schema(), decoded.schema());
+        assert_eq!(round_tripped, decoded);
+    }
+
+    async fn decode_config_map(policy: MapNullValuePolicy) -> Vec<(String, Option<String>)> {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "config", "type": {"type": "map", "values": ["null", "string"]}}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.map_null_values = policy;
+        format.add_reader_schema(avro_schema.clone());
+
+        let entries = Field::new(
+            "entries",
+            DataType::Struct(Fields::from(vec![
+                Field::new("keys", DataType::Utf8, false),
+                Field::new("values", DataType::Utf8, true),
+            ])),
+            false,
+        );
+        let arroyo_schema = ArroyoSchema::from_schema_keys(
+            Arc::new(Schema::new(vec![
+                Field::new("config", DataType::Map(Arc::new(entries), false), false),
+                Field::new(
+                    "_timestamp",
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    false,
+                ),
+            ])),
+            vec![],
+        )
+        .unwrap();
+        let mut builders: Vec<_> = arroyo_schema
+            .schema
+            .fields
+            .iter()
+            .map(|f| make_builder(f.data_type(), 8))
+            .collect();
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+
+        let datum = apache_avro::to_avro_datum(
+            &avro_schema,
+            Value::Record(vec![(
+                "config".to_string(),
+                Value::Map(HashMap::from([
+                    (
+                        "region".to_string(),
+                        Value::Union(1, Box::new(Value::String("eu".to_string()))),
+                    ),
+                    (
+                        "override".to_string(),
+                        Value::Union(0, Box::new(Value::Null)),
+                    ),
+                ])),
+            )]),
+        )
+        .unwrap();
+
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &datum, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        let map = batch.column(0).as_map();
+        let keys = map.keys().as_string::<i32>();
+        let values = map.values().as_string::<i32>();
+
+        let mut entries: Vec<_> = (0..keys.len())
+            .map(|i| {
+                (
+                    keys.value(i).to_string(),
+                    (!values.is_null(i)).then(|| values.value(i).to_string()),
+                )
+            })
+            .collect();
+        entries.sort();
+        entries
+    }
+
+    #[tokio::test]
+    async fn test_map_null_values() {
+        // a key mapped to null is kept, with a null value
+        assert_eq!(
+            decode_config_map(MapNullValuePolicy::Preserve).await,
+            vec![
+                ("override".to_string(), None),
+                ("region".to_string(), Some("eu".to_string())),
+            ]
+        );
+
+        assert_eq!(
+            decode_config_map(MapNullValuePolicy::Drop).await,
+            vec![("region".to_string(), Some("eu".to_string()))]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_map_long_values() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "counts", "type": ["null", {"type": "map", "values": "long"}]}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema.clone());
+
+        let entries = Field::new(
+            "entries",
+            DataType::Struct(Fields::from(vec![
+                Field::new("keys", DataType::Utf8, false),
+                Field::new("values", DataType::Int64, false),
+            ])),
+            false,
+        );
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("counts", DataType::Map(Arc::new(entries), false), true),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let rows = [
+            Value::Union(
+                1,
+                Box::new(Value::Map(HashMap::from([
+                    ("a".to_string(), Value::Long(1)),
+                    ("b".to_string(), Value::Long(2)),
+                ]))),
+            ),
+            Value::Union(1, Box::new(Value::Map(HashMap::new()))),
+            Value::Union(0, Box::new(Value::Null)),
+        ];
+
+        for row in rows {
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![("counts".to_string(), row)]),
+            )
+            .unwrap();
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        let map = batch.column(0).as_map();
+        assert_eq!(map.value_offsets(), &[0, 2, 2, 2]);
+        assert!(map.is_valid(1));
+        assert!(map.is_null(2));
+
+        let keys = map.keys().as_string::<i32>();
+        let values = map.values().as_primitive::<arrow_array::types::Int64Type>();
+        let mut first: Vec<_> = (0..2)
+            .map(|i| (keys.value(i).to_string(), values.value(i)))
+            .collect();
+        first.sort();
+        assert_eq!(first, vec![("a".to_string(), 1), ("b".to_string(), 2)]);
+    }
+
+    #[tokio::test]
+    async fn test_map_duplicate_keys() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "counts", "type": {"type": "map", "values": "long"}}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema);
+
+        let entries = Field::new(
+            "entries",
+            DataType::Struct(Fields::from(vec![
+                Field::new("keys", DataType::Utf8, false),
+                Field::new("values", DataType::Int64, false),
+            ])),
+            false,
+        );
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("counts", DataType::Map(Arc::new(entries), false), false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        // a malformed writer's map block of two entries that share a key: {"a": 1, "a": 2}
+        // (counts, string lengths and longs are zigzag-encoded)
+        let datum = [0x04, 0x02, b'a', 0x02, 0x02, b'a', 0x04, 0x00];
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &datum, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        // the key appears once, with the last value written for it
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        let map = batch.column(0).as_map();
+        assert_eq!(map.value_offsets(), &[0, 1]);
+        assert_eq!(map.keys().as_string::<i32>().value(0), "a");
+        assert_eq!(
+            map.values()
+                .as_primitive::<arrow_array::types::Int64Type>()
+                .value(0),
+            2
+        );
+    }
+
+    async fn decode_unions(mode: UnionMode) -> UnionArray {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "u", "type": ["int", "string", "bytes"]}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema.clone());
+
+        let union_fields = UnionFields::new(
+            vec![0, 1, 2],
+            vec![
+                Field::new("int", DataType::Int32, true),
+                Field::new("string", DataType::Utf8, true),
+                Field::new("bytes", DataType::Binary, true),
+            ],
+        );
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("u", DataType::Union(union_fields, mode), false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let rows = [
+            Value::Union(0, Box::new(Value::Int(5))),
+            Value::Union(1, Box::new(Value::String("five".to_string()))),
+            Value::Union(2, Box::new(Value::Bytes(vec![5]))),
+            Value::Union(0, Box::new(Value::Int(6))),
+        ];
+
+        for row in rows {
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![("u".to_string(), row)]),
+            )
+            .unwrap();
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        batch.column(0).as_union().clone()
+    }
+
+    #[tokio::test]
+    async fn test_three_variant_union() {
+        for mode in [UnionMode::Sparse, UnionMode::Dense] {
+            let union = decode_unions(mode).await;
+            assert_eq!(union.len(), 4);
+            assert_eq!(
+                (0..4).map(|i| union.type_id(i)).collect::<Vec<_>>(),
+                vec![0, 1, 2, 0]
+            );
+
+            let value = |i: usize| union.value(i);
+            assert_eq!(
+                value(0)
+                    .as_primitive::<arrow_array::types::Int32Type>()
+                    .value(0),
+                5
+            );
+            assert_eq!(value(1).as_string::<i32>().value(0), "five");
+            assert_eq!(value(2).as_binary::<i32>().value(0), &[5]);
+            assert_eq!(
+                value(3)
+                    .as_primitive::<arrow_array::types::Int32Type>()
+                    .value(0),
+                6
+            );
+        }
+    }
+
+    async fn decode_map_list(
+        policy: MapNullValuePolicy,
+    ) -> Vec<Option<Vec<(String, Option<i64>)>>> {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "counts", "type": {"type": "array", "items": [
+                "null",
+                {"type": "map", "values": ["null", "long"]}
+            ]}}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.map_null_values = policy;
+        format.add_reader_schema(avro_schema.clone());
+
+        let entries = Field::new(
+            "entries",
+            DataType::Struct(Fields::from(vec![
+                Field::new("keys", DataType::Utf8, false),
+                Field::new("values", DataType::Int64, true),
+            ])),
+            false,
+        );
+        let item = Field::new("item", DataType::Map(Arc::new(entries), false), true);
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("counts", DataType::List(Arc::new(item)), false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let datum = apache_avro::to_avro_datum(
+            &avro_schema,
+            Value::Record(vec![(
+                "counts".to_string(),
+                Value::Array(vec![
+                    Value::Union(
+                        1,
+                        Box::new(Value::Map(HashMap::from([
+                            ("a".to_string(), Value::Union(1, Box::new(Value::Long(1)))),
+                            ("b".to_string(), Value::Union(0, Box::new(Value::Null))),
+                        ]))),
+                    ),
+                    Value::Union(0, Box::new(Value::Null)),
+                    Value::Union(1, Box::new(Value::Map(HashMap::new()))),
+                ]),
+            )]),
+        )
+        .unwrap();
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &datum, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        let list = batch.column(0).as_list::<i32>();
+        let maps = list.value(0);
+        let maps = maps.as_map();
+
+        (0..maps.len())
+            .map(|i| {
+                maps.is_valid(i).then(|| {
+                    let entries = maps.value(i);
+                    let keys = entries.column(0).as_string::<i32>();
+                    let values = entries
+                        .column(1)
+                        .as_primitive::<arrow_array::types::Int64Type>();
+                    let mut entries: Vec<_> = (0..entries.len())
+                        .map(|j| {
+                            (
+                                keys.value(j).to_string(),
+                                values.is_valid(j).then(|| values.value(j)),
+                            )
+                        })
+                        .collect();
+                    entries.sort();
+                    entries
+                })
+            })
+            .collect()
+    }
+
+    #[tokio::test]
+    async fn test_list_of_maps() {
+        assert_eq!(
+            decode_map_list(MapNullValuePolicy::Preserve).await,
+            vec![
+                Some(vec![("a".to_string(), Some(1)), ("b".to_string(), None)]),
+                None,
+                Some(vec![]),
+            ]
+        );
+
+        // null values are also dropped from maps nested in lists
+        assert_eq!(
+            decode_map_list(MapNullValuePolicy::Drop).await,
+            vec![Some(vec![("a".to_string(), Some(1))]), None, Some(vec![])]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_list_of_nullable_strings() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "tags", "type": {"type": "array", "items": ["null", "string"]}}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema.clone());
+
+        let item = Field::new("item", DataType::Utf8, true);
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("tags", DataType::List(Arc::new(item)), false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let tag = |t: Option<&str>| match t {
+            Some(t) => Value::Union(1, Box::new(Value::String(t.to_string()))),
+            None => Value::Union(0, Box::new(Value::Null)),
+        };
+        let datum = apache_avro::to_avro_datum(
+            &avro_schema,
+            Value::Record(vec![(
+                "tags".to_string(),
+                Value::Array(vec![tag(None), tag(Some("a")), tag(None), tag(Some("b"))]),
+            )]),
+        )
+        .unwrap();
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &datum, SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        let tags = batch.column(0).as_list::<i32>().value(0);
+        assert_eq!(
+            tags.as_string::<i32>().iter().collect::<Vec<_>>(),
+            vec![None, Some("a"), None, Some("b")]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_record_union_tagged() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "event", "type": [
+                {"type": "record", "name": "Click", "fields": [
+                    {"name": "user", "type": "long"},
+                    {"name": "url", "type": "string"}
+                ]},
+                {"type": "record", "name": "View", "fields": [
+                    {"name": "user", "type": "long"},
+                    {"name": "duration", "type": "long"}
+                ]}
+            ]}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema.clone());
+
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new(
+                "event",
+                DataType::Struct(Fields::from(vec![
+                    Field::new("__type", DataType::Utf8, false),
+                    Field::new("user", DataType::Int64, false),
+                    Field::new("url", DataType::Utf8, true),
+                    Field::new("duration", DataType::Int64, true),
+                ])),
+                false,
+            ),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), arroyo_schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        let rows = [
+            Value::Union(
+                0,
+                Box::new(Value::Record(vec![
+                    ("user".to_string(), Value::Long(1)),
+                    ("url".to_string(), Value::String("/home".to_string())),
+                ])),
+            ),
+            Value::Union(
+                1,
+                Box::new(Value::Record(vec![
+                    ("user".to_string(), Value::Long(2)),
+                    ("duration".to_string(), Value::Long(30)),
+                ])),
+            ),
+        ];
+
+        for row in rows {
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![("event".to_string(), row)]),
+            )
+            .unwrap();
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        let event = batch.column(0).as_struct();
+        assert_eq!(
+            event
+                .column(0)
+                .as_string::<i32>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Some("Click"), Some("View")]
+        );
+        assert_eq!(
+            event
+                .column(1)
+                .as_primitive::<arrow_array::types::Int64Type>()
+                .values()
+                .to_vec(),
+            vec![1, 2]
+        );
+        assert_eq!(
+            event
+                .column(2)
+                .as_string::<i32>()
+                .iter()
+                .collect::<Vec<_>>(),
+            vec![Some("/home"), None]
+        );
+        let duration = event
+            .column(3)
+            .as_primitive::<arrow_array::types::Int64Type>();
+        assert!(duration.is_null(0));
+        assert_eq!(duration.value(1), 30);
+    }
+
+    #[tokio::test]
+    async fn test_mixed_framed_and_unframed() {
+        let schema =
+            r#"{"type": "record", "name": "r", "fields": [{"name": "x", "type": "long"}]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+        let datum = |x: i64| {
+            apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![("x".to_string(), Value::Long(x))]),
+            )
+            .unwrap()
         };
 
-        (
-            ArrowDeserializer::with_schema_resolver(
-                Format::Avro(format),
-                None,
-                arroyo_schema.clone(),
-                BadData::Fail {},
-                resolver,
-            ),
-            builders,
-            arroyo_schema,
-        )
-    }
+        let framed = [vec![0, 0, 0, 0, 1], datum(3)].concat();
+        let unframed = datum(5);
 
-    async fn deserialize_with_schema(
-        format: AvroFormat,
-        writer_schema: Option<&str>,
-        message: &[u8],
-    ) -> Vec<serde_json::Map<String, serde_json::Value>> {
-        let (mut deserializer, mut builders, arroyo_schema) =
-            deserializer_with_schema(format.clone(), writer_schema);
+        let mut format = AvroFormat::new(true, false, false);
+        format.add_reader_schema(avro_schema.clone());
 
+        let (mut deserializer, mut builders, _) =
+            deserializer_with_schema(format.clone(), Some(schema));
         let errors = deserializer
-            .deserialize_slice(&mut builders, message, SystemTime::now())
+            .deserialize_slice(&mut builders, &unframed, SystemTime::now())
             .await;
-        assert_eq!(errors, vec![]);
+        assert_eq!(errors.len(), 1);
 
-        let batch = if format.into_unstructured_json {
-            RecordBatch::try_new(
-                arroyo_schema.schema,
-                builders.into_iter().map(|mut b| b.finish()).collect(),
-            )
-            .unwrap()
-        } else {
-            deserializer.flush_buffer().unwrap().unwrap()
-        };
+        format.unframed_messages = UnframedMessagePolicy::ReaderSchema;
+        let (mut deserializer, mut builders, _) = deserializer_with_schema(format, Some(schema));
+        for message in [&framed, &unframed] {
+            let errors = deserializer
+                .deserialize_slice(&mut builders, message, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
 
-        #[allow(deprecated)]
-        arrow_json::writer::record_batches_to_json_rows(&[&batch])
-            .unwrap()
-            .into_iter()
-            .map(|mut r| {
-                r.remove("_timestamp");
-                r
-            })
-            .collect()
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<arrow_array::types::Int64Type>()
+                .values()
+                .to_vec(),
+            vec![3, 5]
+        );
     }
 
-    #[tokio::test]
-    async fn test_avro_deserialization() {
-        let message = [
-            0u8, 0, 0, 0, 1, 8, 200, 223, 1, 144, 31, 186, 159, 2, 16, 97, 99, 99, 101, 112, 116,
-            101, 100, 4, 156, 1, 10, 112, 105, 122, 122, 97, 4, 102, 102, 102, 102, 102, 230, 38,
-            64, 102, 102, 102, 102, 102, 230, 54, 64, 84, 14, 100, 101, 115, 115, 101, 114, 116, 2,
-            113, 61, 10, 215, 163, 112, 26, 64, 113, 61, 10, 215, 163, 112, 26, 64, 0, 10,
-        ];
+    #[test]
+    fn test_epoch_offsets() {
+        let offsets = BTreeMap::from([
+            // days since 1900-01-01
+            ("born".to_string(), -2_208_988_800),
+            // milliseconds since 2000-01-01
+            ("updated".to_string(), 946_684_800),
+        ]);
 
-        let mut format = AvroFormat::new(true, false, false);
-        format.add_reader_schema(apache_avro::Schema::parse_str(SCHEMA).unwrap());
-        let row = deserialize_with_schema(format, Some(SCHEMA), &message)
-            .await
-            .remove(0);
-        assert_eq!(*row.get("store_id").unwrap(), json!(4));
-        assert_eq!(*row.get("store_order_id").unwrap(), json!(14308));
-        assert_eq!(*row.get("coupon_code").unwrap(), json!(1992));
-        assert_eq!(*row.get("date").unwrap(), json!(18397));
-        assert_eq!(*row.get("status").unwrap(), json!("accepted"));
+        let date_field = Field::new("born", DataType::Date32, false);
+        let mut value = Value::Date(45000);
+        apply_epoch_offset("born", &date_field, &mut value, &offsets).unwrap();
+        // 45000 days after 1900-01-01 is 2023-03-17
+        assert_eq!(value, Value::Date(19433));
 
-        let order_line_expected = json!(
-            [{"category":"pizza","net_price":22.9,"product_id":78,"quantity":2,"unit_price":11.45},{"category":"dessert","net_price":6.61,"product_id":42,"quantity":1,"unit_price":6.61}]
+        let timestamp_field = Field::new(
+            "updated",
+            DataType::Timestamp(TimeUnit::Millisecond, None),
+            true,
+        );
+        let mut value = Value::Union(1, Box::new(Value::TimestampMillis(1_500)));
+        apply_epoch_offset("updated", &timestamp_field, &mut value, &offsets).unwrap();
+        assert_eq!(
+            value,
+            Value::Union(1, Box::new(Value::TimestampMillis(946_684_801_500)))
         );
 
-        assert_eq!(*row.get("order_lines").unwrap(), order_line_expected);
-    }
+        // plain longs decoded into timestamp columns are shifted in the column's unit
+        let mut value = Value::Long(1_500);
+        apply_epoch_offset("updated", &timestamp_field, &mut value, &offsets).unwrap();
+        assert_eq!(value, Value::Long(946_684_801_500));
 
-    #[tokio::test]
-    async fn test_add_field() {
-        // test schema evolution for adding a field (new_field)
-        let reader_schema = r#"{"namespace": "example.avro",
-            "type": "record",
-            "name": "User",
-            "fields": [
-                {"name": "name", "type": "string"},
-                {"name": "favorite_number", "type": "int"},
-                {"name": "favorite_color", "type": ["string", "null"]}
-            ]
-        }"#;
+        let mut value = Value::Date(45000);
+        apply_epoch_offset("other", &date_field, &mut value, &offsets).unwrap();
+        assert_eq!(value, Value::Date(45000));
 
-        let writer_schema = r#"{"namespace": "example.avro",
-            "type": "record",
-            "name": "User",
-            "fields": [
-            {"name": "name", "type": "string"},
-            {"name": "favorite_number", "type": "int"},
-            {"name": "favorite_color", "type": ["string", "null"]},
-            {"name": "new_field", "type": "string", "default": "hello!"}
-            ]
-        }"#;
+        let mut value = Value::TimestampMillis(i64::MAX);
+        assert!(apply_epoch_offset("updated", &timestamp_field, &mut value, &offsets).is_err());
+    }
 
-        let mut format = AvroFormat::new(true, false, false);
-        format.add_reader_schema(apache_avro::Schema::parse_str(reader_schema).unwrap());
+    #[test]
+    fn test_field_transforms() {
+        use FieldTransform::*;
 
-        let schema = apache_avro::Schema::parse_str(writer_schema).unwrap();
-        let mut value = apache_avro::types::Record::new(&schema).unwrap();
-        value.put(
-            "name",
-            apache_avro::types::Value::String("Alyssa".to_string()),
+        let transform = |transforms: Vec<FieldTransform>, value: Value| {
+            let mut value = value;
+            let transforms = BTreeMap::from([("email".to_string(), transforms)]);
+            apply_field_transforms("email", &mut value, &transforms).map(|_| value)
+        };
+
+        let raw = || Value::String("  Alice ".to_string());
+        assert_eq!(
+            transform(vec![Trim, Lowercase], raw()).unwrap(),
+            Value::String("alice".to_string())
         );
-        value.put("favorite_number", apache_avro::types::Value::Int(256));
-        value.put(
-            "favorite_color",
-            apache_avro::types::Value::Union(1, Box::new(apache_avro::types::Value::Null)),
+
+        // sha256("alice"), as the value is normalized before it's hashed
+        assert_eq!(
+            transform(vec![Trim, Lowercase, Hash], raw()).unwrap(),
+            Value::String(
+                "2bd806c97f0e00af1a1fc3328fa763a9269723c8db8fac4f93af71db186d6e90".to_string()
+            )
         );
-        value.put(
-            "new_field",
-            apache_avro::types::Value::String("new".to_string()),
+
+        // in the other order, the raw value is hashed and the digest is then uppercased
+        assert_eq!(
+            transform(vec![Hash, Uppercase], raw()).unwrap(),
+            Value::String(
+                "E04C4DFF152E50452CD30D41B1A2D332519BDC3D21F4B95CE06587068FDA45BE".to_string()
+            )
         );
 
-        let mut bytes = vec![0, 0, 0, 0, 1];
-        bytes.extend_from_slice(
-            &apache_avro::to_avro_datum(
-                &apache_avro::Schema::parse_str(writer_schema).unwrap(),
-                value,
+        assert_eq!(
+            transform(
+                vec![Trim, Uppercase],
+                Value::Union(1, Box::new(Value::String(" bob".to_string())))
             )
             .unwrap(),
+            Value::Union(1, Box::new(Value::String("BOB".to_string())))
         );
-
-        let v = deserialize_with_schema(format, Some(writer_schema), &bytes[..]).await;
         assert_eq!(
-            serde_json::to_value(v).unwrap(),
-            json!([{
-                "name": "Alyssa",
-                "favorite_number": 256,
-            }])
+            transform(vec![Hash], Value::Union(0, Box::new(Value::Null))).unwrap(),
+            Value::Union(0, Box::new(Value::Null))
         );
+        assert!(transform(vec![Trim], Value::Long(5)).is_err());
+    }
+
+    #[test]
+    fn test_non_finite_decimals() {
+        let field = Field::new("price", DataType::Decimal128(10, 2), true);
+
+        for value in [
+            Value::Double(f64::NAN),
+            Value::Float(f32::INFINITY),
+            Value::Union(1, Box::new(Value::Double(f64::NEG_INFINITY))),
+            Value::String("NaN".to_string()),
+            Value::String("-Infinity".to_string()),
+        ] {
+            let mut v = value.clone();
+            assert!(
+                check_decimal_finite("price", &field, &mut v, NonFiniteDecimalPolicy::Error)
+                    .is_err(),
+                "{:?} should be rejected",
+                value
+            );
+
+            let mut v = value.clone();
+            check_decimal_finite("price", &field, &mut v, NonFiniteDecimalPolicy::Null).unwrap();
+            assert_eq!(v, Value::Null);
+        }
+
+        // finite values, and non-finite values in other columns, are left alone
+        let mut v = Value::String("12.50".to_string());
+        check_decimal_finite("price", &field, &mut v, NonFiniteDecimalPolicy::Error).unwrap();
+        assert_eq!(v, Value::String("12.50".to_string()));
+
+        let float_field = Field::new("ratio", DataType::Float64, true);
+        let mut v = Value::Double(f64::NAN);
+        check_decimal_finite("ratio", &float_field, &mut v, NonFiniteDecimalPolicy::Null).unwrap();
+        assert!(matches!(v, Value::Double(f) if f.is_nan()));
     }
 
     #[tokio::test]
-    async fn test_remove_field() {
-        // test schema evolution for removing a field with a default value
-        let writer_schema = r#"{"namespace": "example.avro",
+    async fn test_column_values_reach_batch() {
+        let schema = r#"{
             "type": "record",
-            "name": "User",
+            "name": "Row",
             "fields": [
-            {"name": "name", "type": "string"},
-            {"name": "favorite_number", "type": "int"},
-            {"name": "favorite_color", "type": ["string", "null"]}
+                {"name": "a", "type": "int"},
+                {"name": "s", "type": "string"},
+                {"name": "inner", "type": {"type": "record", "name": "Inner", "fields": [
+                    {"name": "x", "type": "int"}
+                ]}}
             ]
         }"#;
 
-        let reader_schema = r#"{"namespace": "example.avro",
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(apache_avro::Schema::parse_str(schema).unwrap());
+        let (mut deserializer, mut builders, _) = deserializer_with_schema(format, Some(schema));
+
+        // a = 7, s = "hi", inner.x = -3
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &[14, 4, b'h', b'i', 5], SystemTime::now())
+            .await;
+        assert_eq!(errors, vec![]);
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<arrow_array::types::Int32Type>()
+                .value(0),
+            7
+        );
+        assert_eq!(batch.column(1).as_string::<i32>().value(0), "hi");
+        let inner = batch.column(2).as_struct();
+        assert_eq!(
+            inner
+                .column(0)
+                .as_primitive::<arrow_array::types::Int32Type>()
+                .value(0),
+            -3
+        );
+        for column in batch.columns() {
+            assert_eq!(column.null_count(), 0);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_empty_record_field() {
+        let schema = r#"{
             "type": "record",
-            "name": "User",
+            "name": "Event",
             "fields": [
-                {"name": "name", "type": "string"},
-                {"name": "favorite_number", "type": "int"},
-                {"name": "favorite_color", "type": ["string", "null"]},
-                {"name": "removed_field", "type": "string", "default": "hello!"}
+                {"name": "id", "type": "long"},
+                {"name": "empty", "type": {"type": "record", "name": "Empty", "fields": []}},
+                {"name": "maybe", "type": ["null", {"type": "record", "name": "Maybe", "fields": []}]}
             ]
         }"#;
 
-        let schema = apache_avro::Schema::parse_str(writer_schema).unwrap();
-        let mut value = apache_avro::types::Record::new(&schema).unwrap();
-        value.put(
-            "name",
-            apache_avro::types::Value::String("Alyssa".to_string()),
-        );
-        value.put("favorite_number", apache_avro::types::Value::Int(256));
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(apache_avro::Schema::parse_str(schema).unwrap());
+        let (mut deserializer, mut builders, _) = deserializer_with_schema(format, Some(schema));
 
-        let mut bytes = vec![0, 0, 0, 0, 1];
-        bytes.extend_from_slice(
-            &apache_avro::to_avro_datum(
-                &apache_avro::Schema::parse_str(writer_schema).unwrap(),
-                value,
-            )
-            .unwrap(),
-        );
+        // id = 5 with a null `maybe`, then id = 6 with an empty `maybe`
+        for message in [[10u8, 0], [12, 2]] {
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &message, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
 
-        let mut format = AvroFormat::new(true, false, false);
-        format.add_reader_schema(apache_avro::Schema::parse_str(reader_schema).unwrap());
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.num_rows(), 2);
 
-        let v = deserialize_with_schema(format, Some(writer_schema), bytes.as_slice()).await;
-        assert_eq!(
-            serde_json::to_value(v).unwrap(),
-            json!([{
-                "name": "Alyssa",
-                "favorite_number": 256,
-                "removed_field": "hello!"
-            }])
-        );
+        let empty = batch.column(1).as_struct();
+        empty.to_data().validate_full().unwrap();
+        assert_eq!(empty.len(), 2);
+        assert_eq!(empty.num_columns(), 0);
+        assert_eq!(empty.null_count(), 0);
+
+        let maybe = batch.column(2).as_struct();
+        maybe.to_data().validate_full().unwrap();
+        assert_eq!(maybe.len(), 2);
+        assert!(maybe.is_null(0));
+        assert!(maybe.is_valid(1));
     }
 
     #[tokio::test]
-    async fn test_embedded() {
-        let data = [
-            79u8, 98, 106, 1, 4, 20, 97, 118, 114, 111, 46, 99, 111, 100, 101, 99, 8, 110, 117,
-            108, 108, 22, 97, 118, 114, 111, 46, 115, 99, 104, 101, 109, 97, 186, 3, 123, 34, 116,
-            121, 112, 101, 34, 58, 32, 34, 114, 101, 99, 111, 114, 100, 34, 44, 32, 34, 110, 97,
-            109, 101, 34, 58, 32, 34, 85, 115, 101, 114, 34, 44, 32, 34, 110, 97, 109, 101, 115,
-            112, 97, 99, 101, 34, 58, 32, 34, 101, 120, 97, 109, 112, 108, 101, 46, 97, 118, 114,
-            111, 34, 44, 32, 34, 102, 105, 101, 108, 100, 115, 34, 58, 32, 91, 123, 34, 116, 121,
-            112, 101, 34, 58, 32, 34, 115, 116, 114, 105, 110, 103, 34, 44, 32, 34, 110, 97, 109,
-            101, 34, 58, 32, 34, 110, 97, 109, 101, 34, 125, 44, 32, 123, 34, 116, 121, 112, 101,
-            34, 58, 32, 91, 34, 105, 110, 116, 34, 44, 32, 34, 110, 117, 108, 108, 34, 93, 44, 32,
-            34, 110, 97, 109, 101, 34, 58, 32, 34, 102, 97, 118, 111, 114, 105, 116, 101, 95, 110,
-            117, 109, 98, 101, 114, 34, 125, 44, 32, 123, 34, 116, 121, 112, 101, 34, 58, 32, 91,
-            34, 115, 116, 114, 105, 110, 103, 34, 44, 32, 34, 110, 117, 108, 108, 34, 93, 44, 32,
-            34, 110, 97, 109, 101, 34, 58, 32, 34, 102, 97, 118, 111, 114, 105, 116, 101, 95, 99,
-            111, 108, 111, 114, 34, 125, 93, 125, 0, 52, 104, 70, 176, 108, 101, 199, 71, 44, 76,
-            126, 49, 211, 19, 204, 87, 4, 44, 12, 65, 108, 121, 115, 115, 97, 0, 128, 4, 2, 6, 66,
-            101, 110, 0, 14, 0, 6, 114, 101, 100, 52, 104, 70, 176, 108, 101, 199, 71, 44, 76, 126,
-            49, 211, 19, 204, 87,
-        ];
+    async fn test_duration_field() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "d", "type": {"type": "fixed", "name": "D", "size": 12, "logicalType": "duration"}},
+            {"name": "maybe", "type": ["null", {"type": "fixed", "name": "M", "size": 12, "logicalType": "duration"}]}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
 
-        let format = AvroFormat::new(false, false, true);
-        let vs = deserialize_with_schema(format, None, &data).await;
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema.clone());
 
-        let expected = vec![
-            json!({ "name": "Alyssa", "favorite_number": 256, "favorite_color": null }),
-            json!({ "name": "Ben", "favorite_number": 7, "favorite_color": "red" }),
-        ];
+        // durations are only decoded as intervals into a declared interval column
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(vec![
+            Field::new("d", DataType::Interval(IntervalUnit::MonthDayNano), false),
+            Field::new(
+                "maybe",
+                DataType::Interval(IntervalUnit::MonthDayNano),
+                true,
+            ),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ])))
+        .unwrap();
 
-        for (a, b) in vs.iter().zip(expected) {
-            let v: serde_json::Value =
-                serde_json::from_str(a.get("value").unwrap().as_str().unwrap()).unwrap();
-            assert_eq!(v, b);
-        }
-    }
+        let mut deserializer = ArrowDeserializer::new(
+            Format::Avro(format),
+            arroyo_schema.clone(),
+            None,
+            BadData::Fail {},
+        );
+        let mut builders = vec![];
 
-    #[tokio::test]
-    async fn test_datum_static_schema() {
-        let data = [12, 65, 108, 121, 115, 115, 97, 0, 128, 4, 2];
+        let duration = |months: u32, days: u32, millis: u32| {
+            Value::Duration(apache_avro::Duration::new(
+                months.into(),
+                days.into(),
+                millis.into(),
+            ))
+        };
 
-        let schema_str = r#"{"namespace": "example.avro",
-            "type": "record",
-            "name": "User",
-            "fields": [
-            {"name": "name", "type": "string"},
-            {"name": "favorite_number",  "type": ["int", "null"]},
-            {"name": "favorite_color", "type": ["string", "null"]}
-            ]
-        }"#;
+        // `maybe` is 1 day in the first record and null in the second
+        for maybe in [
+            Value::Union(1, Box::new(duration(0, 1, 0))),
+            Value::Union(0, Box::new(Value::Null)),
+        ] {
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(vec![
+                    ("d".to_string(), duration(1, 2, 3000)),
+                    ("maybe".to_string(), maybe),
+                ]),
+            )
+            .unwrap();
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
 
-        let mut format = AvroFormat::new(false, true, true);
-        format.add_reader_schema(apache_avro::Schema::parse_str(schema_str).unwrap());
-        let vs = deserialize_with_schema(format, Some(schema_str), &data).await;
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.schema(), arroyo_schema.schema);
 
-        let expected = json!({ "name": "Alyssa", "favorite_number": 256, "favorite_color": null });
+        let d = batch.column(0).as_primitive::<IntervalMonthDayNanoType>();
+        let expected = IntervalMonthDayNanoType::make_value(1, 2, 3_000_000_000);
+        assert_eq!(d.values().to_vec(), vec![expected, expected]);
 
+        let maybe = batch.column(1).as_primitive::<IntervalMonthDayNanoType>();
         assert_eq!(
-            serde_json::from_str::<serde_json::Value>(
-                vs[0].get("value").unwrap().as_str().unwrap()
-            )
-            .unwrap(),
-            expected
+            maybe.value(0),
+            IntervalMonthDayNanoType::make_value(0, 1, 0)
         );
+        assert!(maybe.is_null(1));
+    }
+
+    #[test]
+    fn test_field_lookup_reordered() {
+        // fields in the opposite order to the schema they were written with
+        let value = Value::Record(vec![
+            ("b".to_string(), Value::Long(2)),
+            ("a".to_string(), Value::Long(1)),
+        ]);
+
+        assert_eq!(field_lookup(&value, "a"), Some(&Value::Long(1)));
+        assert_eq!(field_lookup(&value, "b"), Some(&Value::Long(2)));
+        assert_eq!(field_lookup(&value, "c"), None);
+
+        let union = Value::Union(1, Box::new(value));
+        assert_eq!(field_lookup(&union, "a"), Some(&Value::Long(1)));
+        assert_eq!(field_lookup(&Value::Long(1), "a"), None);
+
+        let required = BTreeMap::from([(1, vec!["a".to_string(), "b".to_string()])]);
+        assert!(check_required_fields(&required, 1, &union).is_ok());
     }
 }
@@ -1,9 +1,12 @@
 use anyhow::{anyhow, bail};
 use apache_avro::Schema;
-use arrow_schema::{DataType, Field, Fields, TimeUnit};
-use arroyo_rpc::formats::AvroFormat;
+use arrow_schema::{
+    DataType, Field, Fields, TimeUnit, DECIMAL128_MAX_PRECISION, DECIMAL256_MAX_PRECISION,
+};
+use arroyo_rpc::formats::{AvroFormat, LogicalTypeMapping};
 use arroyo_types::ArroyoExtensionType;
 use serde_json::json;
+use std::collections::BTreeMap;
 use std::sync::Arc;
 
 /// Computes an avro schema from an arrow schema
@@ -21,10 +24,19 @@ pub fn to_avro(name: &str, fields: &Fields) -> Schema {
 
 /// Computes an arrow schema from an avro schema
 pub fn to_arrow(schema: &str) -> anyhow::Result<arrow_schema::Schema> {
+    to_arrow_with_mappings(schema, &BTreeMap::new())
+}
+
+/// Computes an arrow schema from an avro schema, using the configured Arrow type for logical
+/// types that appear in `mappings` rather than the default
+pub fn to_arrow_with_mappings(
+    schema: &str,
+    mappings: &BTreeMap<String, LogicalTypeMapping>,
+) -> anyhow::Result<arrow_schema::Schema> {
     let schema =
         Schema::parse_str(schema).map_err(|e| anyhow!("avro schema is not valid: {:?}", e))?;
 
-    let (dt, _, _) = to_arrow_datatype(&schema);
+    let (dt, _, _) = to_arrow_datatype(&schema, mappings);
     let fields = match dt {
         DataType::Struct(fields) => fields,
         _ => {
@@ -100,8 +112,18 @@ fn arrow_to_avro(name: &str, dt: &DataType) -> serde_json::value::Value {
         }
         DataType::Union(_, _) => unimplemented!("unions are not supported"),
         DataType::Dictionary(_, _) => unimplemented!("dictionaries are not supported"),
-        DataType::Decimal128(_, _) => unimplemented!("decimal128 is not supported"),
-        DataType::Decimal256(_, _) => unimplemented!("decimal256 is not supported"),
+        DataType::Decimal128(precision, scale) | DataType::Decimal256(precision, scale) => {
+            if *scale < 0 {
+                unimplemented!("decimals with negative scale are not supported");
+            }
+
+            return json!({
+                "type": "bytes",
+                "logicalType": "decimal",
+                "precision": precision,
+                "scale": scale,
+            });
+        }
         DataType::Map(_, _) => unimplemented!("maps are not supported"),
         DataType::RunEndEncoded(_, _) => unimplemented!("run end encoded is not supported"),
         DataType::BinaryView => unimplemented!("binary view is not supported"),
@@ -115,7 +137,60 @@ fn arrow_to_avro(name: &str, dt: &DataType) -> serde_json::value::Value {
     })
 }
 
-fn to_arrow_datatype(schema: &Schema) -> (DataType, bool, Option<ArroyoExtensionType>) {
+fn logical_type_name(schema: &Schema) -> Option<&'static str> {
+    match schema {
+        Schema::Decimal(_) => Some("decimal"),
+        Schema::TimestampMillis => Some("timestamp-millis"),
+        Schema::TimestampMicros => Some("timestamp-micros"),
+        Schema::LocalTimestampMillis => Some("local-timestamp-millis"),
+        Schema::LocalTimestampMicros => Some("local-timestamp-micros"),
+        Schema::Uuid => Some("uuid"),
+        _ => None,
+    }
+}
+
+fn mapped_datatype(mapping: LogicalTypeMapping, schema: &Schema) -> Option<DataType> {
+    Some(match mapping {
+        LogicalTypeMapping::TimestampMillis => DataType::Timestamp(TimeUnit::Millisecond, None),
+        LogicalTypeMapping::TimestampMicros => DataType::Timestamp(TimeUnit::Microsecond, None),
+        LogicalTypeMapping::TimestampNanos => DataType::Timestamp(TimeUnit::Nanosecond, None),
+        LogicalTypeMapping::Int64 => DataType::Int64,
+        LogicalTypeMapping::Float64 => DataType::Float64,
+        LogicalTypeMapping::Binary => DataType::Binary,
+        LogicalTypeMapping::Decimal => {
+            let Schema::Decimal(decimal) = schema else {
+                return None;
+            };
+            let scale = decimal.scale as i8;
+            if decimal.precision <= DECIMAL128_MAX_PRECISION as usize {
+                DataType::Decimal128(decimal.precision as u8, scale)
+            } else {
+                DataType::Decimal256(
+                    decimal.precision.min(DECIMAL256_MAX_PRECISION as usize) as u8,
+                    scale,
+                )
+            }
+        }
+        LogicalTypeMapping::FixedSizeBinary => {
+            if !matches!(schema, Schema::Uuid) {
+                return None;
+            }
+            DataType::FixedSizeBinary(16)
+        }
+    })
+}
+
+fn to_arrow_datatype(
+    schema: &Schema,
+    mappings: &BTreeMap<String, LogicalTypeMapping>,
+) -> (DataType, bool, Option<ArroyoExtensionType>) {
+    if let Some(dt) = logical_type_name(schema)
+        .and_then(|name| mappings.get(name))
+        .and_then(|mapping| mapped_datatype(*mapping, schema))
+    {
+        return (dt, false, None);
+    }
+
     match schema {
         Schema::Null => (DataType::Null, false, None),
         Schema::Boolean => (DataType::Boolean, false, None),
@@ -146,7 +221,7 @@ fn to_arrow_datatype(schema: &Schema) -> (DataType, bool, Option<ArroyoExtension
                 .partition(|v| matches!(v, Schema::Null));
 
             if nulls.len() == 1 && not_nulls.len() == 1 {
-                let (dt, _, ext) = to_arrow_datatype(not_nulls[0]);
+                let (dt, _, ext) = to_arrow_datatype(not_nulls[0], mappings);
                 (dt, true, ext)
             } else {
                 (DataType::Utf8, false, Some(ArroyoExtensionType::JSON))
@@ -157,7 +232,7 @@ fn to_arrow_datatype(schema: &Schema) -> (DataType, bool, Option<ArroyoExtension
                 .fields
                 .iter()
                 .map(|f| {
-                    let (dt, nullable, extension) = to_arrow_datatype(&f.schema);
+                    let (dt, nullable, extension) = to_arrow_datatype(&f.schema, mappings);
                     Arc::new(ArroyoExtensionType::add_metadata(
                         extension,
                         Field::new(&f.name, dt, nullable),
@@ -167,6 +242,9 @@ fn to_arrow_datatype(schema: &Schema) -> (DataType, bool, Option<ArroyoExtension
 
             (DataType::Struct(fields), false, None)
         }
+        // a reference to a named type, which is how a record refers to itself; recursive types
+        // have no Arrow equivalent, so these are kept as JSON rather than expanded
+        Schema::Ref { .. } => (DataType::Utf8, false, Some(ArroyoExtensionType::JSON)),
         _ => (DataType::Utf8, false, Some(ArroyoExtensionType::JSON)),
     }
 }
@@ -1,9 +1,10 @@
 use apache_avro::types::{Record, Value};
-use apache_avro::Schema;
+use apache_avro::{Decimal, Schema};
+use arrow::datatypes::i256;
 use arrow_array::cast::AsArray;
 use arrow_array::types::{
-    Float16Type, Float32Type, Float64Type, Int32Type, Int64Type, Int8Type, TimestampNanosecondType,
-    UInt32Type, UInt64Type, UInt8Type,
+    Decimal128Type, Decimal256Type, Float16Type, Float32Type, Float64Type, Int32Type, Int64Type,
+    Int8Type, TimestampNanosecondType, UInt32Type, UInt64Type, UInt8Type,
 };
 use arrow_array::{Array, ArrayRef, RecordBatch};
 use arrow_schema::{DataType, TimeUnit};
@@ -142,6 +143,17 @@ fn serialize_column<T: SerializeTarget>(
             |v| (v / 86400000) as i32
         ),
 
+        DataType::Decimal128(_, _) => write_arrow_value!(
+            ArrayRef::as_primitive::<Decimal128Type>,
+            Value::Decimal,
+            |v: i128| Decimal::from(v.to_be_bytes())
+        ),
+        DataType::Decimal256(_, _) => write_arrow_value!(
+            ArrayRef::as_primitive::<Decimal256Type>,
+            Value::Decimal,
+            |v: i256| Decimal::from(v.to_be_bytes())
+        ),
+
         DataType::Binary => {
             write_arrow_value!(ArrayRef::as_binary::<i32>, Value::Bytes, |v: &[u8]| v
                 .to_vec())
@@ -286,9 +298,11 @@ pub fn serialize(schema: &Schema, batch: &RecordBatch) -> Vec<Value> {
 mod tests {
     use crate::avro::schema::to_avro;
     use crate::avro::ser::serialize;
+    use apache_avro::Schema as AvroSchema;
     use arrow_array::builder::{Int64Builder, ListBuilder, StringBuilder, StructBuilder};
     use arrow_array::RecordBatch;
-    use arrow_schema::{DataType, Field, Schema};
+    use arrow_schema::{DataType, Field, Schema, TimeUnit};
+    use serde_json::json;
     use std::sync::Arc;
 
     #[test]
@@ -518,4 +532,77 @@ mod tests {
             ]
         )
     }
+
+    #[test]
+    fn test_schema_generation() {
+        let arrow_schema = Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("user name", DataType::Utf8, true),
+            Field::new("2fa", DataType::Boolean, false),
+            Field::new(
+                "created",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+            Field::new("price", DataType::Decimal128(10, 2), true),
+            Field::new(
+                "tags",
+                DataType::List(Arc::new(Field::new("item", DataType::Utf8, false))),
+                false,
+            ),
+            Field::new(
+                "address",
+                DataType::Struct(
+                    vec![
+                        Field::new("city", DataType::Utf8, false),
+                        Field::new("zip", DataType::Utf8, true),
+                    ]
+                    .into(),
+                ),
+                true,
+            ),
+        ]);
+
+        let schema = to_avro("User", &arrow_schema.fields);
+
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(&schema.canonical_form()).unwrap(),
+            json!({
+                "name": "User",
+                "type": "record",
+                "fields": [
+                    {"name": "id", "type": "long"},
+                    {"name": "user_name", "type": ["null", "string"]},
+                    {"name": "_2fa", "type": "boolean"},
+                    {"name": "created", "type": "long"},
+                    {"name": "price", "type": ["null", "bytes"]},
+                    {"name": "tags", "type": {"type": "array", "items": "string"}},
+                    {"name": "address", "type": ["null", {
+                        "name": "User_address",
+                        "type": "record",
+                        "fields": [
+                            {"name": "city", "type": "string"},
+                            {"name": "zip", "type": ["null", "string"]}
+                        ]
+                    }]}
+                ]
+            })
+        );
+
+        // logical types are dropped from the canonical form
+        let AvroSchema::Record(record) = &schema else {
+            panic!("expected a record");
+        };
+        assert!(matches!(
+            record.fields[3].schema,
+            AvroSchema::TimestampMicros
+        ));
+        let AvroSchema::Union(price) = &record.fields[4].schema else {
+            panic!("expected a union");
+        };
+        let AvroSchema::Decimal(decimal) = &price.variants()[1] else {
+            panic!("expected a decimal");
+        };
+        assert_eq!((decimal.precision, decimal.scale), (10, 2));
+    }
 }
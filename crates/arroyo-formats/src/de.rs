@@ -1,19 +1,24 @@
 use crate::avro::de;
 use crate::should_flush;
+use apache_avro::types::Value as AvroValue;
+use apache_avro::{AvroResult, Reader};
 use arrow::compute::kernels;
 use arrow_array::builder::{
     ArrayBuilder, GenericByteBuilder, StringBuilder, TimestampNanosecondBuilder,
 };
 use arrow_array::types::GenericBinaryType;
-use arrow_array::RecordBatch;
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{ArrowError, FieldRef, Fields, Schema};
 use arroyo_rpc::df::ArroyoSchema;
 use arroyo_rpc::formats::{AvroFormat, BadData, Format, Framing, FramingMethod, JsonFormat};
 use arroyo_rpc::schema_resolver::{FailingSchemaResolver, FixedSchemaResolver, SchemaResolver};
 use arroyo_types::{to_nanos, SourceError};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+use std::io::{BufRead, BufReader, Read};
 use std::sync::Arc;
-use std::time::{Instant, SystemTime};
+use std::time::{Duration, Instant, SystemTime};
 use tokio::sync::Mutex;
+use tracing::info;
 
 pub struct FramingIterator<'a> {
     framing: Option<Arc<Framing>>,
@@ -66,6 +71,34 @@ impl<'a> Iterator for FramingIterator<'a> {
     }
 }
 
+fn json_decoder(
+    format: &Format,
+    schema: &ArroyoSchema,
+    bad_data: &BadData,
+) -> Option<(arrow::json::reader::Decoder, TimestampNanosecondBuilder)> {
+    matches!(
+        format,
+        Format::Json(..)
+            | Format::Avro(AvroFormat {
+                into_unstructured_json: false,
+                ..
+            })
+    )
+    .then(|| {
+        // exclude the timestamp field
+        let fields = de::decoding_fields(schema.schema_without_timestamp().fields());
+        (
+            arrow_json::reader::ReaderBuilder::new(Arc::new(Schema::new(fields)))
+                .with_limit_to_batch_size(false)
+                .with_strict_mode(false)
+                .with_allow_bad_data(matches!(bad_data, BadData::Drop { .. }))
+                .build_decoder()
+                .unwrap(),
+            TimestampNanosecondBuilder::new(),
+        )
+    })
+}
+
 pub struct ArrowDeserializer {
     format: Arc<Format>,
     framing: Option<Arc<Framing>>,
@@ -73,9 +106,17 @@ pub struct ArrowDeserializer {
     bad_data: BadData,
     json_decoder: Option<(arrow::json::reader::Decoder, TimestampNanosecondBuilder)>,
     buffered_count: usize,
+    buffered_bytes: usize,
+    buffered_errors: usize,
     buffered_since: Instant,
+    pending_batches: VecDeque<RecordBatch>,
     schema_registry: Arc<Mutex<HashMap<u32, apache_avro::schema::Schema>>>,
     schema_resolver: Arc<dyn SchemaResolver + Sync>,
+    /// The schemas that single-object encoded messages can be written with, by fingerprint
+    single_object_schemas: HashMap<[u8; 8], apache_avro::schema::Schema>,
+    avro_lookups: Arc<AvroSchemaLookups>,
+    field_timings: Option<HashMap<String, Duration>>,
+    unexpected_fields: Option<HashMap<u32, BTreeSet<String>>>,
 }
 
 impl ArrowDeserializer {
@@ -106,40 +147,109 @@ impl ArrowDeserializer {
         bad_data: BadData,
         schema_resolver: Arc<dyn SchemaResolver + Sync>,
     ) -> Self {
+        let field_timings = matches!(
+            format,
+            Format::Avro(AvroFormat {
+                field_timing: true,
+                ..
+            })
+        )
+        .then(HashMap::new);
+        let unexpected_fields = matches!(
+            format,
+            Format::Avro(AvroFormat {
+                report_unexpected_fields: true,
+                ..
+            })
+        )
+        .then(HashMap::new);
+        let single_object_schemas = match &format {
+            Format::Avro(AvroFormat {
+                single_object_encoding: true,
+                reader_schema: Some(schema),
+                ..
+            }) => HashMap::from([(de::single_object_fingerprint(&schema.0), schema.0.clone())]),
+            _ => HashMap::new(),
+        };
+
         Self {
-            json_decoder: matches!(
-                format,
-                Format::Json(..)
-                    | Format::Avro(AvroFormat {
-                        into_unstructured_json: false,
-                        ..
-                    })
-            )
-            .then(|| {
-                // exclude the timestamp field
-                (
-                    arrow_json::reader::ReaderBuilder::new(Arc::new(
-                        schema.schema_without_timestamp(),
-                    ))
-                    .with_limit_to_batch_size(false)
-                    .with_strict_mode(false)
-                    .with_allow_bad_data(matches!(bad_data, BadData::Drop { .. }))
-                    .build_decoder()
-                    .unwrap(),
-                    TimestampNanosecondBuilder::new(),
-                )
-            }),
+            json_decoder: json_decoder(&format, &schema, &bad_data),
+            avro_lookups: Arc::new(AvroSchemaLookups::new(&format)),
             format: Arc::new(format),
             framing: framing.map(Arc::new),
             schema,
             schema_registry: Arc::new(Mutex::new(HashMap::new())),
             bad_data,
             schema_resolver,
+            single_object_schemas,
             buffered_count: 0,
+            buffered_bytes: 0,
+            buffered_errors: 0,
             buffered_since: Instant::now(),
+            pending_batches: VecDeque::new(),
+            field_timings,
+            unexpected_fields,
+        }
+    }
+
+    /// Restricts decoding to the columns of the schema at the given indices, so that no arrays
+    /// are built for the others. The timestamp column is always kept and columns stay in schema
+    /// order; batches are produced with the projected schema, which is returned by `schema`.
+    pub fn with_projection(mut self, projection: &[usize]) -> Result<Self, ArrowError> {
+        let mut indices: Vec<_> = projection
+            .iter()
+            .copied()
+            .chain([self.schema.timestamp_index])
+            .collect();
+        indices.sort_unstable();
+        indices.dedup();
+
+        let timestamp_index = indices
+            .iter()
+            .position(|i| *i == self.schema.timestamp_index)
+            .unwrap();
+        self.schema = ArroyoSchema::new_unkeyed(
+            Arc::new(self.schema.schema.project(&indices)?),
+            timestamp_index,
+        );
+        self.json_decoder = json_decoder(&self.format, &self.schema, &self.bad_data);
+
+        Ok(self)
+    }
+
+    /// Switches the deserializer to producing batches with a new schema, discarding anything
+    /// buffered. Writer schemas that have already been fetched are kept.
+    pub fn reset(&mut self, schema: ArroyoSchema) {
+        self.schema = schema;
+        self.clear();
+        if let Some(unexpected) = &mut self.unexpected_fields {
+            // these were computed against the old columns
+            unexpected.clear();
         }
     }
 
+    /// Discards anything buffered and releases the memory held for it. The decoder's buffers
+    /// otherwise keep the capacity they grew to during a burst, which saves reallocating them for
+    /// the next one at the cost of holding on to that memory while the source is idle.
+    pub fn clear(&mut self) {
+        self.json_decoder = json_decoder(&self.format, &self.schema, &self.bad_data);
+        self.buffered_count = 0;
+        self.buffered_bytes = 0;
+        self.buffered_errors = 0;
+        self.buffered_since = Instant::now();
+        self.pending_batches = VecDeque::new();
+    }
+
+    /// Whether nothing is buffered, including batches that have been decoded but not yet returned
+    /// by [`Self::next_batch`]
+    pub fn is_empty(&self) -> bool {
+        self.pending_batches.is_empty() && self.buffered_count + self.buffered_errors == 0
+    }
+
+    pub fn schema(&self) -> &ArroyoSchema {
+        &self.schema
+    }
+
     pub async fn deserialize_slice(
         &mut self,
         buffer: &mut [Box<dyn ArrayBuilder>],
@@ -156,46 +266,159 @@ impl ArrowDeserializer {
     }
 
     pub fn should_flush(&self) -> bool {
-        should_flush(self.buffered_count, self.buffered_since)
+        !self.pending_batches.is_empty()
+            || should_flush(
+                self.buffered_count + self.buffered_errors,
+                self.buffered_since,
+            )
+            || self
+                .max_batch_bytes()
+                .is_some_and(|max| self.buffered_count > 0 && self.buffered_bytes >= max)
+    }
+
+    fn max_batch_bytes(&self) -> Option<usize> {
+        match &*self.format {
+            Format::Avro(AvroFormat {
+                max_batch_bytes, ..
+            }) => max_batch_bytes.map(|max| max as usize),
+            _ => None,
+        }
+    }
+
+    /// Returns the cumulative time spent decoding each top-level field, if field timing has
+    /// been enabled for the format.
+    pub fn field_decode_times(&self) -> Option<&HashMap<String, Duration>> {
+        self.field_timings.as_ref()
+    }
+
+    /// Returns the fields of each writer schema (by schema id) that don't correspond to any
+    /// column and so are ignored, if reporting has been enabled for the format. Each schema is
+    /// checked when the first message written with it is seen.
+    pub fn unexpected_fields(&self) -> Option<&HashMap<u32, BTreeSet<String>>> {
+        self.unexpected_fields.as_ref()
+    }
+
+    /// Fails the batch if more of its records failed to decode than the format allows.
+    fn check_error_threshold(&self, records: usize, errors: usize) -> Result<(), SourceError> {
+        let Format::Avro(format) = &*self.format else {
+            return Ok(());
+        };
+
+        let too_many = format
+            .max_batch_errors
+            .is_some_and(|max| errors as u64 > max);
+        let too_high_rate = format
+            .max_batch_error_percent
+            .is_some_and(|max| records > 0 && errors as u64 * 100 > max as u64 * records as u64);
+
+        if too_many || too_high_rate {
+            return Err(SourceError::other(
+                "Avro error threshold exceeded",
+                format!(
+                    "{} of {} records in the batch failed to decode",
+                    errors, records
+                ),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Returns the next batch of at most `batch_size` rows, or None if nothing is buffered. If the
+    /// buffered rows don't fit in a single batch, the remainder is kept for subsequent calls,
+    /// which return it before anything buffered since.
+    pub fn next_batch(&mut self, batch_size: usize) -> Option<Result<RecordBatch, SourceError>> {
+        if let Some(batch) = self.pending_batches.pop_front() {
+            return Some(Ok(batch));
+        }
+
+        if self.buffered_count + self.buffered_errors == 0 {
+            return None;
+        }
+
+        let batch = match self.flush_buffer()? {
+            Ok(batch) => batch,
+            Err(e) => return Some(Err(e)),
+        };
+
+        let batch_size = batch_size.max(1);
+        if batch.num_rows() <= batch_size {
+            return Some(Ok(batch));
+        }
+
+        let mut offset = 0;
+        while offset < batch.num_rows() {
+            let len = batch_size.min(batch.num_rows() - offset);
+            self.pending_batches.push_back(batch.slice(offset, len));
+            offset += len;
+        }
+
+        self.pending_batches.pop_front().map(Ok)
     }
 
     pub fn flush_buffer(&mut self) -> Option<Result<RecordBatch, SourceError>> {
+        let parallel = matches!(
+            &*self.format,
+            Format::Avro(AvroFormat {
+                parallel_decode: true,
+                ..
+            })
+        );
         let (decoder, timestamp) = self.json_decoder.as_mut()?;
+        let records = self.buffered_count + self.buffered_errors;
+        let mut errors = std::mem::take(&mut self.buffered_errors);
         self.buffered_since = Instant::now();
         self.buffered_count = 0;
-        match self.bad_data {
-            BadData::Fail { .. } => Some(
-                decoder
-                    .flush()
-                    .map_err(|e| {
-                        SourceError::bad_data(format!("JSON does not match schema: {:?}", e))
-                    })
-                    .transpose()?
-                    .map(|batch| {
-                        let mut columns = batch.columns().to_vec();
+        self.buffered_bytes = 0;
+        let batch = match self.bad_data {
+            BadData::Fail { .. } => decoder
+                .flush()
+                .map_err(|e| SourceError::bad_data(format!("JSON does not match schema: {:?}", e)))
+                .map(|batch| {
+                    batch.map(|batch| {
+                        let mut columns = struct_columns(&batch, &self.schema, parallel);
                         columns.insert(self.schema.timestamp_index, Arc::new(timestamp.finish()));
                         RecordBatch::try_new(self.schema.schema.clone(), columns).unwrap()
-                    }),
-            ),
-            BadData::Drop { .. } => Some(
-                decoder
-                    .flush_with_bad_data()
-                    .map_err(|e| {
-                        SourceError::bad_data(format!(
-                            "Something went wrong decoding JSON: {:?}",
-                            e
-                        ))
                     })
-                    .transpose()?
-                    .map(|(batch, mask, _)| {
-                        let mut columns = batch.columns().to_vec();
+                }),
+            BadData::Drop { .. } => decoder
+                .flush_with_bad_data()
+                .map_err(|e| {
+                    SourceError::bad_data(format!("Something went wrong decoding JSON: {:?}", e))
+                })
+                .map(|batch| {
+                    batch.map(|(batch, mask, _)| {
+                        errors += mask.false_count();
+                        let mut columns = struct_columns(&batch, &self.schema, parallel);
                         let timestamp =
                             kernels::filter::filter(&timestamp.finish(), &mask).unwrap();
 
                         columns.insert(self.schema.timestamp_index, Arc::new(timestamp));
                         RecordBatch::try_new(self.schema.schema.clone(), columns).unwrap()
-                    }),
-            ),
+                    })
+                }),
+        };
+
+        // dropping bad data can leave a batch with no rows; when configured, we avoid sending
+        // those downstream (watermarks are emitted independently of the deserializer, so they
+        // are unaffected)
+        let suppress_empty = matches!(
+            &*self.format,
+            Format::Avro(AvroFormat {
+                suppress_empty_batches: true,
+                ..
+            })
+        );
+
+        // this must come before returning the decoded batch, as there is none if every record
+        // failed before reaching the decoder
+        if let Err(e) = self.check_error_threshold(records, errors) {
+            return Some(Err(e));
+        }
+
+        match batch.transpose()? {
+            Ok(batch) if suppress_empty && batch.num_rows() == 0 => None,
+            batch => Some(batch),
         }
     }
 
@@ -247,33 +470,56 @@ impl ArrowDeserializer {
         msg: &'a [u8],
         timestamp: SystemTime,
     ) -> Vec<SourceError> {
-        let Format::Avro(format) = &*self.format else {
+        let format = self.format.clone();
+        let Format::Avro(format) = &*format else {
             unreachable!("not avro");
         };
 
-        let messages = match de::avro_messages(
+        let (schema_id, messages) = match de::avro_messages(
             format,
             &self.schema_registry,
             &self.schema_resolver,
+            &self.single_object_schemas,
             msg,
         )
         .await
         {
             Ok(messages) => messages,
             Err(e) => {
+                self.buffered_errors += 1;
                 return vec![e];
             }
         };
 
-        let into_json = format.into_unstructured_json;
+        self.record_unexpected_fields(schema_id, msg).await;
+
+        self.append_avro_records(builders, format, schema_id, messages, timestamp)
+    }
+
+    /// Decodes Avro records into the buffer (or, for unstructured output, into the builders),
+    /// returning the errors for the records that couldn't be decoded
+    fn append_avro_records(
+        &mut self,
+        builders: &mut [Box<dyn ArrayBuilder>],
+        format: &AvroFormat,
+        schema_id: u32,
+        messages: impl IntoIterator<Item = AvroResult<AvroValue>>,
+        timestamp: SystemTime,
+    ) -> Vec<SourceError> {
+        let lookups = self.avro_lookups.clone();
         let errors = messages
             .into_iter()
             .map(|record| {
-                let value = record.map_err(|e| {
-                    SourceError::bad_data(format!("failed to deserialize from avro: {:?}", e))
-                })?;
-
-                if into_json {
+                let value = prepare_avro_record(
+                    format,
+                    self.schema.schema.fields(),
+                    &lookups.decimal_scales,
+                    &lookups.union_records,
+                    schema_id,
+                    record,
+                )?;
+
+                if format.into_unstructured_json {
                     let (idx, _) = self
                         .schema
                         .schema
@@ -288,29 +534,340 @@ impl ArrowDeserializer {
                     add_timestamp(builders, self.schema.timestamp_index, timestamp);
                     self.buffered_count += 1;
                 } else {
+                    if format.max_batch_bytes.is_some() {
+                        self.buffered_bytes += de::estimated_size(&value);
+                    }
+
                     // for now round-trip through json in order to handle unsupported avro features
                     // as that allows us to rely on raw json deserialization
-                    let json = de::avro_to_json(value).to_string();
-
-                    let Some((decoder, timestamp_builder)) = &mut self.json_decoder else {
-                        panic!("json decoder not initialized");
+                    let json = match &mut self.field_timings {
+                        Some(timings) => de::avro_to_json_timed(value, timings),
+                        None => de::avro_to_json(value),
                     };
 
-                    decoder
-                        .decode(json.as_bytes())
-                        .map_err(|e| SourceError::bad_data(format!("invalid JSON: {:?}", e)))?;
-                    self.buffered_count += 1;
-                    timestamp_builder.append_value(to_nanos(timestamp) as i64);
+                    self.decode_json_value(&json, timestamp)?;
                 }
 
                 Ok(())
             })
             .filter_map(|r: Result<(), SourceError>| r.err())
+            .collect::<Vec<_>>();
+
+        self.buffered_errors += errors.len();
+        errors
+    }
+
+    /// Deserializes Avro records read from a stream, for sources that read files or sockets rather
+    /// than receiving discrete messages. The stream holds either consecutive datums (for
+    /// `raw_datums`, written with the schema resolved for id 0) or an object container file.
+    /// Records are read and decoded one at a time, and a batch is returned each time `batch_size`
+    /// rows have been buffered; any remainder stays buffered until the next flush. Reading stops
+    /// at the first datum that can't be decoded, as the position of the next one is unknown.
+    pub async fn deserialize_avro_reader(
+        &mut self,
+        builders: &mut [Box<dyn ArrayBuilder>],
+        reader: impl Read,
+        batch_size: usize,
+        timestamp: SystemTime,
+    ) -> (Vec<RecordBatch>, Vec<SourceError>) {
+        let format = self.format.clone();
+        let Format::Avro(format) = &*format else {
+            return (
+                vec![],
+                vec![SourceError::other(
+                    "invalid format",
+                    "only Avro can be deserialized from a stream",
+                )],
+            );
+        };
+
+        if format.confluent_schema_registry {
+            return (
+                vec![],
+                vec![SourceError::other(
+                    "invalid format",
+                    "schema registry messages can't be deserialized from a stream",
+                )],
+            );
+        }
+
+        let mut reader = BufReader::new(reader);
+        let mut batches = vec![];
+        let mut errors = vec![];
+
+        if format.raw_datums {
+            let schema = {
+                let mut registry = self.schema_registry.lock().await;
+                match registry.get(&0) {
+                    Some(schema) => schema.clone(),
+                    None => match de::resolve_schema(&self.schema_resolver, 0).await {
+                        Ok(schema) => registry.entry(0).or_insert(schema).clone(),
+                        Err(e) => return (vec![], vec![e]),
+                    },
+                }
+            };
+            let reader_schema = format.reader_schema.as_ref().map(|s| &s.0);
+
+            loop {
+                match reader.fill_buf() {
+                    Ok([]) => break,
+                    Ok(_) => {}
+                    Err(e) => {
+                        errors.push(SourceError::other(
+                            "failed to read Avro stream",
+                            e.to_string(),
+                        ));
+                        break;
+                    }
+                }
+
+                let record = apache_avro::from_avro_datum(&schema, &mut reader, reader_schema);
+                let failed = record.is_err();
+                errors.extend(self.append_avro_records(builders, format, 0, [record], timestamp));
+                self.flush_full_batch(batch_size, &mut batches, &mut errors);
+
+                if failed {
+                    break;
+                }
+            }
+        } else {
+            let records = match &format.reader_schema {
+                Some(schema) => Reader::with_schema(&schema.0, reader),
+                None => Reader::new(reader),
+            };
+
+            match records {
+                Ok(records) => {
+                    for record in records {
+                        errors.extend(self.append_avro_records(
+                            builders,
+                            format,
+                            0,
+                            [record],
+                            timestamp,
+                        ));
+                        self.flush_full_batch(batch_size, &mut batches, &mut errors);
+                    }
+                }
+                Err(e) => errors.push(SourceError::bad_data(format!(
+                    "invalid Avro container file: {:?}",
+                    e
+                ))),
+            }
+        }
+
+        (batches, errors)
+    }
+
+    fn flush_full_batch(
+        &mut self,
+        batch_size: usize,
+        batches: &mut Vec<RecordBatch>,
+        errors: &mut Vec<SourceError>,
+    ) {
+        if self.buffered_count + self.buffered_errors < batch_size.max(1) {
+            return;
+        }
+
+        match self.flush_buffer() {
+            Some(Ok(batch)) => batches.push(batch),
+            Some(Err(e)) => errors.push(e),
+            None => {}
+        }
+    }
+
+    /// Deserializes a set of messages into the buffer, in order. For Avro messages framed with
+    /// schema ids and with `parallel_decode` set, the messages for each schema id are decoded in
+    /// parallel, on up to one thread per core; otherwise (or if any message is unframed or has a schema that can't be
+    /// resolved) this is equivalent to deserializing each message in turn.
+    pub async fn deserialize_slices(
+        &mut self,
+        builders: &mut [Box<dyn ArrayBuilder>],
+        msgs: &[(&[u8], SystemTime)],
+    ) -> Vec<SourceError> {
+        let format = self.format.clone();
+        let parallel = match &*format {
+            Format::Avro(format) => self
+                .parallel_groups(format, msgs)
+                .await
+                .map(|groups| (format, groups)),
+            _ => None,
+        };
+
+        let Some((format, groups)) = parallel else {
+            let mut errors = vec![];
+            for (msg, timestamp) in msgs {
+                errors.extend(self.deserialize_slice(builders, msg, *timestamp).await);
+            }
+            return errors;
+        };
+
+        let lookups = self.avro_lookups.clone();
+        let fields = self.schema.schema.fields();
+        let reader_schema = format.reader_schema.as_ref().map(|s| &s.0);
+        // groups are spread over at most one thread per core, as a buffer can hold many schemas
+        let thread_count = std::thread::available_parallelism()
+            .map(|n| n.get())
+            .unwrap_or(1)
+            .min(groups.len());
+        let mut assigned: Vec<Vec<_>> = (0..thread_count).map(|_| vec![]).collect();
+        for (n, group) in groups.iter().enumerate() {
+            assigned[n % thread_count].push(group);
+        }
+
+        let mut decoded: Vec<_> = std::thread::scope(|s| {
+            let threads: Vec<_> = assigned
+                .into_iter()
+                .map(|groups| {
+                    let lookups = &lookups;
+                    s.spawn(move || {
+                        groups
+                            .into_iter()
+                            .flat_map(|(schema_id, (schema, indices))| {
+                                indices.iter().map(move |i| {
+                                    let mut datum = &msgs[*i].0[5..];
+                                    let record = apache_avro::from_avro_datum(
+                                        schema,
+                                        &mut datum,
+                                        reader_schema,
+                                    );
+                                    let json = prepare_avro_record(
+                                        format,
+                                        fields,
+                                        &lookups.decimal_scales,
+                                        &lookups.union_records,
+                                        *schema_id,
+                                        record,
+                                    )
+                                    .map(|value| {
+                                        let size = de::estimated_size(&value);
+                                        (de::avro_to_json(value), size)
+                                    });
+                                    (*i, json)
+                                })
+                            })
+                            .collect::<Vec<_>>()
+                    })
+                })
+                .collect();
+
+            threads
+                .into_iter()
+                .flat_map(|t| t.join().expect("avro decoding thread panicked"))
+                .collect()
+        });
+
+        // each group was decoded independently, so restore the original order of the messages
+        decoded.sort_by_key(|(i, _)| *i);
+
+        let errors: Vec<_> = decoded
+            .into_iter()
+            .filter_map(|(i, json)| {
+                let (json, size) = match json {
+                    Ok(json) => json,
+                    Err(e) => return Some(e),
+                };
+                if format.max_batch_bytes.is_some() {
+                    self.buffered_bytes += size;
+                }
+                self.decode_json_value(&json, msgs[i].1).err()
+            })
             .collect();
 
+        self.buffered_errors += errors.len();
         errors
     }
 
+    /// Groups the messages by schema id for parallel decoding, along with their (resolved)
+    /// schemas, or returns None if they need to be decoded serially.
+    async fn parallel_groups(
+        &mut self,
+        format: &AvroFormat,
+        msgs: &[(&[u8], SystemTime)],
+    ) -> Option<BTreeMap<u32, (apache_avro::Schema, Vec<usize>)>> {
+        // unstructured output and field timings are accumulated as each record is decoded
+        if !format.parallel_decode
+            || !format.confluent_schema_registry
+            || format.into_unstructured_json
+            || self.field_timings.is_some()
+        {
+            return None;
+        }
+
+        let mut indices: BTreeMap<u32, Vec<usize>> = BTreeMap::new();
+        for (i, (msg, _)) in msgs.iter().enumerate() {
+            if msg.len() < 5 || msg[0] != 0 {
+                return None;
+            }
+            let id = u32::from_be_bytes([msg[1], msg[2], msg[3], msg[4]]);
+            indices.entry(id).or_default().push(i);
+        }
+
+        let mut groups = BTreeMap::new();
+        for (id, indices) in indices {
+            let schema = {
+                let mut registry = self.schema_registry.lock().await;
+                match registry.get(&id) {
+                    Some(schema) => schema.clone(),
+                    None => {
+                        // leave the error (or fallback) to the serial path
+                        let schema = de::resolve_schema(&self.schema_resolver, id).await.ok()?;
+                        info!("Loaded new schema with id {} from Schema Registry", id);
+                        registry.insert(id, schema.clone());
+                        schema
+                    }
+                }
+            };
+
+            self.record_unexpected_fields(id, msgs[indices[0]].0).await;
+            groups.insert(id, (schema, indices));
+        }
+
+        Some(groups)
+    }
+
+    async fn record_unexpected_fields(&mut self, schema_id: u32, msg: &[u8]) {
+        let Some(unexpected) = &mut self.unexpected_fields else {
+            return;
+        };
+
+        if !unexpected.contains_key(&schema_id) {
+            // object container files embed their writer schema rather than registering it
+            let writer_schema = match self.schema_registry.lock().await.get(&schema_id) {
+                Some(schema) => Some(schema.clone()),
+                None => apache_avro::Reader::new(msg)
+                    .ok()
+                    .map(|reader| reader.writer_schema().clone()),
+            };
+
+            if let Some(writer_schema) = writer_schema {
+                unexpected.insert(
+                    schema_id,
+                    de::unexpected_fields(&writer_schema, self.schema.schema.fields()),
+                );
+            }
+        }
+    }
+
+    /// Decodes an already-parsed JSON value, which is written directly to the decoder's tape
+    /// rather than being formatted as text and tokenized again
+    fn decode_json_value(
+        &mut self,
+        json: &serde_json::Value,
+        timestamp: SystemTime,
+    ) -> Result<(), SourceError> {
+        let Some((decoder, timestamp_builder)) = &mut self.json_decoder else {
+            panic!("json decoder not initialized");
+        };
+
+        decoder
+            .serialize(std::slice::from_ref(json))
+            .map_err(|e| SourceError::bad_data(format!("invalid JSON: {:?}", e)))?;
+        self.buffered_count += 1;
+        timestamp_builder.append_value(to_nanos(timestamp) as i64);
+        Ok(())
+    }
+
     fn deserialize_raw_string(&mut self, buffer: &mut [Box<dyn ArrayBuilder>], msg: &[u8]) {
         let (col, _) = self
             .schema
@@ -342,6 +899,138 @@ impl ArrowDeserializer {
     }
 }
 
+/// Lookups by field path into the Avro reader schema, which are used when preparing every
+/// record. Walking the schema and formatting each nested path is costly for deep schemas, so
+/// these are computed once when the deserializer is created.
+#[derive(Default)]
+struct AvroSchemaLookups {
+    /// The scale of each decimal field
+    decimal_scales: HashMap<String, usize>,
+    /// The record names of the branches of each union field
+    union_records: HashMap<String, Vec<Option<String>>>,
+}
+
+impl AvroSchemaLookups {
+    fn new(format: &Format) -> Self {
+        match format {
+            Format::Avro(AvroFormat {
+                reader_schema: Some(schema),
+                ..
+            }) => Self {
+                decimal_scales: de::decimal_scales(&schema.0),
+                union_records: de::union_record_names(&schema.0),
+            },
+            _ => Self::default(),
+        }
+    }
+}
+
+/// Applies the configured checks and normalizations to a decoded Avro record. For unstructured
+/// output, only the structural limits are applied.
+fn prepare_avro_record(
+    format: &AvroFormat,
+    fields: &Fields,
+    decimal_scales: &HashMap<String, usize>,
+    union_records: &HashMap<String, Vec<Option<String>>>,
+    schema_id: u32,
+    record: AvroResult<AvroValue>,
+) -> Result<AvroValue, SourceError> {
+    let mut value = record
+        .map_err(|e| SourceError::bad_data(format!("failed to deserialize from avro: {:?}", e)))?;
+
+    if let Some(schema) = &format.reader_schema {
+        de::apply_field_defaults(&mut value, &schema.0);
+    }
+
+    de::check_required_fields(&format.required_fields, schema_id, &value)?;
+
+    if let Some(max_length) = format.max_array_length {
+        de::limit_array_lengths(&mut value, max_length as usize, format.oversized_arrays)?;
+    }
+
+    if let Some(max_depth) = format.max_nesting_depth {
+        de::limit_nesting_depth(&mut value, max_depth as usize, format.nesting_overflow)?;
+    }
+
+    if format.into_unstructured_json {
+        return Ok(value);
+    }
+
+    // rows are decoded from JSON objects, and anything else would fail the entire batch when
+    // it's flushed, so it's rejected here
+    if !matches!(value, AvroValue::Record(_)) {
+        return Err(SourceError::bad_data(format!(
+            "top-level Avro value must be a record, got: {:?}",
+            value
+        )));
+    }
+
+    if format.case_insensitive_fields {
+        de::normalize_field_names(&mut value, fields, format.field_name_collisions)?;
+    }
+
+    de::for_each_field(&mut value, fields, "", &mut |path, field, v| {
+        de::apply_null_sentinel(path, v, &format.null_sentinels);
+        de::apply_field_transforms(path, v, &format.field_transforms)?;
+        de::apply_epoch_offset(path, field, v, &format.epoch_offsets)?;
+        de::apply_map_null_values(field, v, format.map_null_values);
+        de::normalize_numeric_string(field, v, format.decimal_separator);
+        de::stringify_scalar(field, v, format.stringify_scalars);
+        de::tag_union_branch(path, field, v)?;
+        de::tag_enum_symbol(path, field, v, format.unknown_enum_symbols)?;
+        de::tag_union_record(path, field, v, union_records);
+        // this also applies when the columns' types come from elsewhere (like a table's
+        // declared schema), which may use a different unit for timestamps
+        de::apply_logical_type_mapping(path, field, v, decimal_scales)?;
+        de::check_float_precision(path, field, v, format.float_precision_loss)?;
+        de::check_decimal_finite(path, field, v, format.non_finite_decimals)?;
+        de::check_time_range(path, field, v, format.out_of_range_times)?;
+        de::check_integer_range(path, field, v, format.integer_overflow)
+    })?;
+
+    Ok(value)
+}
+
+/// Batches with at least this many columns have their columns converted in parallel when
+/// `parallel_decode` is set
+const PARALLEL_COLUMN_THRESHOLD: usize = 32;
+
+/// The columns of a decoded batch, with struct columns rebuilt to the batch's length so that
+/// records without fields produce valid arrays (see [`de::build_struct_array`]). With
+/// `parallel`, wide batches split their columns into contiguous chunks that are each converted
+/// on their own thread; each column is still converted (including any nested columns) on a single
+/// thread, and the columns keep their order.
+fn struct_columns(batch: &RecordBatch, schema: &ArroyoSchema, parallel: bool) -> Vec<ArrayRef> {
+    let fields = schema.schema_without_timestamp().fields().clone();
+    let columns = batch.columns();
+    let build =
+        |(c, f): (&ArrayRef, &FieldRef)| de::build_struct_array(c, f.data_type(), batch.num_rows());
+
+    if !parallel || columns.len() < PARALLEL_COLUMN_THRESHOLD {
+        return columns.iter().zip(fields.iter()).map(build).collect();
+    }
+
+    let threads = std::thread::available_parallelism()
+        .map(|n| n.get())
+        .unwrap_or(1);
+    let chunk_size = columns.len().div_ceil(threads);
+
+    std::thread::scope(|s| {
+        let threads: Vec<_> = columns
+            .chunks(chunk_size)
+            .zip(fields.chunks(chunk_size))
+            .map(|(columns, fields)| {
+                s.spawn(move || columns.iter().zip(fields).map(build).collect::<Vec<_>>())
+            })
+            .collect();
+
+        threads
+            .into_iter()
+            .flat_map(|t| t.join().expect("column conversion thread panicked"))
+            .collect()
+    })
+}
+
 pub(crate) fn add_timestamp(
     builder: &mut [Box<dyn ArrayBuilder>],
     idx: usize,
@@ -356,19 +1045,22 @@ pub(crate) fn add_timestamp(
 
 #[cfg(test)]
 mod tests {
-    use crate::de::{ArrowDeserializer, FramingIterator};
+    use crate::de::{prepare_avro_record, ArrowDeserializer, FramingIterator};
+    use apache_avro::types::Value;
     use arrow_array::builder::{make_builder, ArrayBuilder};
     use arrow_array::cast::AsArray;
     use arrow_array::types::{GenericBinaryType, Int64Type, TimestampNanosecondType};
     use arrow_array::RecordBatch;
-    use arrow_schema::{Schema, TimeUnit};
+    use arrow_schema::{DataType, Field, Fields, Schema, TimeUnit};
     use arroyo_rpc::df::ArroyoSchema;
     use arroyo_rpc::formats::{
-        BadData, Format, Framing, FramingMethod, JsonFormat, NewlineDelimitedFraming,
+        AvroFormat, BadData, Format, Framing, FramingMethod, JsonFormat, NewlineDelimitedFraming,
         RawBytesFormat,
     };
-    use arroyo_types::{to_nanos, SourceError};
+    use arroyo_rpc::schema_resolver::FailingSchemaResolver;
+    use arroyo_types::{from_nanos, to_nanos, SourceError};
     use serde_json::json;
+    use std::collections::HashMap;
     use std::sync::Arc;
     use std::time::SystemTime;
 
@@ -545,6 +1237,47 @@ mod tests {
         assert!(matches!(err, SourceError::BadData { .. }));
     }
 
+    #[tokio::test]
+    async fn test_flush_varying_batch_sizes() {
+        let (mut arrays, mut deserializer) = setup_deserializer(BadData::Fail {});
+
+        let mut x: i64 = 0;
+        for size in [5, 2, 8, 3] {
+            for _ in 0..size {
+                assert_eq!(
+                    deserializer
+                        .deserialize_slice(
+                            &mut arrays[..],
+                            json!({ "x": x }).to_string().as_bytes(),
+                            from_nanos(x as u128)
+                        )
+                        .await,
+                    vec![]
+                );
+                x += 1;
+            }
+
+            let batch = deserializer.flush_buffer().unwrap().unwrap();
+            assert_eq!(batch.num_rows(), size);
+
+            let start = x - size as i64;
+            assert_eq!(
+                batch.columns()[0]
+                    .as_primitive::<Int64Type>()
+                    .values()
+                    .to_vec(),
+                (start..x).collect::<Vec<_>>()
+            );
+            assert_eq!(
+                batch.columns()[1]
+                    .as_primitive::<TimestampNanosecondType>()
+                    .values()
+                    .to_vec(),
+                (start..x).collect::<Vec<_>>()
+            );
+        }
+    }
+
     #[tokio::test]
     async fn test_raw_bytes() {
         let schema = Arc::new(Schema::new(vec![
@@ -594,4 +1327,577 @@ mod tests {
             to_nanos(time) as i64
         );
     }
+
+    const READER_SCHEMA: &str = r#"{"type": "record", "name": "Event", "fields": [
+        {"name": "id", "type": "long"},
+        {"name": "name", "type": "string"}
+    ]}"#;
+
+    const EXTENDED_SCHEMA: &str = r#"{"type": "record", "name": "Event", "fields": [
+        {"name": "id", "type": "long"},
+        {"name": "name", "type": "string"},
+        {"name": "score", "type": "double"}
+    ]}"#;
+
+    /// Messages alternating between writer schemas (with ids 1 to `schemas`), in order of `id`
+    fn multi_schema_messages(count: i64, schemas: u32) -> Vec<Vec<u8>> {
+        let extended = apache_avro::Schema::parse_str(EXTENDED_SCHEMA).unwrap();
+        (0..count)
+            .map(|id| {
+                let schema_id = (id as u32 % schemas) + 1;
+                let mut record = vec![
+                    ("id".to_string(), Value::Long(id)),
+                    ("name".to_string(), Value::String(format!("event-{}", id))),
+                    ("score".to_string(), Value::Double(id as f64 / 2.0)),
+                ];
+                if schema_id % 2 == 1 {
+                    record.pop();
+                }
+                let schema = if schema_id % 2 == 1 {
+                    apache_avro::Schema::parse_str(READER_SCHEMA).unwrap()
+                } else {
+                    extended.clone()
+                };
+
+                let mut message = vec![0];
+                message.extend(schema_id.to_be_bytes());
+                message.extend(apache_avro::to_avro_datum(&schema, Value::Record(record)).unwrap());
+                message
+            })
+            .collect()
+    }
+
+    async fn decode_messages(
+        messages: &[Vec<u8>],
+        schemas: u32,
+        parallel_decode: bool,
+        time: SystemTime,
+    ) -> RecordBatch {
+        let reader = apache_avro::Schema::parse_str(READER_SCHEMA).unwrap();
+        let mut format = AvroFormat::new(true, false, false);
+        format.parallel_decode = parallel_decode;
+        format.add_reader_schema(reader);
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+        let mut builders: Vec<_> = schema
+            .fields
+            .iter()
+            .map(|f| make_builder(f.data_type(), 16))
+            .collect();
+
+        let mut deserializer = ArrowDeserializer::with_schema_resolver(
+            Format::Avro(format),
+            None,
+            ArroyoSchema::from_schema_unkeyed(schema).unwrap(),
+            BadData::Fail {},
+            Arc::new(FailingSchemaResolver::new()),
+        );
+        {
+            let mut registry = deserializer.schema_registry.lock().await;
+            for id in 1..=schemas {
+                let schema = if id % 2 == 1 {
+                    READER_SCHEMA
+                } else {
+                    EXTENDED_SCHEMA
+                };
+                registry.insert(id, apache_avro::Schema::parse_str(schema).unwrap());
+            }
+        }
+
+        let messages: Vec<_> = messages.iter().map(|m| (m.as_slice(), time)).collect();
+        let errors = deserializer
+            .deserialize_slices(&mut builders, &messages)
+            .await;
+        assert!(errors.is_empty());
+
+        deserializer.flush_buffer().unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_parallel_decode_matches_serial() {
+        let messages = multi_schema_messages(1000, 4);
+        let time = SystemTime::now();
+
+        let serial = decode_messages(&messages, 4, false, time).await;
+        let parallel = decode_messages(&messages, 4, true, time).await;
+
+        assert_eq!(serial, parallel);
+        assert_eq!(
+            parallel
+                .column(0)
+                .as_primitive::<Int64Type>()
+                .values()
+                .to_vec(),
+            (0..1000).collect::<Vec<_>>()
+        );
+        assert_eq!(parallel.column(1).as_string::<i32>().value(7), "event-7");
+    }
+
+    async fn decode_wide(columns: usize, rows: usize, parallel_decode: bool) -> RecordBatch {
+        let avro_fields: Vec<_> = (0..columns)
+            .map(|i| {
+                if i % 2 == 0 {
+                    json!({"name": format!("c{i}"), "type": "long"})
+                } else {
+                    json!({
+                        "name": format!("c{i}"),
+                        "type": {"type": "fixed", "name": format!("f{i}"), "size": 4}
+                    })
+                }
+            })
+            .collect();
+        let avro_schema = apache_avro::Schema::parse_str(
+            &json!({"type": "record", "name": "wide", "fields": avro_fields}).to_string(),
+        )
+        .unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.parallel_decode = parallel_decode;
+        format.add_reader_schema(avro_schema.clone());
+
+        let mut fields: Vec<_> = (0..columns)
+            .map(|i| {
+                let data_type = if i % 2 == 0 {
+                    DataType::Int64
+                } else {
+                    DataType::FixedSizeBinary(4)
+                };
+                Field::new(format!("c{i}"), data_type, false)
+            })
+            .collect();
+        fields.push(Field::new(
+            "_timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        ));
+        let schema = ArroyoSchema::from_schema_unkeyed(Arc::new(Schema::new(fields))).unwrap();
+
+        let mut deserializer =
+            ArrowDeserializer::new(Format::Avro(format), schema, None, BadData::Fail {});
+        let mut builders = vec![];
+
+        for row in 0..rows {
+            let datum = apache_avro::to_avro_datum(
+                &avro_schema,
+                Value::Record(
+                    (0..columns)
+                        .map(|i| {
+                            let value = if i % 2 == 0 {
+                                Value::Long((row * columns + i) as i64)
+                            } else {
+                                Value::Fixed(4, ((row * columns + i) as u32).to_be_bytes().to_vec())
+                            };
+                            (format!("c{i}"), value)
+                        })
+                        .collect(),
+                ),
+            )
+            .unwrap();
+
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &datum, from_nanos(row as u128))
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+
+        deserializer.flush_buffer().unwrap().unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_parallel_columns_match_serial() {
+        let serial = decode_wide(50, 100, false).await;
+        let parallel = decode_wide(50, 100, true).await;
+
+        assert_eq!(serial, parallel);
+        assert_eq!(parallel.num_columns(), 51);
+        assert_eq!(parallel.column(2).as_primitive::<Int64Type>().value(3), 152);
+        assert_eq!(
+            parallel.column(49).as_fixed_size_binary().value(3),
+            199u32.to_be_bytes()
+        );
+    }
+
+    #[tokio::test]
+    async fn test_avro_schema_lookups_computed_once() {
+        let schema = r#"{"type": "record", "name": "r", "fields": [
+            {"name": "price", "type": {"type": "bytes", "logicalType": "decimal", "precision": 10, "scale": 2}},
+            {"name": "item", "type": ["null", {"type": "record", "name": "item", "fields": [
+                {"name": "id", "type": "long"}
+            ]}]}
+        ]}"#;
+        let avro_schema = apache_avro::Schema::parse_str(schema).unwrap();
+
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(avro_schema.clone());
+
+        let arrow_schema = Arc::new(Schema::new(vec![
+            Field::new("price", DataType::Decimal128(10, 2), false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+        let arroyo_schema = ArroyoSchema::from_schema_unkeyed(arrow_schema).unwrap();
+
+        let mut deserializer = ArrowDeserializer::new(
+            Format::Avro(format),
+            arroyo_schema.clone(),
+            None,
+            BadData::Fail {},
+        );
+        let lookups = deserializer.avro_lookups.clone();
+        assert_eq!(
+            lookups.decimal_scales,
+            HashMap::from([("price".to_string(), 2)])
+        );
+        assert_eq!(
+            lookups.union_records,
+            HashMap::from([("item".to_string(), vec![None, Some("item".to_string())])])
+        );
+
+        let datum = apache_avro::to_avro_datum(
+            &avro_schema,
+            Value::Record(vec![
+                (
+                    "price".to_string(),
+                    Value::Decimal(apache_avro::Decimal::from(1234i32.to_be_bytes())),
+                ),
+                ("item".to_string(), Value::Union(0, Box::new(Value::Null))),
+            ]),
+        )
+        .unwrap();
+
+        for _ in 0..2 {
+            let errors = deserializer
+                .deserialize_slice(&mut [], &datum, SystemTime::now())
+                .await;
+            assert_eq!(errors, vec![]);
+        }
+        deserializer.reset(arroyo_schema);
+
+        // the same lookups are used for every message, and survive a reset
+        assert!(Arc::ptr_eq(&lookups, &deserializer.avro_lookups));
+    }
+
+    #[tokio::test]
+    async fn test_non_record_value() {
+        let format = AvroFormat::new(true, false, false);
+        let fields = Fields::from(vec![Field::new("id", DataType::Int64, false)]);
+
+        let err = prepare_avro_record(
+            &format,
+            &fields,
+            &HashMap::new(),
+            &HashMap::new(),
+            1,
+            Ok(Value::Int(5)),
+        )
+        .unwrap_err();
+        assert!(matches!(err, SourceError::BadData { .. }));
+        assert!(err.details().contains("must be a record"));
+
+        // unstructured output can hold any value
+        let mut unstructured = format.clone();
+        unstructured.into_unstructured_json = true;
+        assert_eq!(
+            prepare_avro_record(
+                &unstructured,
+                &fields,
+                &HashMap::new(),
+                &HashMap::new(),
+                1,
+                Ok(Value::Int(5))
+            )
+            .unwrap(),
+            Value::Int(5)
+        );
+
+        // a datum that isn't a record is reported without failing the rest of the batch
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+        let mut builders: Vec<_> = schema
+            .fields
+            .iter()
+            .map(|f| make_builder(f.data_type(), 16))
+            .collect();
+        let mut deserializer = ArrowDeserializer::with_schema_resolver(
+            Format::Avro(format),
+            None,
+            ArroyoSchema::from_schema_unkeyed(schema).unwrap(),
+            BadData::Drop {},
+            Arc::new(FailingSchemaResolver::new()),
+        );
+        {
+            let mut registry = deserializer.schema_registry.lock().await;
+            registry.insert(1, apache_avro::Schema::parse_str(r#""int""#).unwrap());
+            registry.insert(
+                2,
+                apache_avro::Schema::parse_str(
+                    r#"{"type": "record", "name": "R", "fields": [{"name": "id", "type": "long"}]}"#,
+                )
+                .unwrap(),
+            );
+        }
+
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &[0, 0, 0, 0, 1, 10], SystemTime::now())
+            .await;
+        assert_eq!(errors.len(), 1);
+        let errors = deserializer
+            .deserialize_slice(&mut builders, &[0, 0, 0, 0, 2, 14], SystemTime::now())
+            .await;
+        assert!(errors.is_empty());
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<Int64Type>()
+                .values()
+                .to_vec(),
+            vec![7]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_flush_drains_buffer() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+        let mut builders: Vec<_> = schema
+            .fields
+            .iter()
+            .map(|f| make_builder(f.data_type(), 16))
+            .collect();
+        let mut format = AvroFormat::new(false, true, false);
+        format.add_reader_schema(
+            apache_avro::Schema::parse_str(
+                r#"{"type": "record", "name": "R", "fields": [{"name": "id", "type": "long"}]}"#,
+            )
+            .unwrap(),
+        );
+        let mut deserializer = ArrowDeserializer::new(
+            Format::Avro(format),
+            ArroyoSchema::from_schema_unkeyed(schema).unwrap(),
+            None,
+            BadData::Fail {},
+        );
+
+        // ids 1, 2 and 3
+        for message in [[2u8], [4], [6]] {
+            let errors = deserializer
+                .deserialize_slice(&mut builders, &message, SystemTime::now())
+                .await;
+            assert!(errors.is_empty());
+        }
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(
+            batch
+                .column(0)
+                .as_primitive::<Int64Type>()
+                .values()
+                .to_vec(),
+            vec![1, 2, 3]
+        );
+
+        // nothing is left once the buffer has been drained
+        assert!(deserializer.flush_buffer().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_next_batch_size() {
+        let (mut arrays, mut deserializer) = setup_deserializer(BadData::Fail {});
+
+        let time = SystemTime::now();
+        for i in 0..2500 {
+            let result = deserializer
+                .deserialize_slice(&mut arrays, json!({ "x": i }).to_string().as_bytes(), time)
+                .await;
+            assert!(result.is_empty());
+        }
+
+        let mut next = 0;
+        for expected in [1000, 1000, 500] {
+            let batch = deserializer.next_batch(1000).unwrap().unwrap();
+            assert_eq!(batch.num_rows(), expected);
+            assert_eq!(batch.column(0).as_primitive::<Int64Type>().value(0), next);
+            next += expected as i64;
+        }
+
+        assert!(deserializer.next_batch(1000).is_none());
+    }
+
+    #[test]
+    fn test_next_batch_empty() {
+        let (_, mut deserializer) = setup_deserializer(BadData::Fail {});
+        assert!(deserializer.next_batch(1000).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_projection() {
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("x", DataType::Int64, true),
+            Field::new("y", DataType::Utf8, true),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+
+        let mut arrays: Vec<_> = schema
+            .fields
+            .iter()
+            .map(|f| make_builder(f.data_type(), 16))
+            .collect();
+
+        let mut deserializer = ArrowDeserializer::new(
+            Format::Json(JsonFormat {
+                confluent_schema_registry: false,
+                schema_id: None,
+                include_schema: false,
+                debezium: false,
+                unstructured: false,
+                timestamp_format: Default::default(),
+            }),
+            ArroyoSchema::from_schema_unkeyed(schema).unwrap(),
+            None,
+            BadData::Fail {},
+        )
+        .with_projection(&[0])
+        .unwrap();
+
+        let result = deserializer
+            .deserialize_slice(
+                &mut arrays,
+                json!({ "x": 5, "y": "unused" }).to_string().as_bytes(),
+                SystemTime::now(),
+            )
+            .await;
+        assert!(result.is_empty());
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.schema(), deserializer.schema().schema);
+        assert_eq!(batch.num_columns(), 2);
+        assert_eq!(batch.schema().field(0).name(), "x");
+        assert_eq!(batch.schema().field(1).name(), "_timestamp");
+        assert_eq!(deserializer.schema().timestamp_index, 1);
+        assert_eq!(batch.column(0).as_primitive::<Int64Type>().value(0), 5);
+    }
+
+    #[tokio::test]
+    async fn test_reset_schema() {
+        let (mut arrays, mut deserializer) = setup_deserializer(BadData::Fail {});
+
+        let time = SystemTime::now();
+        let result = deserializer
+            .deserialize_slice(&mut arrays, json!({ "x": 1 }).to_string().as_bytes(), time)
+            .await;
+        assert!(result.is_empty());
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("y", DataType::Utf8, true),
+            Field::new(
+                "_timestamp",
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+        deserializer.reset(ArroyoSchema::from_schema_unkeyed(schema.clone()).unwrap());
+
+        // the row buffered before the reset is discarded
+        assert!(deserializer.flush_buffer().is_none());
+
+        let result = deserializer
+            .deserialize_slice(
+                &mut arrays,
+                json!({ "x": 2, "y": "new" }).to_string().as_bytes(),
+                time,
+            )
+            .await;
+        assert!(result.is_empty());
+
+        let batch = deserializer.flush_buffer().unwrap().unwrap();
+        assert_eq!(batch.schema(), schema);
+        assert_eq!(batch.num_rows(), 1);
+        assert_eq!(batch.column(0).as_string::<i32>().value(0), "new");
+    }
+
+    #[tokio::test]
+    async fn test_clear_releases_buffers() {
+        let (mut arrays, mut deserializer) = setup_deserializer(BadData::Fail {});
+
+        let time = SystemTime::now();
+        for i in 0..1_000_000 {
+            let result = deserializer
+                .deserialize_slice(&mut arrays, json!({ "x": i }).to_string().as_bytes(), time)
+                .await;
+            assert!(result.is_empty());
+        }
+
+        // splitting into smaller batches leaves the rest pending
+        assert!(deserializer.next_batch(1_000).is_some());
+        assert!(deserializer.pending_batches.capacity() >= 999);
+
+        deserializer.clear();
+        assert_eq!(deserializer.pending_batches.capacity(), 0);
+        assert!(!deserializer.should_flush());
+        assert!(deserializer.next_batch(1).is_none());
+    }
+
+    fn next_xs(deserializer: &mut ArrowDeserializer, batch_size: usize) -> Option<Vec<i64>> {
+        deserializer.next_batch(batch_size).map(|batch| {
+            batch
+                .unwrap()
+                .column(0)
+                .as_primitive::<Int64Type>()
+                .values()
+                .to_vec()
+        })
+    }
+
+    #[tokio::test]
+    async fn test_next_batch_consumes_rows() {
+        let (mut arrays, mut deserializer) = setup_deserializer(BadData::Fail {});
+
+        let time = SystemTime::now();
+        for i in 0..3 {
+            let result = deserializer
+                .deserialize_slice(&mut arrays, json!({ "x": i }).to_string().as_bytes(), time)
+                .await;
+            assert!(result.is_empty());
+        }
+
+        assert_eq!(next_xs(&mut deserializer, 2), Some(vec![0, 1]));
+        assert_eq!(next_xs(&mut deserializer, 2), Some(vec![2]));
+
+        let result = deserializer
+            .deserialize_slice(&mut arrays, json!({ "x": 3 }).to_string().as_bytes(), time)
+            .await;
+        assert!(result.is_empty());
+
+        assert_eq!(next_xs(&mut deserializer, 2), Some(vec![3]));
+        assert_eq!(next_xs(&mut deserializer, 2), None);
+    }
 }
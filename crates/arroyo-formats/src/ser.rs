@@ -425,4 +425,48 @@ mod tests {
         assert_eq!(iter.next().unwrap(), br#"{"value":null}"#);
         assert_eq!(iter.next().unwrap(), br#"{"value":1712274910045}"#);
     }
+
+    #[test]
+    fn test_avro_confluent() {
+        let mut format = arroyo_rpc::formats::AvroFormat::new(true, false, false);
+        format.schema_id = Some(0x01020304);
+        let mut serializer = ArrowSerializer::new(Format::Avro(format));
+
+        let schema = Arc::new(Schema::new(vec![
+            arrow_schema::Field::new("value", arrow_schema::DataType::Utf8, false),
+            arrow_schema::Field::new(
+                "_timestamp",
+                arrow_schema::DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+
+        let batch = arrow_array::RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(arrow_array::StringArray::from(vec!["a", "bc"])),
+                Arc::new(arrow_array::TimestampNanosecondArray::from(vec![0, 1])),
+            ],
+        )
+        .unwrap();
+
+        let avro_schema = ArrowSerializer::avro_schema(&schema);
+        let messages: Vec<_> = serializer.serialize(&batch).collect();
+        assert_eq!(messages.len(), 2);
+
+        for (message, expected) in messages.iter().zip(["a", "bc"]) {
+            // magic byte followed by the big-endian schema id
+            assert_eq!(message[..5], [0, 1, 2, 3, 4]);
+
+            let value =
+                apache_avro::from_avro_datum(&avro_schema, &mut &message[5..], None).unwrap();
+            assert_eq!(
+                value,
+                apache_avro::types::Value::Record(vec![(
+                    "value".to_string(),
+                    apache_avro::types::Value::String(expected.to_string())
+                )])
+            );
+        }
+    }
 }
@@ -14,7 +14,8 @@ use arroyo_rpc::{get_hasher, CompactionResult, ControlMessage, ControlResp};
 use arroyo_state::tables::table_manager::TableManager;
 use arroyo_state::{BackingStore, StateBackend};
 use arroyo_types::{
-    from_micros, ArrowMessage, CheckpointBarrier, SourceError, TaskInfo, UserError, Watermark,
+    from_micros, ArrowMessage, CheckpointBarrier, SignalMessage, SourceError, TaskInfo, UserError,
+    Watermark,
 };
 use datafusion::common::hash_utils;
 use rand::Rng;
@@ -347,6 +348,20 @@ fn repartition<'a>(
 
 impl ArrowCollector {
     pub async fn collect(&mut self, record: RecordBatch) {
+        let record = self.prepare(record);
+        for i in 0..self.out_qs.len() {
+            self.send_to_output(i, &record).await;
+        }
+    }
+
+    /// Sends the batch only to the output edge with logical index `output`, for operators that
+    /// route records between their outputs
+    pub async fn collect_output(&mut self, output: usize, record: RecordBatch) {
+        let record = self.prepare(record);
+        self.send_to_output(output, &record).await;
+    }
+
+    fn prepare(&self, record: RecordBatch) -> RecordBatch {
         TaskCounters::MessagesSent
             .for_task(&self.task_info, |c| c.inc_by(record.num_rows() as u64));
         TaskCounters::BatchesSent.for_task(&self.task_info, |c| c.inc());
@@ -367,35 +382,37 @@ impl ArrowCollector {
             record
         };
 
-        let record = RecordBatch::try_new(out_schema.schema.clone(), record.columns().to_vec())
+        RecordBatch::try_new(out_schema.schema.clone(), record.columns().to_vec())
             .unwrap_or_else(|e| {
                 panic!(
                     "Data does not match expected schema for {}: {:?}. expected schema:\n{:#?}\n, actual schema:\n{:#?}",
                     self.task_info.operator_id, e, out_schema.schema, record.schema()
                 );
-            });
+            })
+    }
 
-        for (i, out_q) in self.out_qs.iter_mut().enumerate() {
-            let partitions = repartition(&record, &out_schema.key_indices, out_q.len());
+    async fn send_to_output(&mut self, i: usize, record: &RecordBatch) {
+        let key_indices = &self.out_schema.as_ref().unwrap().key_indices;
+        let out_q = &self.out_qs[i];
+        let partitions = repartition(record, key_indices, out_q.len());
 
-            for (partition, batch) in partitions {
-                out_q[partition]
-                    .send(ArrowMessage::Data(batch))
-                    .await
-                    .unwrap();
+        for (partition, batch) in partitions {
+            out_q[partition]
+                .send(ArrowMessage::Data(batch))
+                .await
+                .unwrap();
 
-                self.tx_queue_rem_gauges[i][partition]
-                    .iter()
-                    .for_each(|g| g.set(out_q[partition].capacity() as i64));
+            self.tx_queue_rem_gauges[i][partition]
+                .iter()
+                .for_each(|g| g.set(out_q[partition].capacity() as i64));
 
-                self.tx_queue_size_gauges[i][partition]
-                    .iter()
-                    .for_each(|g| g.set(out_q[partition].size() as i64));
+            self.tx_queue_size_gauges[i][partition]
+                .iter()
+                .for_each(|g| g.set(out_q[partition].size() as i64));
 
-                self.tx_queue_bytes_gauges[i][partition]
-                    .iter()
-                    .for_each(|g| g.set(out_q[partition].queued_bytes() as i64));
-            }
+            self.tx_queue_bytes_gauges[i][partition]
+                .iter()
+                .for_each(|g| g.set(out_q[partition].queued_bytes() as i64));
         }
     }
 
@@ -543,15 +560,18 @@ impl ArrowContext {
             ));
         }
 
-        if let Some(deserializer) = self.deserializer.as_mut() {
-            if let Some(buffer) = deserializer.flush_buffer() {
-                match buffer {
-                    Ok(batch) => {
-                        self.collector.collect(batch).await;
-                    }
-                    Err(e) => {
-                        self.collect_source_errors(vec![e]).await?;
-                    }
+        let batch_size = config().pipeline.source_batch_size;
+        while let Some(buffer) = self
+            .deserializer
+            .as_mut()
+            .and_then(|d| d.next_batch(batch_size))
+        {
+            match buffer {
+                Ok(batch) => {
+                    self.collector.collect(batch).await;
+                }
+                Err(e) => {
+                    self.collect_source_errors(vec![e]).await?;
                 }
             }
         }
@@ -567,6 +587,10 @@ impl ArrowContext {
         self.collector.collect(record).await;
     }
 
+    pub async fn collect_output(&mut self, output: usize, record: RecordBatch) {
+        self.collector.collect_output(output, record).await;
+    }
+
     pub fn should_flush(&self) -> bool {
         self.buffer
             .as_ref()
@@ -580,8 +604,24 @@ impl ArrowContext {
     }
 
     pub async fn broadcast(&mut self, message: ArrowMessage) {
-        if let Err(e) = self.flush_buffer().await {
-            self.buffered_error.replace(e);
+        let flushed = match self.flush_buffer().await {
+            Ok(()) => true,
+            Err(e) => {
+                self.buffered_error.replace(e);
+                false
+            }
+        };
+        if flushed
+            && matches!(
+                message,
+                ArrowMessage::Signal(SignalMessage::Watermark(Watermark::Idle))
+            )
+        {
+            // the source has gone idle, so there's no need to hold on to buffer capacity; this
+            // only happens once everything has been flushed, so no pending batches are discarded
+            if let Some(deserializer) = self.deserializer.as_mut().filter(|d| d.is_empty()) {
+                deserializer.clear();
+            }
         }
         self.collector.broadcast(message).await;
     }
@@ -684,6 +724,27 @@ impl ArrowContext {
         Ok(())
     }
 
+    /// Deserializes a set of messages with their timestamps, which for some formats allows them
+    /// to be decoded in parallel; rows are added in the order of the messages.
+    pub async fn deserialize_slices(
+        &mut self,
+        msgs: &[(&[u8], SystemTime)],
+    ) -> Result<(), UserError> {
+        let deserializer = self
+            .deserializer
+            .as_mut()
+            .expect("deserializer not initialized!");
+        let errors = deserializer
+            .deserialize_slices(
+                &mut self.buffer.as_mut().expect("no out schema").buffer,
+                msgs,
+            )
+            .await;
+        self.collect_source_errors(errors).await?;
+
+        Ok(())
+    }
+
     /// Handling errors and rate limiting error reporting.
     /// Considers the `bad_data` option to determine whether to drop or fail on bad data.
     async fn collect_source_errors(&mut self, errors: Vec<SourceError>) -> Result<(), UserError> {
@@ -862,6 +923,88 @@ mod tests {
         }
     }
 
+    #[tokio::test]
+    async fn test_collect_output() {
+        let schema = Arc::new(Schema::new(vec![Field::new("x", DataType::Int64, false)]));
+        let record =
+            RecordBatch::try_new(schema.clone(), vec![Arc::new(Int64Array::from(vec![1, 2]))])
+                .unwrap();
+
+        let (tx1, mut rx1) = batch_bounded(8);
+        let (tx2, mut rx2) = batch_bounded(8);
+
+        let task_info = Arc::new(TaskInfo {
+            job_id: "test-job".to_string(),
+            operator_name: "test-operator".to_string(),
+            operator_id: "test-operator-2".to_string(),
+            task_index: 0,
+            parallelism: 1,
+            key_range: 0..=1,
+        });
+
+        let out_qs = vec![vec![tx1], vec![tx2]];
+
+        let mut collector = ArrowCollector {
+            tx_queue_rem_gauges: register_queue_gauge(
+                "arroyo_worker_tx_queue_rem",
+                "Remaining space in a tx queue",
+                &task_info,
+                &out_qs,
+                0,
+            ),
+            tx_queue_size_gauges: register_queue_gauge(
+                "arroyo_worker_tx_queue_size",
+                "Size of a tx queue",
+                &task_info,
+                &out_qs,
+                0,
+            ),
+            tx_queue_bytes_gauges: register_queue_gauge(
+                "arroyo_worker_tx_bytes",
+                "Number of bytes queued in a tx queue",
+                &task_info,
+                &out_qs,
+                0,
+            ),
+            task_info,
+            out_schema: Some(ArroyoSchema::new_unkeyed(schema, 0)),
+            projection: None,
+            out_qs,
+        };
+
+        collector.collect_output(1, record.clone()).await;
+        collector
+            .broadcast(ArrowMessage::Signal(SignalMessage::Watermark(
+                Watermark::Idle,
+            )))
+            .await;
+        drop(collector);
+
+        // the data only goes to the second output, but both receive the watermark
+        let mut q1 = vec![];
+        while let Some(m) = rx1.recv().await {
+            q1.push(m);
+        }
+        let mut q2 = vec![];
+        while let Some(m) = rx2.recv().await {
+            q2.push(m);
+        }
+
+        assert_eq!(
+            q1,
+            vec![ArrowMessage::Signal(SignalMessage::Watermark(
+                Watermark::Idle
+            ))]
+        );
+        assert_eq!(
+            q2,
+            vec![
+                ArrowMessage::Data(record),
+                ArrowMessage::Signal(SignalMessage::Watermark(Watermark::Idle))
+            ]
+        );
+    }
+
     #[tokio::test]
     async fn test_batch_queues() {
         let (tx, mut rx) = batch_bounded(8);
@@ -90,6 +90,13 @@ impl OperatorNode {
                 );
                 let result = s.run(ctx).await;
 
+                // rows still buffered when the source finishes would otherwise be lost
+                if !matches!(result, SourceFinishType::Immediate) {
+                    if let Err(e) = ctx.flush_buffer().await {
+                        ctx.report_user_error(e).await;
+                    }
+                }
+
                 s.on_close(ctx).await;
 
                 result.into()
@@ -496,12 +503,20 @@ pub trait ArrowOperator: Send + 'static {
     async fn on_close(&mut self, final_message: &Option<SignalMessage>, ctx: &mut ArrowContext) {}
 }
 
+/// A user-supplied asynchronous function applied to whole batches, for example to enrich
+/// records with batched lookups against an external service.
+#[async_trait]
+pub trait AsyncBatchTransform: Send + Sync {
+    async fn transform(&self, batch: RecordBatch) -> anyhow::Result<RecordBatch>;
+}
+
 #[derive(Default)]
 pub struct Registry {
     dylibs: Arc<std::sync::Mutex<HashMap<String, Arc<UdfDylib>>>>,
     udfs: HashMap<String, Arc<ScalarUDF>>,
     udafs: HashMap<String, Arc<AggregateUDF>>,
     udwfs: HashMap<String, Arc<WindowUDF>>,
+    batch_transforms: HashMap<String, Arc<dyn AsyncBatchTransform>>,
 }
 
 impl Registry {
@@ -660,6 +675,18 @@ impl Registry {
     pub fn add_udaf(&mut self, udaf: Arc<AggregateUDF>) {
         self.udafs.insert(udaf.name().to_string(), udaf);
     }
+
+    pub fn add_batch_transform(
+        &mut self,
+        name: impl Into<String>,
+        transform: Arc<dyn AsyncBatchTransform>,
+    ) {
+        self.batch_transforms.insert(name.into(), transform);
+    }
+
+    pub fn get_batch_transform(&self, name: &str) -> Option<Arc<dyn AsyncBatchTransform>> {
+        self.batch_transforms.get(name).cloned()
+    }
 }
 
 impl FunctionRegistry for Registry {
@@ -1,7 +1,7 @@
 use regex::Regex;
 use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp::Ordering;
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
 use std::hash::{Hash, Hasher};
 use std::str::FromStr;
 use std::sync::OnceLock;
@@ -164,6 +164,356 @@ impl<'de> Deserialize<'de> for SerializableAvroSchema {
     }
 }
 
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum OversizedArrayPolicy {
+    /// Keep the first `max_array_length` elements and drop the rest
+    #[default]
+    Truncate,
+    /// Treat the record as bad data
+    Reject,
+}
+
+impl TryFrom<&str> for OversizedArrayPolicy {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "truncate" => Ok(OversizedArrayPolicy::Truncate),
+            "reject" => Ok(OversizedArrayPolicy::Reject),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum NestingOverflowPolicy {
+    /// Replace values nested deeper than `max_nesting_depth` with null
+    #[default]
+    Truncate,
+    /// Treat the record as bad data
+    Drop,
+    /// Fail the pipeline
+    Error,
+}
+
+impl TryFrom<&str> for NestingOverflowPolicy {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "truncate" => Ok(NestingOverflowPolicy::Truncate),
+            "drop" => Ok(NestingOverflowPolicy::Drop),
+            "error" => Ok(NestingOverflowPolicy::Error),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldNameCollisionPolicy {
+    /// Treat records with several fields matching the same column as bad data
+    #[default]
+    Error,
+    /// Use the first matching field in the record, ignoring the others
+    FirstWins,
+}
+
+impl TryFrom<&str> for FieldNameCollisionPolicy {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "error" => Ok(FieldNameCollisionPolicy::Error),
+            "first_wins" => Ok(FieldNameCollisionPolicy::FirstWins),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum UnframedMessagePolicy {
+    /// Treat messages without the schema registry framing as bad data
+    #[default]
+    Error,
+    /// Decode messages without the schema registry framing as raw datums written with the
+    /// reader schema. Framing is detected by the leading zero magic byte, so unframed datums that
+    /// happen to start with a zero byte are still treated as framed.
+    ReaderSchema,
+}
+
+impl TryFrom<&str> for UnframedMessagePolicy {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "error" => Ok(UnframedMessagePolicy::Error),
+            "reader_schema" => Ok(UnframedMessagePolicy::ReaderSchema),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum NonFiniteDecimalPolicy {
+    /// Treat the record as bad data, reporting the column and value
+    #[default]
+    Error,
+    /// Replace the value with null
+    Null,
+}
+
+impl TryFrom<&str> for NonFiniteDecimalPolicy {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "error" => Ok(NonFiniteDecimalPolicy::Error),
+            "null" => Ok(NonFiniteDecimalPolicy::Null),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum FieldTransform {
+    /// Remove leading and trailing whitespace
+    Trim,
+    /// Convert to lowercase
+    Lowercase,
+    /// Convert to uppercase
+    Uppercase,
+    /// Replace with the hex-encoded SHA-256 hash of the value
+    Hash,
+}
+
+impl TryFrom<&str> for FieldTransform {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "trim" => Ok(FieldTransform::Trim),
+            "lowercase" => Ok(FieldTransform::Lowercase),
+            "uppercase" => Ok(FieldTransform::Uppercase),
+            "hash" => Ok(FieldTransform::Hash),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum MapNullValuePolicy {
+    /// Keep entries whose value is null, with a null value, so that a key mapped to null can be
+    /// distinguished from an absent key
+    #[default]
+    Preserve,
+    /// Remove entries whose value is null, as if the key were absent
+    Drop,
+}
+
+impl TryFrom<&str> for MapNullValuePolicy {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "preserve" => Ok(MapNullValuePolicy::Preserve),
+            "drop" => Ok(MapNullValuePolicy::Drop),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum IntegerOverflowPolicy {
+    /// Treat the record as bad data, reporting the column and value
+    #[default]
+    Error,
+    /// Replace the value with null
+    Null,
+    /// Clamp the value to the range of the column type
+    Saturate,
+}
+
+impl TryFrom<&str> for IntegerOverflowPolicy {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "error" => Ok(IntegerOverflowPolicy::Error),
+            "null" => Ok(IntegerOverflowPolicy::Null),
+            "saturate" => Ok(IntegerOverflowPolicy::Saturate),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How to handle time-of-day values outside of a day (negative, or a day or more past midnight)
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum OutOfRangeTimePolicy {
+    /// Treat the record as bad data, reporting the column and value
+    #[default]
+    Error,
+    /// Replace the value with null
+    Null,
+}
+
+impl TryFrom<&str> for OutOfRangeTimePolicy {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "error" => Ok(OutOfRangeTimePolicy::Error),
+            "null" => Ok(OutOfRangeTimePolicy::Null),
+            _ => Err(()),
+        }
+    }
+}
+
+/// How to handle values of dictionary-encoded enum columns that aren't enum symbols, or whose
+/// symbol index doesn't fit in the column's key type
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum UnknownEnumSymbolPolicy {
+    /// Treat the record as bad data, reporting the column
+    #[default]
+    Error,
+    /// Replace the value with null
+    Null,
+}
+
+impl TryFrom<&str> for UnknownEnumSymbolPolicy {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "error" => Ok(UnknownEnumSymbolPolicy::Error),
+            "null" => Ok(UnknownEnumSymbolPolicy::Null),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum PrecisionLossPolicy {
+    /// Truncate the fractional part of the value
+    #[default]
+    Truncate,
+    /// Treat the record as bad data, reporting the column and value
+    Error,
+}
+
+impl TryFrom<&str> for PrecisionLossPolicy {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "truncate" => Ok(PrecisionLossPolicy::Truncate),
+            "error" => Ok(PrecisionLossPolicy::Error),
+            _ => Err(()),
+        }
+    }
+}
+
+#[derive(
+    Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Default, Hash, PartialOrd, ToSchema,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum DecimalSeparator {
+    /// Numeric strings use a period as the decimal separator, and are parsed as-is
+    #[default]
+    Period,
+    /// Numeric strings use a comma as the decimal separator and periods or spaces to group
+    /// digits, like `1.234,56`
+    Comma,
+}
+
+impl TryFrom<&str> for DecimalSeparator {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "period" => Ok(DecimalSeparator::Period),
+            "comma" => Ok(DecimalSeparator::Comma),
+            _ => Err(()),
+        }
+    }
+}
+
+/// An Arrow type that Avro values with a logical type may be decoded into, in place of the
+/// default for that logical type
+#[derive(Clone, Copy, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum LogicalTypeMapping {
+    TimestampMillis,
+    TimestampMicros,
+    TimestampNanos,
+    Int64,
+    Float64,
+    Binary,
+    /// Decimals only; decoded with the decimal's scale and precision, as Decimal128 for
+    /// precisions of up to 38 and Decimal256 above that
+    Decimal,
+    /// UUIDs only; decoded as their 16 bytes
+    FixedSizeBinary,
+}
+
+impl TryFrom<&str> for LogicalTypeMapping {
+    type Error = ();
+
+    fn try_from(value: &str) -> Result<Self, Self::Error> {
+        match value {
+            "timestamp_millis" => Ok(LogicalTypeMapping::TimestampMillis),
+            "timestamp_micros" => Ok(LogicalTypeMapping::TimestampMicros),
+            "timestamp_nanos" => Ok(LogicalTypeMapping::TimestampNanos),
+            "int64" => Ok(LogicalTypeMapping::Int64),
+            "float64" => Ok(LogicalTypeMapping::Float64),
+            "binary" => Ok(LogicalTypeMapping::Binary),
+            "decimal" => Ok(LogicalTypeMapping::Decimal),
+            "fixed_size_binary" => Ok(LogicalTypeMapping::FixedSizeBinary),
+            _ => Err(()),
+        }
+    }
+}
+
+/// The Avro logical types whose Arrow type can be overridden with a [`LogicalTypeMapping`]
+pub const AVRO_MAPPABLE_LOGICAL_TYPES: &[&str] = &[
+    "decimal",
+    "timestamp-millis",
+    "timestamp-micros",
+    "local-timestamp-millis",
+    "local-timestamp-micros",
+    "uuid",
+];
+
 #[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq, Hash, PartialOrd, ToSchema)]
 #[serde(rename_all = "camelCase")]
 pub struct AvroFormat {
@@ -173,6 +523,16 @@ pub struct AvroFormat {
     #[serde(default)]
     pub raw_datums: bool,
 
+    /// Messages use Avro's single-object encoding, which identifies the writer schema by its
+    /// fingerprint; the reader schema is the only one that's recognized
+    #[serde(default)]
+    pub single_object_encoding: bool,
+
+    /// How to handle messages that don't have the schema registry framing, for topics that mix
+    /// framed and unframed messages
+    #[serde(default)]
+    pub unframed_messages: UnframedMessagePolicy,
+
     #[serde(default)]
     pub into_unstructured_json: bool,
 
@@ -183,6 +543,109 @@ pub struct AvroFormat {
     #[serde(default)]
     #[schema(read_only)]
     pub schema_id: Option<u32>,
+
+    /// A schema to decode messages against when their writer schema can't be resolved, for
+    /// example during a schema registry outage
+    #[serde(default)]
+    #[schema(value_type = String)]
+    pub fallback_schema: Option<SerializableAvroSchema>,
+
+    #[serde(default)]
+    pub max_array_length: Option<u64>,
+
+    #[serde(default)]
+    pub oversized_arrays: OversizedArrayPolicy,
+
+    #[serde(default)]
+    pub max_nesting_depth: Option<u32>,
+
+    #[serde(default)]
+    pub nesting_overflow: NestingOverflowPolicy,
+
+    #[serde(default)]
+    pub integer_overflow: IntegerOverflowPolicy,
+
+    #[serde(default)]
+    pub out_of_range_times: OutOfRangeTimePolicy,
+
+    #[serde(default)]
+    pub unknown_enum_symbols: UnknownEnumSymbolPolicy,
+
+    /// Match Avro fields to columns ignoring case, for producers that are inconsistent about the
+    /// casing of field names
+    #[serde(default)]
+    pub case_insensitive_fields: bool,
+
+    #[serde(default)]
+    pub field_name_collisions: FieldNameCollisionPolicy,
+
+    /// Write int, long, float, double, and boolean values into string columns as text, so that
+    /// unions mixing those with strings can be read into a single string column
+    #[serde(default)]
+    pub stringify_scalars: bool,
+
+    #[serde(default)]
+    pub map_null_values: MapNullValuePolicy,
+
+    #[serde(default)]
+    pub suppress_empty_batches: bool,
+
+    /// When a set of messages is deserialized together, decode the messages for each schema id
+    /// in parallel; wide batches also have their columns converted in parallel when flushed
+    #[serde(default)]
+    pub parallel_decode: bool,
+
+    /// If set, batches are flushed once the estimated in-memory size of their records reaches
+    /// this many bytes, in addition to the usual row-count and time limits
+    #[serde(default)]
+    pub max_batch_bytes: Option<u64>,
+
+    /// If more than this many records in a batch fail to decode, the batch is failed rather than
+    /// emitted without them
+    #[serde(default)]
+    pub max_batch_errors: Option<u64>,
+
+    /// If more than this percentage of records in a batch fail to decode, the batch is failed
+    /// rather than emitted without them
+    #[serde(default)]
+    pub max_batch_error_percent: Option<u32>,
+
+    #[serde(default)]
+    pub field_timing: bool,
+
+    /// Record the names of fields in writer schemas that don't correspond to any column, which
+    /// are otherwise silently ignored
+    #[serde(default)]
+    pub report_unexpected_fields: bool,
+
+    #[serde(default)]
+    pub null_sentinels: BTreeMap<String, String>,
+
+    #[serde(default)]
+    pub float_precision_loss: PrecisionLossPolicy,
+
+    /// How to handle NaN and infinite values decoded into decimal columns, which can't represent
+    /// them
+    #[serde(default)]
+    pub non_finite_decimals: NonFiniteDecimalPolicy,
+
+    #[serde(default)]
+    pub required_fields: BTreeMap<u32, Vec<String>>,
+
+    #[serde(default)]
+    pub decimal_separator: DecimalSeparator,
+
+    #[serde(default)]
+    pub logical_type_mappings: BTreeMap<String, LogicalTypeMapping>,
+
+    /// For fields whose dates or timestamps are relative to an epoch other than the Unix epoch,
+    /// that epoch in seconds since the Unix epoch, by field path
+    #[serde(default)]
+    pub epoch_offsets: BTreeMap<String, i64>,
+
+    /// Transforms applied to string fields as they're decoded, in order, by field path
+    #[serde(default)]
+    pub field_transforms: BTreeMap<String, Vec<FieldTransform>>,
 }
 
 impl AvroFormat {
@@ -194,14 +657,43 @@ impl AvroFormat {
         Self {
             confluent_schema_registry,
             raw_datums,
+            single_object_encoding: false,
+            unframed_messages: UnframedMessagePolicy::default(),
             into_unstructured_json,
             reader_schema: None,
             schema_id: None,
+            fallback_schema: None,
+            max_array_length: None,
+            oversized_arrays: OversizedArrayPolicy::default(),
+            max_nesting_depth: None,
+            nesting_overflow: NestingOverflowPolicy::default(),
+            integer_overflow: IntegerOverflowPolicy::default(),
+            out_of_range_times: OutOfRangeTimePolicy::default(),
+            unknown_enum_symbols: UnknownEnumSymbolPolicy::default(),
+            case_insensitive_fields: false,
+            field_name_collisions: FieldNameCollisionPolicy::default(),
+            stringify_scalars: false,
+            map_null_values: MapNullValuePolicy::default(),
+            suppress_empty_batches: false,
+            parallel_decode: false,
+            max_batch_bytes: None,
+            max_batch_errors: None,
+            max_batch_error_percent: None,
+            field_timing: false,
+            report_unexpected_fields: false,
+            null_sentinels: BTreeMap::new(),
+            float_precision_loss: PrecisionLossPolicy::default(),
+            non_finite_decimals: NonFiniteDecimalPolicy::default(),
+            required_fields: BTreeMap::new(),
+            decimal_separator: DecimalSeparator::default(),
+            logical_type_mappings: BTreeMap::new(),
+            epoch_offsets: BTreeMap::new(),
+            field_transforms: BTreeMap::new(),
         }
     }
 
     pub fn from_opts(opts: &mut HashMap<String, String>) -> Result<Self, String> {
-        Ok(Self::new(
+        let mut format = Self::new(
             opts.remove("avro.confluent_schema_registry")
                 .filter(|t| t == "true")
                 .is_some(),
@@ -211,7 +703,265 @@ impl AvroFormat {
             opts.remove("avro.into_unstructured_json")
                 .filter(|t| t == "true")
                 .is_some(),
-        ))
+        );
+
+        format.single_object_encoding = opts
+            .remove("avro.single_object_encoding")
+            .filter(|t| t == "true")
+            .is_some();
+
+        if let Some(policy) = opts.remove("avro.unframed_messages") {
+            format.unframed_messages = policy.as_str().try_into().map_err(|_| {
+                "invalid value for avro.unframed_messages; must be one of 'error' or 'reader_schema'"
+                    .to_string()
+            })?;
+        }
+
+        format.fallback_schema = opts
+            .remove("avro.fallback_schema")
+            .map(|t| apache_avro::Schema::parse_str(&t).map(SerializableAvroSchema))
+            .transpose()
+            .map_err(|e| format!("invalid value for avro.fallback_schema: {:?}", e))?;
+
+        format.max_array_length = opts
+            .remove("avro.max_array_length")
+            .map(|t| u64::from_str(&t))
+            .transpose()
+            .map_err(|_| {
+                "invalid value for avro.max_array_length; must be an unsigned integer".to_string()
+            })?;
+
+        if let Some(policy) = opts.remove("avro.oversized_arrays") {
+            format.oversized_arrays = policy.as_str().try_into().map_err(|_| {
+                "invalid value for avro.oversized_arrays; must be one of 'truncate' or 'reject'"
+                    .to_string()
+            })?;
+        }
+
+        format.max_nesting_depth = opts
+            .remove("avro.max_nesting_depth")
+            .map(|t| u32::from_str(&t))
+            .transpose()
+            .map_err(|_| {
+                "invalid value for avro.max_nesting_depth; must be an unsigned integer".to_string()
+            })?;
+
+        if let Some(policy) = opts.remove("avro.nesting_overflow") {
+            format.nesting_overflow = policy.as_str().try_into().map_err(|_| {
+                "invalid value for avro.nesting_overflow; must be one of 'truncate', 'drop', or 'error'"
+                    .to_string()
+            })?;
+        }
+
+        if let Some(policy) = opts.remove("avro.integer_overflow") {
+            format.integer_overflow = policy.as_str().try_into().map_err(|_| {
+                "invalid value for avro.integer_overflow; must be one of 'error', 'null', or 'saturate'"
+                    .to_string()
+            })?;
+        }
+
+        if let Some(policy) = opts.remove("avro.out_of_range_times") {
+            format.out_of_range_times = policy.as_str().try_into().map_err(|_| {
+                "invalid value for avro.out_of_range_times; must be one of 'error' or 'null'"
+                    .to_string()
+            })?;
+        }
+
+        if let Some(policy) = opts.remove("avro.unknown_enum_symbols") {
+            format.unknown_enum_symbols = policy.as_str().try_into().map_err(|_| {
+                "invalid value for avro.unknown_enum_symbols; must be one of 'error' or 'null'"
+                    .to_string()
+            })?;
+        }
+
+        format.case_insensitive_fields = opts
+            .remove("avro.case_insensitive_fields")
+            .filter(|t| t == "true")
+            .is_some();
+
+        if let Some(policy) = opts.remove("avro.field_name_collisions") {
+            format.field_name_collisions = policy.as_str().try_into().map_err(|_| {
+                "invalid value for avro.field_name_collisions; must be one of 'error' or 'first_wins'"
+                    .to_string()
+            })?;
+        }
+
+        format.stringify_scalars = opts
+            .remove("avro.stringify_scalars")
+            .filter(|t| t == "true")
+            .is_some();
+
+        if let Some(policy) = opts.remove("avro.map_null_values") {
+            format.map_null_values = policy.as_str().try_into().map_err(|_| {
+                "invalid value for avro.map_null_values; must be one of 'preserve' or 'drop'"
+                    .to_string()
+            })?;
+        }
+
+        format.suppress_empty_batches = opts
+            .remove("avro.suppress_empty_batches")
+            .filter(|t| t == "true")
+            .is_some();
+
+        format.parallel_decode = opts
+            .remove("avro.parallel_decode")
+            .filter(|t| t == "true")
+            .is_some();
+
+        format.max_batch_bytes = opts
+            .remove("avro.max_batch_bytes")
+            .map(|t| u64::from_str(&t))
+            .transpose()
+            .map_err(|_| {
+                "invalid value for avro.max_batch_bytes; must be an unsigned integer".to_string()
+            })?;
+
+        format.max_batch_errors = opts
+            .remove("avro.max_batch_errors")
+            .map(|t| u64::from_str(&t))
+            .transpose()
+            .map_err(|_| {
+                "invalid value for avro.max_batch_errors; must be an unsigned integer".to_string()
+            })?;
+
+        if let Some(percent) = opts.remove("avro.max_batch_error_percent") {
+            format.max_batch_error_percent = Some(
+                u32::from_str(&percent)
+                    .ok()
+                    .filter(|p| *p <= 100)
+                    .ok_or_else(|| {
+                        "invalid value for avro.max_batch_error_percent; must be an integer \
+                        between 0 and 100"
+                            .to_string()
+                    })?,
+            );
+        }
+
+        format.field_timing = opts
+            .remove("avro.field_timing")
+            .filter(|t| t == "true")
+            .is_some();
+
+        format.report_unexpected_fields = opts
+            .remove("avro.report_unexpected_fields")
+            .filter(|t| t == "true")
+            .is_some();
+
+        if let Some(policy) = opts.remove("avro.float_precision_loss") {
+            format.float_precision_loss = policy.as_str().try_into().map_err(|_| {
+                "invalid value for avro.float_precision_loss; must be one of 'truncate' or 'error'"
+                    .to_string()
+            })?;
+        }
+
+        if let Some(policy) = opts.remove("avro.non_finite_decimals") {
+            format.non_finite_decimals = policy.as_str().try_into().map_err(|_| {
+                "invalid value for avro.non_finite_decimals; must be one of 'error' or 'null'"
+                    .to_string()
+            })?;
+        }
+
+        if let Some(separator) = opts.remove("avro.decimal_separator") {
+            format.decimal_separator = separator.as_str().try_into().map_err(|_| {
+                "invalid value for avro.decimal_separator; must be one of 'period' or 'comma'"
+                    .to_string()
+            })?;
+        }
+
+        if let Some(required) = opts.remove("avro.required_fields") {
+            for schema in required.split(';').filter(|s| !s.is_empty()) {
+                let (id, fields) = schema
+                    .split_once('=')
+                    .and_then(|(id, fields)| Some((u32::from_str(id.trim()).ok()?, fields)))
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid value for avro.required_fields; expected semicolon-separated \
+                            'schema_id=field,field' entries, found '{}'",
+                            schema
+                        )
+                    })?;
+                format.required_fields.insert(
+                    id,
+                    fields.split(',').map(|f| f.trim().to_string()).collect(),
+                );
+            }
+        }
+
+        if let Some(sentinels) = opts.remove("avro.null_sentinels") {
+            for sentinel in sentinels.split(',').filter(|s| !s.is_empty()) {
+                let (field, value) = sentinel.split_once('=').ok_or_else(|| {
+                    format!(
+                        "invalid value for avro.null_sentinels; expected comma-separated \
+                        'field=value' pairs, found '{}'",
+                        sentinel
+                    )
+                })?;
+                format
+                    .null_sentinels
+                    .insert(field.trim().to_string(), value.to_string());
+            }
+        }
+
+        if let Some(mappings) = opts.remove("avro.logical_type_mappings") {
+            for mapping in mappings.split(',').filter(|s| !s.is_empty()) {
+                let (logical_type, mapping) = mapping
+                    .split_once('=')
+                    .map(|(l, m)| (l.trim(), m.trim()))
+                    .filter(|(l, _)| AVRO_MAPPABLE_LOGICAL_TYPES.contains(l))
+                    .and_then(|(l, m)| Some((l, LogicalTypeMapping::try_from(m).ok()?)))
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid value for avro.logical_type_mappings; expected comma-separated \
+                            'logical-type=arrow_type' pairs, found '{}'",
+                            mapping
+                        )
+                    })?;
+                format
+                    .logical_type_mappings
+                    .insert(logical_type.to_string(), mapping);
+            }
+        }
+
+        if let Some(offsets) = opts.remove("avro.epoch_offsets") {
+            for offset in offsets.split(',').filter(|s| !s.is_empty()) {
+                let (field, seconds) = offset
+                    .split_once('=')
+                    .and_then(|(f, s)| Some((f.trim(), i64::from_str(s.trim()).ok()?)))
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid value for avro.epoch_offsets; expected comma-separated \
+                            'field=seconds' pairs, found '{}'",
+                            offset
+                        )
+                    })?;
+                format.epoch_offsets.insert(field.to_string(), seconds);
+            }
+        }
+
+        if let Some(transforms) = opts.remove("avro.field_transforms") {
+            for pipeline in transforms.split(',').filter(|s| !s.is_empty()) {
+                let (field, steps) = pipeline
+                    .split_once('=')
+                    .and_then(|(f, t)| {
+                        let steps = t
+                            .split('|')
+                            .map(|t| FieldTransform::try_from(t.trim()).ok())
+                            .collect::<Option<Vec<_>>>()?;
+                        Some((f.trim(), steps))
+                    })
+                    .ok_or_else(|| {
+                        format!(
+                            "invalid value for avro.field_transforms; expected comma-separated \
+                            'field=transform|transform' pipelines using 'trim', 'lowercase', \
+                            'uppercase' or 'hash', found '{}'",
+                            pipeline
+                        )
+                    })?;
+                format.field_transforms.insert(field.to_string(), steps);
+            }
+        }
+
+        Ok(format)
     }
 
     pub fn add_reader_schema(&mut self, schema: apache_avro::Schema) {
@@ -222,7 +972,14 @@ impl AvroFormat {
         static RE: OnceLock<Regex> = OnceLock::new();
         let re = RE.get_or_init(|| Regex::new(r"[^a-zA-Z0-9_.]").unwrap());
 
-        re.replace_all(s, "_").replace('.', "__")
+        let sanitized = re.replace_all(s, "_").replace('.', "__");
+
+        // avro names can't start with a digit
+        if sanitized.is_empty() || sanitized.starts_with(|c: char| c.is_ascii_digit()) {
+            format!("_{}", sanitized)
+        } else {
+            sanitized
+        }
     }
 }
 
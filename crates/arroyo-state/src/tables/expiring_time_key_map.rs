@@ -11,7 +11,7 @@ use arrow::row::OwnedRow;
 use arrow_array::{
     cast::AsArray,
     types::{TimestampNanosecondType, UInt64Type},
-    BooleanArray, PrimitiveArray, RecordBatch, TimestampNanosecondArray, UInt64Array,
+    ArrayRef, BooleanArray, PrimitiveArray, RecordBatch, TimestampNanosecondArray, UInt64Array,
 };
 use arrow_ord::{partition::partition, sort::sort_to_indices};
 use arroyo_rpc::{
@@ -1099,6 +1099,52 @@ impl LastKeyValueView {
         )))
     }
 
+    /// Returns the current value of every key, in the schema of the batches that are inserted.
+    pub fn get_all_values(&self) -> Result<RecordBatch> {
+        let memory_schema = self.parent.schema.memory_schema();
+        let generation_index = self
+            .parent
+            .schema
+            .generation_index()
+            .ok_or_else(|| anyhow!("should have generation index"))?;
+        let indices: Vec<_> = (0..memory_schema.schema.fields().len())
+            .filter(|index| *index != generation_index)
+            .collect();
+        let schema = Arc::new(memory_schema.schema.project(&indices)?);
+
+        let mut keys = Vec::with_capacity(self.backing_map.len());
+        let mut values = Vec::with_capacity(self.backing_map.len());
+        let mut timestamps = TimestampNanosecondArray::builder(self.backing_map.len());
+        for (key, value) in &self.backing_map {
+            keys.push(key.as_slice());
+            values.push(value.value_row_bytes.as_slice());
+            timestamps.append_value(to_nanos(value.timestamp) as i64);
+        }
+
+        let mut columns: Vec<Option<ArrayRef>> = vec![None; indices.len()];
+        for (index, column) in self
+            .key_indices
+            .iter()
+            .zip(self.key_converter.convert_raw_rows(keys)?)
+        {
+            columns[*index] = Some(column);
+        }
+        for (index, column) in self
+            .value_indices
+            .iter()
+            .zip(self.value_converter.convert_raw_rows(values)?)
+        {
+            columns[*index] = Some(column);
+        }
+        columns[memory_schema.timestamp_index] = Some(Arc::new(timestamps.finish()));
+
+        let columns = columns
+            .into_iter()
+            .map(|column| column.ok_or_else(|| anyhow!("missing column for schema")))
+            .collect::<Result<Vec<_>>>()?;
+        Ok(RecordBatch::try_new(schema, columns)?)
+    }
+
     async fn insert_batch_internal(&mut self, batch: RecordBatch, is_backfill: bool) -> Result<()> {
         if batch.num_rows() == 0 {
             return Ok(());
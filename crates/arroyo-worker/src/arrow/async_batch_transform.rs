@@ -0,0 +1,349 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use arrow_array::RecordBatch;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{
+    ArrowOperator, AsyncBatchTransform, OperatorConstructor, OperatorNode, Registry,
+};
+use arroyo_rpc::grpc::api;
+use arroyo_types::{CheckpointBarrier, SignalMessage, Watermark};
+use futures::future::BoxFuture;
+use futures::stream::{FuturesOrdered, FuturesUnordered};
+use futures::{FutureExt, StreamExt};
+use tracing::warn;
+
+type TransformFuture = BoxFuture<'static, Result<RecordBatch>>;
+
+enum Pending {
+    Ordered(FuturesOrdered<TransformFuture>),
+    Unordered(FuturesUnordered<TransformFuture>),
+}
+
+impl Pending {
+    fn len(&self) -> usize {
+        match self {
+            Pending::Ordered(f) => f.len(),
+            Pending::Unordered(f) => f.len(),
+        }
+    }
+
+    fn push(&mut self, future: TransformFuture) {
+        match self {
+            Pending::Ordered(f) => f.push_back(future),
+            Pending::Unordered(f) => f.push(future),
+        }
+    }
+
+    async fn next(&mut self) -> Option<Result<RecordBatch>> {
+        match self {
+            Pending::Ordered(f) => f.next().await,
+            Pending::Unordered(f) => f.next().await,
+        }
+    }
+}
+
+/// Runs a transform over batches with at most `max_concurrency` invocations in flight, retrying
+/// failed invocations with a linear backoff. When ordered, results are returned in the order the
+/// batches were submitted; otherwise they are returned as they complete.
+pub(crate) struct AsyncTransformer {
+    transform: Arc<dyn AsyncBatchTransform>,
+    max_concurrency: usize,
+    max_retries: u32,
+    retry_backoff: Duration,
+    pending: Pending,
+}
+
+impl AsyncTransformer {
+    pub(crate) fn new(
+        transform: Arc<dyn AsyncBatchTransform>,
+        ordered: bool,
+        max_concurrency: usize,
+        max_retries: u32,
+        retry_backoff: Duration,
+    ) -> Self {
+        Self {
+            transform,
+            max_concurrency: max_concurrency.max(1),
+            max_retries,
+            retry_backoff,
+            pending: if ordered {
+                Pending::Ordered(FuturesOrdered::new())
+            } else {
+                Pending::Unordered(FuturesUnordered::new())
+            },
+        }
+    }
+
+    /// Submits a batch to be transformed. If the concurrency limit has been reached, this first
+    /// waits for in-flight transforms to complete, returning their results; this provides
+    /// backpressure to the upstream.
+    pub(crate) async fn submit(&mut self, batch: RecordBatch) -> Vec<Result<RecordBatch>> {
+        let mut results = vec![];
+        while self.pending.len() >= self.max_concurrency {
+            results.extend(self.pending.next().await);
+        }
+
+        let transform = self.transform.clone();
+        let max_retries = self.max_retries;
+        let retry_backoff = self.retry_backoff;
+        // spawn the invocation so that it makes progress independently of the operator polling
+        let handle = tokio::spawn(async move {
+            let mut attempt = 0;
+            loop {
+                match transform.transform(batch.clone()).await {
+                    Ok(result) => return Ok(result),
+                    Err(e) if attempt < max_retries => {
+                        attempt += 1;
+                        warn!("async transform failed (attempt {}): {:?}", attempt, e);
+                        tokio::time::sleep(retry_backoff * attempt).await;
+                    }
+                    Err(e) => return Err(e),
+                }
+            }
+        });
+
+        self.pending.push(
+            handle
+                .map(|r| r.unwrap_or_else(|e| Err(anyhow!("async transform panicked: {:?}", e))))
+                .boxed(),
+        );
+
+        results
+    }
+
+    /// Returns the results of any transforms that have already completed, without waiting.
+    pub(crate) fn ready(&mut self) -> Vec<Result<RecordBatch>> {
+        let mut results = vec![];
+        while let Some(Some(result)) = self.pending.next().now_or_never() {
+            results.push(result);
+        }
+        results
+    }
+
+    /// Waits for all in-flight transforms to complete.
+    pub(crate) async fn drain(&mut self) -> Vec<Result<RecordBatch>> {
+        let mut results = vec![];
+        while let Some(result) = self.pending.next().await {
+            results.push(result);
+        }
+        results
+    }
+}
+
+pub struct AsyncBatchTransformOperator {
+    name: String,
+    drop_failed_batches: bool,
+    transformer: AsyncTransformer,
+}
+
+impl AsyncBatchTransformOperator {
+    async fn emit(&mut self, results: Vec<Result<RecordBatch>>, ctx: &mut ArrowContext) {
+        for result in results {
+            match result {
+                Ok(batch) => ctx.collect(batch).await,
+                Err(e) if self.drop_failed_batches => {
+                    ctx.report_error("async transform failed; dropping batch", format!("{:?}", e))
+                        .await;
+                }
+                Err(e) => panic!("async transform {} failed: {:?}", self.name, e),
+            }
+        }
+    }
+}
+
+pub struct AsyncBatchTransformConstructor;
+
+impl OperatorConstructor for AsyncBatchTransformConstructor {
+    type ConfigT = api::AsyncBatchTransformOperator;
+
+    fn with_config(&self, config: Self::ConfigT, registry: Arc<Registry>) -> Result<OperatorNode> {
+        let transform = registry
+            .get_batch_transform(&config.transform)
+            .ok_or_else(|| anyhow!("no async batch transform named '{}'", config.transform))?;
+
+        let ordered = match api::AsyncUdfOrdering::try_from(config.ordering) {
+            Err(_) | Ok(api::AsyncUdfOrdering::Ordered) => true,
+            Ok(api::AsyncUdfOrdering::Unordered) => false,
+        };
+
+        Ok(OperatorNode::from_operator(Box::new(
+            AsyncBatchTransformOperator {
+                name: config.name,
+                drop_failed_batches: config.drop_failed_batches,
+                transformer: AsyncTransformer::new(
+                    transform,
+                    ordered,
+                    config.max_concurrency as usize,
+                    config.max_retries,
+                    Duration::from_micros(config.retry_backoff_micros),
+                ),
+            },
+        )))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for AsyncBatchTransformOperator {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(Duration::from_millis(50))
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        let results = self.transformer.submit(batch).await;
+        self.emit(results, ctx).await;
+    }
+
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) {
+        let results = self.transformer.ready();
+        self.emit(results, ctx).await;
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        // everything submitted before the watermark must be emitted before it
+        let results = self.transformer.drain().await;
+        self.emit(results, ctx).await;
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+        // in-flight batches are completed rather than checkpointed, so no state is needed
+        let results = self.transformer.drain().await;
+        self.emit(results, ctx).await;
+    }
+
+    async fn on_close(&mut self, _: &Option<SignalMessage>, ctx: &mut ArrowContext) {
+        let results = self.transformer.drain().await;
+        self.emit(results, ctx).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Array, Int64Array};
+    use arrow_schema::{DataType, Field, Schema};
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicU32, AtomicUsize, Ordering};
+
+    /// Sleeps for a time that decreases with the batch's value, so later batches finish first,
+    /// and tracks how many invocations are running at once.
+    #[derive(Default)]
+    struct MockTransform {
+        running: AtomicUsize,
+        max_running: AtomicUsize,
+        failures_remaining: AtomicU32,
+    }
+
+    #[async_trait]
+    impl AsyncBatchTransform for MockTransform {
+        async fn transform(&self, batch: RecordBatch) -> Result<RecordBatch> {
+            let running = self.running.fetch_add(1, Ordering::SeqCst) + 1;
+            self.max_running.fetch_max(running, Ordering::SeqCst);
+
+            let value = value(&batch);
+            tokio::time::sleep(Duration::from_millis(10 * (10 - value as u64))).await;
+            self.running.fetch_sub(1, Ordering::SeqCst);
+
+            if self
+                .failures_remaining
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |f| f.checked_sub(1))
+                .is_ok()
+            {
+                return Err(anyhow!("transient failure"));
+            }
+
+            Ok(batch)
+        }
+    }
+
+    fn batch(value: i64) -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("v", DataType::Int64, false)])),
+            vec![Arc::new(Int64Array::from(vec![value]))],
+        )
+        .unwrap()
+    }
+
+    fn value(batch: &RecordBatch) -> i64 {
+        batch
+            .column(0)
+            .as_any()
+            .downcast_ref::<Int64Array>()
+            .unwrap()
+            .value(0)
+    }
+
+    async fn run(transformer: &mut AsyncTransformer) -> Vec<i64> {
+        let mut results = vec![];
+        for i in 0..6 {
+            results.extend(transformer.submit(batch(i)).await);
+        }
+        results.extend(transformer.drain().await);
+        results.into_iter().map(|r| value(&r.unwrap())).collect()
+    }
+
+    #[tokio::test]
+    async fn test_ordered() {
+        let transform = Arc::new(MockTransform::default());
+        let mut transformer = AsyncTransformer::new(transform.clone(), true, 3, 0, Duration::ZERO);
+
+        assert_eq!(run(&mut transformer).await, vec![0, 1, 2, 3, 4, 5]);
+        assert_eq!(transform.max_running.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_unordered() {
+        let transform = Arc::new(MockTransform::default());
+        let mut transformer = AsyncTransformer::new(transform.clone(), false, 3, 0, Duration::ZERO);
+
+        let mut values = run(&mut transformer).await;
+        assert_ne!(values, vec![0, 1, 2, 3, 4, 5]);
+        values.sort();
+        assert_eq!(values, vec![0, 1, 2, 3, 4, 5]);
+        assert!(transform.max_running.load(Ordering::SeqCst) <= 3);
+    }
+
+    #[tokio::test]
+    async fn test_retries() {
+        let transform = Arc::new(MockTransform {
+            failures_remaining: AtomicU32::new(2),
+            ..Default::default()
+        });
+        let mut transformer =
+            AsyncTransformer::new(transform.clone(), true, 1, 2, Duration::from_millis(1));
+        assert_eq!(run(&mut transformer).await, vec![0, 1, 2, 3, 4, 5]);
+
+        // once retries are exhausted the error is returned
+        transform.failures_remaining.store(2, Ordering::SeqCst);
+        let mut transformer = AsyncTransformer::new(transform, true, 1, 1, Duration::ZERO);
+        transformer.submit(batch(0)).await;
+        let results = transformer.drain().await;
+        assert_eq!(results.len(), 1);
+        assert!(results[0].is_err());
+    }
+
+    #[tokio::test]
+    async fn test_ready_does_not_block() {
+        let transform = Arc::new(MockTransform::default());
+        let mut transformer = AsyncTransformer::new(transform, true, 2, 0, Duration::ZERO);
+
+        assert!(transformer.submit(batch(0)).await.is_empty());
+        assert!(transformer.ready().is_empty());
+
+        tokio::time::sleep(Duration::from_millis(150)).await;
+        let ready = transformer.ready();
+        assert_eq!(ready.len(), 1);
+        assert_eq!(ready[0].as_ref().unwrap().num_rows(), 1);
+        assert_eq!(ready[0].as_ref().unwrap().column(0).len(), 1);
+    }
+}
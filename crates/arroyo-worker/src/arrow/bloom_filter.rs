@@ -0,0 +1,269 @@
+use std::collections::HashMap;
+use std::hash::BuildHasher;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::row::{Rows, SortField};
+use arrow_array::{BooleanArray, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::{get_hasher, Converter};
+use arroyo_types::Watermark;
+use bincode::{Decode, Encode};
+
+use crate::arrow::keyed_state::{max_timestamp, KeyedValues};
+
+/// A bloom filter sized for an expected number of items and false-positive rate. Hashing uses
+/// fixed seeds, so filters restored from a checkpoint remain valid.
+#[derive(Debug, Clone, Encode, Decode, PartialEq)]
+pub(crate) struct BloomFilter {
+    bits: Vec<u64>,
+    num_bits: u64,
+    num_hashes: u32,
+}
+
+impl BloomFilter {
+    pub(crate) fn new(expected_items: u64, false_positive_rate: f64) -> Self {
+        let ln2 = std::f64::consts::LN_2;
+        let n = expected_items.max(1) as f64;
+        let num_bits = ((-n * false_positive_rate.ln()) / (ln2 * ln2))
+            .ceil()
+            .max(64.0) as u64;
+        let num_hashes = ((num_bits as f64 / n) * ln2).round().max(1.0) as u32;
+
+        Self {
+            bits: vec![0; num_bits.div_ceil(64) as usize],
+            num_bits,
+            num_hashes,
+        }
+    }
+
+    fn bit_indices(&self, item: &[u8]) -> impl Iterator<Item = u64> + '_ {
+        let hasher = get_hasher();
+        let h1 = hasher.hash_one(item);
+        let h2 = hasher.hash_one((item, 1u8)) | 1;
+        (0..self.num_hashes as u64)
+            .map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits)
+    }
+
+    /// Adds the item to the filter, returning whether it was possibly already present.
+    pub(crate) fn check_and_insert(&mut self, item: &[u8]) -> bool {
+        let indices: Vec<_> = self.bit_indices(item).collect();
+
+        let mut present = true;
+        for i in indices {
+            let (word, bit) = ((i / 64) as usize, 1 << (i % 64));
+            present &= self.bits[word] & bit != 0;
+            self.bits[word] |= bit;
+        }
+        present
+    }
+
+    pub(crate) fn contains(&self, item: &[u8]) -> bool {
+        self.bit_indices(item)
+            .all(|i| self.bits[(i / 64) as usize] & (1 << (i % 64)) != 0)
+    }
+}
+
+/// Flags each record whose value has possibly been seen before for its key. A key's filter is
+/// dropped once the key sees no records for
+/// [`KEYED_STATE_TTL`](crate::arrow::keyed_state::KEYED_STATE_TTL) of event time.
+pub struct BloomFilterFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    value_index: usize,
+    expected_items: u64,
+    false_positive_rate: f64,
+    key_converter: Converter,
+    value_converter: Converter,
+    stored_filters: KeyedValues,
+    filters: HashMap<Vec<u8>, BloomFilter>,
+}
+
+impl BloomFilterFunc {
+    fn keys(&self, batch: &RecordBatch) -> Result<Rows> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        self.key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())
+    }
+
+    fn process(&mut self, batch: &RecordBatch, keys: &Rows) -> Result<RecordBatch> {
+        let value_column = batch.column(self.value_index).clone();
+        let values = self
+            .value_converter
+            .convert_all_columns(&[value_column], batch.num_rows())?;
+
+        let (expected_items, false_positive_rate) = (self.expected_items, self.false_positive_rate);
+        let mut seen = Vec::with_capacity(batch.num_rows());
+        for i in 0..batch.num_rows() {
+            let filter = self
+                .filters
+                .entry(keys.row(i).as_ref().to_vec())
+                .or_insert_with(|| BloomFilter::new(expected_items, false_positive_rate));
+            seen.push(filter.check_and_insert(values.row(i).as_ref()));
+        }
+
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(BooleanArray::from(seen)));
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+
+    /// Checks the batch against the filters stored for its keys, and stores the updated filters.
+    async fn process_with_state(
+        &mut self,
+        batch: &RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> Result<RecordBatch> {
+        let keys = self.keys(batch)?;
+        self.filters = self.stored_filters.get(ctx, &keys).await?;
+        let output = self.process(batch, &keys)?;
+        if let Some(timestamp) = max_timestamp(batch, self.input_schema.timestamp_index) {
+            self.stored_filters
+                .insert(ctx, &self.filters, timestamp)
+                .await?;
+        }
+        Ok(output)
+    }
+}
+
+pub struct BloomFilterConstructor;
+
+impl OperatorConstructor for BloomFilterConstructor {
+    type ConfigT = api::BloomFilterOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        if !(config.false_positive_rate > 0.0 && config.false_positive_rate < 1.0) {
+            bail!("bloom filter false positive rate must be between 0 and 1");
+        }
+
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+        let value_index = input_schema.schema.index_of(&config.value_field)?;
+        let value_converter = Converter::new(vec![SortField::new(
+            input_schema.schema.field(value_index).data_type().clone(),
+        )])?;
+
+        let mut fields = input_schema.schema.fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            config.seen_field,
+            DataType::Boolean,
+            false,
+        )));
+
+        Ok(OperatorNode::from_operator(Box::new(BloomFilterFunc {
+            name: config.name,
+            key_converter: input_schema.converter(false)?,
+            value_converter,
+            output_schema: Arc::new(Schema::new(fields)),
+            stored_filters: KeyedValues::new("b", "bloom filters by key", &input_schema)?,
+            input_schema,
+            value_index,
+            expected_items: config.expected_items,
+            false_positive_rate: config.false_positive_rate,
+            filters: HashMap::new(),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for BloomFilterFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.stored_filters.table_config()].into_iter().collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        self.stored_filters
+            .load(ctx)
+            .await
+            .expect("should have bloom filter table");
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        let batch = self
+            .process_with_state(&batch, ctx)
+            .await
+            .expect("should be able to check bloom filters");
+        ctx.collect(batch).await;
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        self.stored_filters
+            .expire(ctx)
+            .await
+            .expect("should expire bloom filter table");
+        Some(watermark)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_no_false_negatives() {
+        let mut filter = BloomFilter::new(1000, 0.01);
+
+        for i in 0..1000u32 {
+            filter.check_and_insert(&i.to_le_bytes());
+        }
+
+        for i in 0..1000u32 {
+            assert!(filter.contains(&i.to_le_bytes()));
+            assert!(filter.check_and_insert(&i.to_le_bytes()));
+        }
+    }
+
+    #[test]
+    fn test_false_positive_rate() {
+        for rate in [0.1, 0.01] {
+            let mut filter = BloomFilter::new(5000, rate);
+            for i in 0..5000u32 {
+                filter.check_and_insert(&i.to_le_bytes());
+            }
+
+            let false_positives = (5000..105000u32)
+                .filter(|i| filter.contains(&i.to_le_bytes()))
+                .count();
+            let observed = false_positives as f64 / 100000.0;
+            assert!(
+                observed <= rate * 1.5,
+                "false positive rate {} exceeds bound for {}",
+                observed,
+                rate
+            );
+        }
+    }
+
+    #[test]
+    fn test_survives_checkpoint() {
+        let mut filter = BloomFilter::new(100, 0.01);
+        filter.check_and_insert(b"hello");
+
+        let encoded = bincode::encode_to_vec(&filter, bincode::config::standard()).unwrap();
+        let (mut restored, _): (BloomFilter, _) =
+            bincode::decode_from_slice(&encoded, bincode::config::standard()).unwrap();
+
+        assert_eq!(restored, filter);
+        assert!(restored.check_and_insert(b"hello"));
+    }
+}
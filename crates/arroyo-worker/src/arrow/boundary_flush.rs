@@ -0,0 +1,291 @@
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::{anyhow, bail, Result};
+use arrow::compute::{concat_batches, filter_record_batch};
+use arrow_array::cast::AsArray;
+use arrow_array::types::TimestampNanosecondType;
+use arrow_array::{BooleanArray, RecordBatch};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_state::timestamp_table_config;
+use arroyo_types::{from_nanos, to_nanos, CheckpointBarrier, Watermark};
+
+use crate::arrow::window_assigner::window_starts;
+
+/// Splits a batch into one batch per interval-aligned boundary, keyed by the start of the
+/// boundary.
+pub(crate) fn split_by_boundary(
+    batch: &RecordBatch,
+    timestamp_index: usize,
+    interval: Duration,
+) -> Result<Vec<(i64, RecordBatch)>> {
+    let starts = window_starts(
+        batch
+            .column(timestamp_index)
+            .as_primitive::<TimestampNanosecondType>(),
+        interval,
+    );
+    let distinct: BTreeSet<i64> = starts.values().iter().copied().collect();
+
+    distinct
+        .into_iter()
+        .map(|start| {
+            let mask: BooleanArray = starts.values().iter().map(|s| Some(*s == start)).collect();
+            Ok((start, filter_record_batch(batch, &mask)?))
+        })
+        .collect()
+}
+
+/// Tracks which boundaries have buffered data, and which of those can be flushed once the
+/// watermark passes their end.
+pub(crate) struct BoundaryTracker {
+    interval: i64,
+    pending: BTreeSet<i64>,
+}
+
+impl BoundaryTracker {
+    pub(crate) fn new(interval: Duration) -> Self {
+        Self {
+            interval: interval.as_nanos() as i64,
+            pending: BTreeSet::new(),
+        }
+    }
+
+    pub(crate) fn add(&mut self, start: i64) {
+        self.pending.insert(start);
+    }
+
+    /// Removes and returns the starts of the boundaries that the watermark has passed, in order.
+    pub(crate) fn ready(&mut self, watermark: i64) -> Vec<i64> {
+        let remaining = self.pending.split_off(&(watermark - self.interval + 1));
+        std::mem::replace(&mut self.pending, remaining)
+            .into_iter()
+            .collect()
+    }
+}
+
+/// Buffers records until the watermark crosses the end of the interval-aligned boundary (for
+/// example, the hour) they belong to, then emits all of that boundary's records together. This
+/// allows sinks downstream to write complete event-time partitions.
+pub struct BoundaryFlushFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    interval: Duration,
+    tracker: BoundaryTracker,
+}
+
+impl BoundaryFlushFunc {
+    fn table_watermark(ctx: &ArrowContext) -> Option<SystemTime> {
+        ctx.watermark().and_then(|watermark| match watermark {
+            Watermark::EventTime(watermark) => Some(watermark),
+            Watermark::Idle => None,
+        })
+    }
+}
+
+pub struct BoundaryFlushConstructor;
+
+impl OperatorConstructor for BoundaryFlushConstructor {
+    type ConfigT = api::BoundaryFlushOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        if config.interval_micros == 0 {
+            bail!("flush interval must be greater than 0");
+        }
+
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+        let interval = Duration::from_micros(config.interval_micros);
+
+        Ok(OperatorNode::from_operator(Box::new(BoundaryFlushFunc {
+            name: config.name,
+            input_schema,
+            interval,
+            tracker: BoundaryTracker::new(interval),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for BoundaryFlushFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        vec![(
+            "b".to_string(),
+            timestamp_table_config(
+                "b",
+                "records buffered by boundary",
+                self.interval,
+                false,
+                self.input_schema.clone(),
+            ),
+        )]
+        .into_iter()
+        .collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let watermark = Self::table_watermark(ctx);
+        let table = ctx
+            .table_manager
+            .get_expiring_time_key_table("b", watermark)
+            .await
+            .expect("should have boundary table");
+
+        let flushed_through = watermark.map(|w| to_nanos(w) as i64 - self.tracker.interval);
+        for (start, _) in table.all_batches_for_watermark(watermark) {
+            let start = to_nanos(*start) as i64;
+            if !flushed_through.is_some_and(|f| start <= f) {
+                self.tracker.add(start);
+            }
+        }
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        let watermark = Self::table_watermark(ctx);
+        let flushed_through = watermark.map(|w| to_nanos(w) as i64 - self.tracker.interval);
+
+        let table = ctx
+            .table_manager
+            .get_expiring_time_key_table("b", watermark)
+            .await
+            .expect("should have boundary table");
+
+        for (start, batch) in
+            split_by_boundary(&batch, self.input_schema.timestamp_index, self.interval)
+                .expect("should be able to split batch by boundary")
+        {
+            // boundaries that have already been flushed can't accept more data
+            if flushed_through.is_some_and(|f| start <= f) {
+                continue;
+            }
+            table.insert(from_nanos(start as u128), batch);
+            self.tracker.add(start);
+        }
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        let Watermark::EventTime(t) = watermark else {
+            return Some(watermark);
+        };
+
+        let ready = self.tracker.ready(to_nanos(t) as i64);
+        if ready.is_empty() {
+            return Some(watermark);
+        }
+
+        let table = ctx
+            .table_manager
+            .get_expiring_time_key_table("b", Some(t))
+            .await
+            .expect("should have boundary table");
+
+        let flushed: Vec<_> = ready
+            .into_iter()
+            .map(|start| table.expire_timestamp(from_nanos(start as u128)))
+            .filter(|batches| !batches.is_empty())
+            .collect();
+
+        for batches in flushed {
+            let batch = concat_batches(&batches[0].schema(), &batches)
+                .expect("should be able to concatenate boundary batches");
+            ctx.collect(batch).await;
+        }
+
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+        let watermark = Self::table_watermark(ctx);
+        ctx.table_manager
+            .get_expiring_time_key_table("b", watermark)
+            .await
+            .expect("should have boundary table")
+            .flush(watermark)
+            .await
+            .expect("should be able to flush boundary table");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Int64Array, TimestampNanosecondArray};
+    use arrow_schema::{DataType, Field, Schema, TimeUnit};
+
+    const HOUR: i64 = 3600 * 1_000_000_000;
+
+    #[test]
+    fn test_split_by_boundary() {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("x", DataType::Int64, false),
+                Field::new(
+                    "_timestamp",
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    false,
+                ),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2, 3, 4])),
+                Arc::new(TimestampNanosecondArray::from(vec![
+                    HOUR + 5,
+                    10,
+                    HOUR - 1,
+                    2 * HOUR + HOUR / 2,
+                ])),
+            ],
+        )
+        .unwrap();
+
+        let split: Vec<_> = split_by_boundary(&batch, 1, Duration::from_secs(3600))
+            .unwrap()
+            .into_iter()
+            .map(|(start, b)| {
+                (
+                    start,
+                    b.column(0)
+                        .as_primitive::<arrow_array::types::Int64Type>()
+                        .values()
+                        .to_vec(),
+                )
+            })
+            .collect();
+
+        assert_eq!(
+            split,
+            vec![(0, vec![2, 3]), (HOUR, vec![1]), (2 * HOUR, vec![4])]
+        );
+    }
+
+    #[test]
+    fn test_flushes_at_boundaries() {
+        let mut tracker = BoundaryTracker::new(Duration::from_secs(3600));
+        tracker.add(0);
+        tracker.add(HOUR);
+        tracker.add(3 * HOUR);
+
+        // the watermark hasn't yet reached the end of the first hour
+        assert!(tracker.ready(HOUR - 1).is_empty());
+        assert_eq!(tracker.ready(HOUR), vec![0]);
+        assert!(tracker.ready(2 * HOUR - 1).is_empty());
+
+        // crossing several boundaries at once flushes each of them
+        tracker.add(2 * HOUR);
+        assert_eq!(tracker.ready(4 * HOUR + 1), vec![HOUR, 2 * HOUR, 3 * HOUR]);
+        assert!(tracker.ready(10 * HOUR).is_empty());
+    }
+}
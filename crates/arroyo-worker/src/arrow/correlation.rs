@@ -0,0 +1,318 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::compute::cast;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, TimestampNanosecondType};
+use arrow_array::{Array, Float64Array, RecordBatch, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::{Converter, TIMESTAMP_FIELD};
+use arroyo_types::{to_nanos, CheckpointBarrier, Watermark};
+
+use crate::arrow::keyed_state::BufferedInput;
+use crate::arrow::keyed_windows::{window_timestamps, KeyedTumblingWindows};
+
+/// The running sums from which the Pearson correlation of the pairs seen so far is computed.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub(crate) struct CorrelationSums {
+    count: u64,
+    sum_x: f64,
+    sum_y: f64,
+    sum_xy: f64,
+    sum_x2: f64,
+    sum_y2: f64,
+}
+
+impl CorrelationSums {
+    fn add(&mut self, x: f64, y: f64) {
+        self.count += 1;
+        self.sum_x += x;
+        self.sum_y += y;
+        self.sum_xy += x * y;
+        self.sum_x2 += x * x;
+        self.sum_y2 += y * y;
+    }
+
+    /// The correlation coefficient, which is undefined (`None`) for fewer than two pairs or when
+    /// either column has no variance.
+    pub(crate) fn coefficient(&self) -> Option<f64> {
+        let n = self.count as f64;
+        let covariance = n * self.sum_xy - self.sum_x * self.sum_y;
+        let variance_x = n * self.sum_x2 - self.sum_x * self.sum_x;
+        let variance_y = n * self.sum_y2 - self.sum_y * self.sum_y;
+
+        if self.count < 2 || variance_x <= 0.0 || variance_y <= 0.0 {
+            return None;
+        }
+
+        // rounding can push the result just outside of [-1, 1]
+        Some((covariance / (variance_x * variance_y).sqrt()).clamp(-1.0, 1.0))
+    }
+}
+
+/// Accumulates correlation sums for each key in each tumbling window.
+pub(crate) struct CorrelationState {
+    windows: KeyedTumblingWindows<CorrelationSums>,
+}
+
+impl CorrelationState {
+    pub(crate) fn new(width: Duration) -> Self {
+        Self {
+            windows: KeyedTumblingWindows::new(width),
+        }
+    }
+
+    /// Adds a pair to its window, ignoring pairs for windows that have already been emitted.
+    pub(crate) fn add(&mut self, key: Vec<u8>, timestamp: i64, x: f64, y: f64) {
+        if let Some(entry) = self.windows.entry(key, timestamp) {
+            entry.or_default().add(x, y);
+        }
+    }
+
+    /// Removes every window that ends at or before the watermark, returning each as
+    /// `(window end, key, sums)`.
+    pub(crate) fn advance(&mut self, watermark: i64) -> Vec<(i64, Vec<u8>, CorrelationSums)> {
+        self.windows.advance(watermark)
+    }
+}
+
+/// Computes the Pearson correlation between two numeric columns for each key in each tumbling
+/// window, emitting the number of pairs and the coefficient when the window closes. Rows where
+/// either column is null are ignored, and the coefficient is null when it's undefined.
+pub struct CorrelationFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    x_index: usize,
+    y_index: usize,
+    key_converter: Converter,
+    input: BufferedInput,
+    state: CorrelationState,
+}
+
+impl CorrelationFunc {
+    fn process(&mut self, batch: &RecordBatch) -> Result<()> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        let keys = self
+            .key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())?;
+        let x = cast(batch.column(self.x_index), &DataType::Float64)?;
+        let x = x.as_primitive::<Float64Type>();
+        let y = cast(batch.column(self.y_index), &DataType::Float64)?;
+        let y = y.as_primitive::<Float64Type>();
+        let timestamps = batch
+            .column(self.input_schema.timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+
+        for i in 0..batch.num_rows() {
+            if x.is_valid(i) && y.is_valid(i) {
+                self.state.add(
+                    keys.row(i).as_ref().to_vec(),
+                    timestamps.value(i),
+                    x.value(i),
+                    y.value(i),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit(&mut self, watermark: i64) -> Result<RecordBatch> {
+        let windows = self.state.advance(watermark);
+
+        let mut columns = self
+            .key_converter
+            .convert_raw_rows(windows.iter().map(|(_, k, _)| k.as_slice()).collect())?;
+        columns.push(Arc::new(UInt64Array::from_iter_values(
+            windows.iter().map(|(_, _, sums)| sums.count),
+        )));
+        columns.push(Arc::new(Float64Array::from_iter(
+            windows.iter().map(|(_, _, sums)| sums.coefficient()),
+        )));
+        columns.push(Arc::new(window_timestamps(
+            windows.iter().map(|(end, _, _)| *end),
+        )));
+
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+}
+
+pub struct CorrelationConstructor;
+
+impl OperatorConstructor for CorrelationConstructor {
+    type ConfigT = api::CorrelationOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        if config.width_micros == 0 {
+            bail!("window width must be greater than 0");
+        }
+
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+        let x_index = input_schema.schema.index_of(&config.x_field)?;
+        let y_index = input_schema.schema.index_of(&config.y_field)?;
+
+        let mut fields: Vec<_> = input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| input_schema.schema.fields()[*i].clone())
+            .collect();
+        fields.push(Arc::new(Field::new("count", DataType::UInt64, false)));
+        fields.push(Arc::new(Field::new(
+            config.correlation_field,
+            DataType::Float64,
+            true,
+        )));
+        fields.push(Arc::new(Field::new(
+            TIMESTAMP_FIELD,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+
+        Ok(OperatorNode::from_operator(Box::new(CorrelationFunc {
+            name: config.name,
+            key_converter: input_schema.converter(false)?,
+            output_schema: Arc::new(Schema::new(fields)),
+            input: BufferedInput::new(
+                "i",
+                "correlation input",
+                Duration::from_micros(config.width_micros),
+                input_schema.clone(),
+            ),
+            input_schema,
+            x_index,
+            y_index,
+            state: CorrelationState::new(Duration::from_micros(config.width_micros)),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for CorrelationFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.input.table_config()].into_iter().collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let batches = self
+            .input
+            .restore(ctx)
+            .await
+            .expect("should be able to restore correlation input");
+        for batch in &batches {
+            self.process(batch)
+                .expect("should be able to update correlation sums");
+        }
+        if let Some(watermark) = ctx.last_present_watermark() {
+            self.state.advance(to_nanos(watermark) as i64);
+        }
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        self.process(&batch)
+            .expect("should be able to update correlation sums");
+        self.input
+            .insert(ctx, &batch)
+            .await
+            .expect("should be able to buffer correlation input");
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        if let Watermark::EventTime(t) = watermark {
+            let batch = self
+                .emit(to_nanos(t) as i64)
+                .expect("should be able to emit correlations");
+            if batch.num_rows() > 0 {
+                ctx.collect(batch).await;
+            }
+        }
+
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+        self.input
+            .flush(ctx)
+            .await
+            .expect("should be able to flush correlation input");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: i64 = 1_000_000_000;
+
+    fn correlation(pairs: &[(f64, f64)]) -> Option<f64> {
+        let mut sums = CorrelationSums::default();
+        for (x, y) in pairs {
+            sums.add(*x, *y);
+        }
+        sums.coefficient()
+    }
+
+    #[test]
+    fn test_matches_reference() {
+        // r = 6 / sqrt(10 * 6)
+        let r = correlation(&[(1.0, 2.0), (2.0, 4.0), (3.0, 5.0), (4.0, 4.0), (5.0, 5.0)]).unwrap();
+        assert!((r - 0.7745966692414834).abs() < 1e-12);
+
+        let r = correlation(&[(1.0, -2.0), (2.0, -4.0), (3.0, -6.0)]).unwrap();
+        assert!((r + 1.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_undefined_correlation() {
+        assert_eq!(correlation(&[(1.0, 1.0)]), None);
+        assert_eq!(correlation(&[(1.0, 3.0), (2.0, 3.0), (3.0, 3.0)]), None);
+    }
+
+    #[test]
+    fn test_emitted_per_window_and_key() {
+        let mut state = CorrelationState::new(Duration::from_secs(10));
+        for (t, x, y) in [(1, 1.0, 1.0), (2, 2.0, 2.0), (3, 3.0, 3.0), (11, 1.0, 5.0)] {
+            state.add(b"a".to_vec(), t * SECOND, x, y);
+        }
+        for (t, x, y) in [(4, 1.0, 3.0), (5, 2.0, 2.0), (6, 3.0, 1.0)] {
+            state.add(b"b".to_vec(), t * SECOND, x, y);
+        }
+
+        let results = state.advance(10 * SECOND);
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].1, b"a");
+        assert!((results[0].2.coefficient().unwrap() - 1.0).abs() < 1e-12);
+        assert_eq!(results[1].1, b"b");
+        assert!((results[1].2.coefficient().unwrap() + 1.0).abs() < 1e-12);
+
+        // late pairs for the emitted window are ignored
+        state.add(b"a".to_vec(), 5 * SECOND, 10.0, -10.0);
+        let results = state.advance(20 * SECOND);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].2.count, 1);
+    }
+}
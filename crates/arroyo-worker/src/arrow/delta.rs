@@ -0,0 +1,236 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use arrow::compute::cast;
+use arrow::row::Rows;
+use arrow_array::builder::Float64Builder;
+use arrow_array::{Array, Float64Array, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::Converter;
+use arroyo_types::Watermark;
+
+use crate::arrow::keyed_state::{max_timestamp, KeyedValues};
+
+/// Tracks the last observed value for each key and turns a stream of cumulative values
+/// (like Prometheus-style counters) into a stream of deltas. A key that sees no records for
+/// [`KEYED_STATE_TTL`](crate::arrow::keyed_state::KEYED_STATE_TTL) of event time starts over.
+#[derive(Default)]
+pub(crate) struct DeltaState {
+    previous: HashMap<Vec<u8>, f64>,
+}
+
+impl DeltaState {
+    /// Computes the delta for each row against the previous value for its key. The first value
+    /// seen for a key has a null delta, and a decrease is treated as a counter reset, in which
+    /// case the delta is the new value itself.
+    pub(crate) fn compute(&mut self, keys: &Rows, values: &Float64Array) -> Float64Array {
+        let mut deltas = Float64Builder::with_capacity(values.len());
+        for (i, value) in values.iter().enumerate() {
+            let Some(value) = value else {
+                deltas.append_null();
+                continue;
+            };
+
+            let key = keys.row(i).as_ref().to_vec();
+            match self.previous.insert(key, value) {
+                Some(previous) if value < previous => deltas.append_value(value),
+                Some(previous) => deltas.append_value(value - previous),
+                None => deltas.append_null(),
+            }
+        }
+        deltas.finish()
+    }
+}
+
+pub struct DeltaFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    value_index: usize,
+    key_converter: Converter,
+    previous: KeyedValues,
+    state: DeltaState,
+}
+
+impl DeltaFunc {
+    fn keys(&self, batch: &RecordBatch) -> Result<Rows> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        self.key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())
+    }
+
+    fn process(&mut self, batch: &RecordBatch, keys: &Rows) -> Result<RecordBatch> {
+        let values = cast(batch.column(self.value_index), &DataType::Float64)?;
+        let values = values
+            .as_any()
+            .downcast_ref::<Float64Array>()
+            .ok_or_else(|| anyhow!("failed to cast value column to f64"))?;
+
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(self.state.compute(keys, values)));
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+
+    /// Computes the batch's deltas against the previous values stored for its keys, and stores
+    /// the new ones.
+    async fn process_with_state(
+        &mut self,
+        batch: &RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> Result<RecordBatch> {
+        let keys = self.keys(batch)?;
+        self.state.previous = self.previous.get(ctx, &keys).await?;
+        let output = self.process(batch, &keys)?;
+        if let Some(timestamp) = max_timestamp(batch, self.input_schema.timestamp_index) {
+            self.previous
+                .insert(ctx, &self.state.previous, timestamp)
+                .await?;
+        }
+        Ok(output)
+    }
+}
+
+pub struct DeltaConstructor;
+
+impl OperatorConstructor for DeltaConstructor {
+    type ConfigT = api::DeltaOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+        let value_index = input_schema.schema.index_of(&config.value_field)?;
+
+        let mut fields = input_schema.schema.fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            config.delta_field,
+            DataType::Float64,
+            true,
+        )));
+
+        Ok(OperatorNode::from_operator(Box::new(DeltaFunc {
+            name: config.name,
+            key_converter: input_schema.converter(false)?,
+            output_schema: Arc::new(Schema::new(fields)),
+            previous: KeyedValues::new("d", "previous value by key", &input_schema)?,
+            input_schema,
+            value_index,
+            state: DeltaState::default(),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for DeltaFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.previous.table_config()].into_iter().collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        self.previous
+            .load(ctx)
+            .await
+            .expect("should have delta table");
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        let batch = self
+            .process_with_state(&batch, ctx)
+            .await
+            .expect("should be able to compute deltas");
+        ctx.collect(batch).await;
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        self.previous
+            .expire(ctx)
+            .await
+            .expect("should expire delta table");
+        Some(watermark)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow::row::{RowConverter, SortField};
+    use arrow_array::StringArray;
+
+    fn keys(keys: Vec<&str>) -> Rows {
+        RowConverter::new(vec![SortField::new(DataType::Utf8)])
+            .unwrap()
+            .convert_columns(&[Arc::new(StringArray::from(keys))])
+            .unwrap()
+    }
+
+    #[test]
+    fn test_increasing_counters() {
+        let mut state = DeltaState::default();
+
+        let deltas = state.compute(
+            &keys(vec!["a", "b", "a", "b", "a"]),
+            &Float64Array::from(vec![1.0, 10.0, 3.0, 15.0, 8.0]),
+        );
+
+        assert_eq!(
+            deltas,
+            Float64Array::from(vec![None, None, Some(2.0), Some(5.0), Some(5.0)])
+        );
+
+        // state carries across batches
+        let deltas = state.compute(&keys(vec!["b"]), &Float64Array::from(vec![16.0]));
+        assert_eq!(deltas, Float64Array::from(vec![Some(1.0)]));
+    }
+
+    #[test]
+    fn test_counter_reset() {
+        let mut state = DeltaState::default();
+
+        let deltas = state.compute(
+            &keys(vec!["a", "a", "a", "a"]),
+            &Float64Array::from(vec![100.0, 120.0, 4.0, 10.0]),
+        );
+
+        assert_eq!(
+            deltas,
+            Float64Array::from(vec![None, Some(20.0), Some(4.0), Some(6.0)])
+        );
+    }
+
+    #[test]
+    fn test_first_record_per_key() {
+        let mut state = DeltaState::default();
+
+        let deltas = state.compute(
+            &keys(vec!["a", "b", "c"]),
+            &Float64Array::from(vec![Some(5.0), None, Some(7.0)]),
+        );
+
+        assert_eq!(deltas, Float64Array::from(vec![None::<f64>, None, None]));
+        assert_eq!(state.previous.len(), 2);
+
+        // a null value doesn't count as the first observation for its key
+        let deltas = state.compute(&keys(vec!["b"]), &Float64Array::from(vec![3.0]));
+        assert_eq!(deltas, Float64Array::from(vec![None::<f64>]));
+    }
+}
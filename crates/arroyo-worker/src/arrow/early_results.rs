@@ -0,0 +1,371 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::compute::cast;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, TimestampNanosecondType};
+use arrow_array::{Array, BooleanArray, Float64Array, RecordBatch, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::{Converter, TIMESTAMP_FIELD};
+use arroyo_types::{to_nanos, CheckpointBarrier, Watermark};
+
+use crate::arrow::keyed_state::BufferedInput;
+use crate::arrow::keyed_windows::{window_timestamps, KeyedTumblingWindows};
+
+/// The running aggregates of the values for a key in a window.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Aggregates {
+    count: u64,
+    sum: f64,
+    min: f64,
+    max: f64,
+}
+
+impl Aggregates {
+    fn new(value: f64) -> Self {
+        Self {
+            count: 1,
+            sum: value,
+            min: value,
+            max: value,
+        }
+    }
+
+    fn add(&mut self, value: f64) {
+        self.count += 1;
+        self.sum += value;
+        self.min = self.min.min(value);
+        self.max = self.max.max(value);
+    }
+}
+
+/// Aggregates values for each key in each tumbling window.
+pub(crate) struct EarlyResultsState {
+    windows: KeyedTumblingWindows<Aggregates>,
+}
+
+impl EarlyResultsState {
+    pub(crate) fn new(width: Duration) -> Self {
+        Self {
+            windows: KeyedTumblingWindows::new(width),
+        }
+    }
+
+    /// Adds a value to its window, ignoring values for windows that have already been emitted.
+    pub(crate) fn add(&mut self, key: Vec<u8>, timestamp: i64, value: f64) {
+        if let Some(entry) = self.windows.entry(key, timestamp) {
+            entry
+                .and_modify(|aggregates| aggregates.add(value))
+                .or_insert_with(|| Aggregates::new(value));
+        }
+    }
+
+    /// Removes and returns every window that ends at or before the watermark, as
+    /// `(window end, key, aggregates)`.
+    pub(crate) fn advance(&mut self, watermark: i64) -> Vec<(i64, Vec<u8>, Aggregates)> {
+        self.windows.advance(watermark)
+    }
+
+    /// Returns the current aggregates of every open window without removing them, as
+    /// `(window end, key, aggregates)`, in chunks of at most `chunk_size` entries. Each chunk is
+    /// only built when it's reached, so a large key space isn't copied all at once.
+    pub(crate) fn snapshot(
+        &self,
+        chunk_size: usize,
+    ) -> impl Iterator<Item = Vec<(i64, Vec<u8>, Aggregates)>> + '_ {
+        let mut windows = self.windows.iter().peekable();
+        std::iter::from_fn(move || {
+            windows.peek()?;
+            Some(
+                windows
+                    .by_ref()
+                    .take(chunk_size)
+                    .map(|(end, key, aggregates)| (end, key.to_vec(), *aggregates))
+                    .collect(),
+            )
+        })
+    }
+}
+
+/// Aggregates a column for each key over tumbling windows. In addition to the final result
+/// emitted when the watermark closes a window, a snapshot of every open window's current
+/// aggregates is emitted each `tick_interval`, providing early results for live dashboards. The
+/// `final` column distinguishes the two.
+pub struct EarlyResultsFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    value_index: usize,
+    tick_interval: Duration,
+    chunk_size: usize,
+    key_converter: Converter,
+    input: BufferedInput,
+    state: EarlyResultsState,
+}
+
+impl EarlyResultsFunc {
+    fn process(&mut self, batch: &RecordBatch) -> Result<()> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        let keys = self
+            .key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())?;
+        let values = cast(batch.column(self.value_index), &DataType::Float64)?;
+        let values = values.as_primitive::<Float64Type>();
+        let timestamps = batch
+            .column(self.input_schema.timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+
+        for i in 0..batch.num_rows() {
+            if !values.is_null(i) {
+                self.state.add(
+                    keys.row(i).as_ref().to_vec(),
+                    timestamps.value(i),
+                    values.value(i),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn to_batch(
+        &self,
+        results: &[(i64, Vec<u8>, Aggregates)],
+        is_final: bool,
+    ) -> Result<RecordBatch> {
+        let mut columns = self
+            .key_converter
+            .convert_raw_rows(results.iter().map(|(_, k, _)| k.as_slice()).collect())?;
+        columns.push(Arc::new(UInt64Array::from_iter_values(
+            results.iter().map(|(_, _, a)| a.count),
+        )));
+        columns.push(Arc::new(Float64Array::from_iter_values(
+            results.iter().map(|(_, _, a)| a.sum),
+        )));
+        columns.push(Arc::new(Float64Array::from_iter_values(
+            results.iter().map(|(_, _, a)| a.min),
+        )));
+        columns.push(Arc::new(Float64Array::from_iter_values(
+            results.iter().map(|(_, _, a)| a.max),
+        )));
+        columns.push(Arc::new(BooleanArray::from(vec![is_final; results.len()])));
+        columns.push(Arc::new(window_timestamps(
+            results.iter().map(|(end, _, _)| *end),
+        )));
+
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+}
+
+pub struct EarlyResultsConstructor;
+
+impl OperatorConstructor for EarlyResultsConstructor {
+    type ConfigT = api::EarlyResultsOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        if config.width_micros == 0 {
+            bail!("window width must be greater than 0");
+        }
+        if config.tick_interval_micros == 0 {
+            bail!("tick interval must be greater than 0");
+        }
+        if config.chunk_size == 0 {
+            bail!("chunk size must be greater than 0");
+        }
+
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+        let value_index = input_schema.schema.index_of(&config.value_field)?;
+
+        let mut fields: Vec<_> = input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| input_schema.schema.fields()[*i].clone())
+            .collect();
+        fields.push(Arc::new(Field::new("count", DataType::UInt64, false)));
+        for name in ["sum", "min", "max"] {
+            fields.push(Arc::new(Field::new(name, DataType::Float64, false)));
+        }
+        fields.push(Arc::new(Field::new("final", DataType::Boolean, false)));
+        fields.push(Arc::new(Field::new(
+            TIMESTAMP_FIELD,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+
+        Ok(OperatorNode::from_operator(Box::new(EarlyResultsFunc {
+            name: config.name,
+            key_converter: input_schema.converter(false)?,
+            output_schema: Arc::new(Schema::new(fields)),
+            input: BufferedInput::new(
+                "i",
+                "early results input",
+                Duration::from_micros(config.width_micros),
+                input_schema.clone(),
+            ),
+            input_schema,
+            value_index,
+            tick_interval: Duration::from_micros(config.tick_interval_micros),
+            chunk_size: config.chunk_size as usize,
+            state: EarlyResultsState::new(Duration::from_micros(config.width_micros)),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for EarlyResultsFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.input.table_config()].into_iter().collect()
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(self.tick_interval)
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let batches = self
+            .input
+            .restore(ctx)
+            .await
+            .expect("should be able to restore early results input");
+        for batch in &batches {
+            self.process(batch)
+                .expect("should be able to aggregate batch");
+        }
+        if let Some(watermark) = ctx.last_present_watermark() {
+            self.state.advance(to_nanos(watermark) as i64);
+        }
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        self.process(&batch)
+            .expect("should be able to aggregate batch");
+        self.input
+            .insert(ctx, &batch)
+            .await
+            .expect("should be able to buffer early results input");
+    }
+
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) {
+        // large key spaces are emitted in several batches rather than one huge one
+        for chunk in self.state.snapshot(self.chunk_size) {
+            let batch = self
+                .to_batch(&chunk, false)
+                .expect("should be able to emit early results");
+            ctx.collect(batch).await;
+        }
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        if let Watermark::EventTime(t) = watermark {
+            let results = self.state.advance(to_nanos(t) as i64);
+            for chunk in results.chunks(self.chunk_size) {
+                let batch = self
+                    .to_batch(chunk, true)
+                    .expect("should be able to emit final results");
+                ctx.collect(batch).await;
+            }
+        }
+
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+        self.input
+            .flush(ctx)
+            .await
+            .expect("should be able to flush early results input");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: i64 = 1_000_000_000;
+
+    fn counts(entries: &[(i64, Vec<u8>, Aggregates)]) -> Vec<(&str, u64, f64)> {
+        entries
+            .iter()
+            .map(|(_, key, a)| (std::str::from_utf8(key).unwrap(), a.count, a.sum))
+            .collect()
+    }
+
+    #[test]
+    fn test_snapshots_reflect_current_state() {
+        let mut state = EarlyResultsState::new(Duration::from_secs(60));
+        state.add(b"a".to_vec(), SECOND, 1.0);
+        state.add(b"b".to_vec(), 2 * SECOND, 5.0);
+
+        let snapshot: Vec<_> = state.snapshot(100).collect();
+        assert_eq!(snapshot.len(), 1);
+        assert_eq!(counts(&snapshot[0]), vec![("a", 1, 1.0), ("b", 1, 5.0)]);
+
+        // snapshots don't consume the state, and later ones include everything since
+        state.add(b"a".to_vec(), 3 * SECOND, 2.0);
+        state.add(b"a".to_vec(), 4 * SECOND, -1.0);
+        let snapshot: Vec<_> = state.snapshot(100).collect();
+        assert_eq!(counts(&snapshot[0]), vec![("a", 3, 2.0), ("b", 1, 5.0)]);
+        assert_eq!((snapshot[0][0].2.min, snapshot[0][0].2.max), (-1.0, 2.0));
+
+        // the final results match the last snapshot, after which the window is gone
+        let results = state.advance(60 * SECOND);
+        assert_eq!(counts(&results), vec![("a", 3, 2.0), ("b", 1, 5.0)]);
+        assert_eq!(results[0].0, 60 * SECOND);
+        assert!(state.snapshot(100).next().is_none());
+    }
+
+    #[test]
+    fn test_snapshot_chunks() {
+        let mut state = EarlyResultsState::new(Duration::from_secs(60));
+        for i in 0..10u8 {
+            state.add(vec![i], SECOND, i as f64);
+        }
+
+        let chunks: Vec<_> = state.snapshot(4).collect();
+        assert_eq!(
+            chunks.iter().map(|c| c.len()).collect::<Vec<_>>(),
+            vec![4, 4, 2]
+        );
+        assert_eq!(chunks.concat().len(), 10);
+    }
+
+    #[test]
+    fn test_open_windows_kept_across_watermarks() {
+        let mut state = EarlyResultsState::new(Duration::from_secs(10));
+        state.add(b"a".to_vec(), SECOND, 1.0);
+        state.add(b"a".to_vec(), 12 * SECOND, 1.0);
+
+        assert_eq!(state.advance(10 * SECOND).len(), 1);
+        let snapshot: Vec<_> = state.snapshot(100).collect();
+        assert_eq!(snapshot[0].len(), 1);
+        assert_eq!(snapshot[0][0].0, 20 * SECOND);
+
+        // late values for emitted windows are ignored
+        state.add(b"a".to_vec(), 2 * SECOND, 1.0);
+        assert_eq!(state.snapshot(100).next().unwrap().len(), 1);
+    }
+}
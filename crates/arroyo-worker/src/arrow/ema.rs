@@ -0,0 +1,231 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::compute::cast;
+use arrow::row::Rows;
+use arrow_array::cast::AsArray;
+use arrow_array::types::Float64Type;
+use arrow_array::{Array, Float64Array, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::Converter;
+use arroyo_types::Watermark;
+
+use crate::arrow::keyed_state::{max_timestamp, KeyedValues};
+
+/// The current exponential moving average for each key.
+pub(crate) struct EmaState {
+    alpha: f64,
+    averages: HashMap<Vec<u8>, f64>,
+}
+
+impl EmaState {
+    pub(crate) fn new(alpha: f64) -> Self {
+        Self {
+            alpha,
+            averages: HashMap::new(),
+        }
+    }
+
+    /// Folds a value into the key's average and returns the updated average. The first value seen
+    /// for a key becomes its initial average.
+    pub(crate) fn update(&mut self, key: Vec<u8>, value: f64) -> f64 {
+        let alpha = self.alpha;
+        *self
+            .averages
+            .entry(key)
+            .and_modify(|ema| *ema = alpha * value + (1.0 - alpha) * *ema)
+            .or_insert(value)
+    }
+}
+
+/// Computes an exponential moving average of a column for each key, updated with every record in
+/// arrival order and appended to the record as a new column. Records with a null value don't
+/// affect the average, and are emitted with a null average. A key that sees no records for
+/// [`KEYED_STATE_TTL`](crate::arrow::keyed_state::KEYED_STATE_TTL) of event time starts over.
+pub struct EmaFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    value_index: usize,
+    key_converter: Converter,
+    stored_averages: KeyedValues,
+    state: EmaState,
+}
+
+impl EmaFunc {
+    fn keys(&self, batch: &RecordBatch) -> Result<Rows> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        self.key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())
+    }
+
+    fn process(&mut self, batch: &RecordBatch, keys: &Rows) -> Result<RecordBatch> {
+        let values = cast(batch.column(self.value_index), &DataType::Float64)?;
+        let values = values.as_primitive::<Float64Type>();
+
+        let averages: Float64Array = (0..batch.num_rows())
+            .map(|i| {
+                (!values.is_null(i)).then(|| {
+                    self.state
+                        .update(keys.row(i).as_ref().to_vec(), values.value(i))
+                })
+            })
+            .collect();
+
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(averages));
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+
+    /// Folds the batch into the averages stored for its keys, and stores the updated averages.
+    async fn process_with_state(
+        &mut self,
+        batch: &RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> Result<RecordBatch> {
+        let keys = self.keys(batch)?;
+        self.state.averages = self.stored_averages.get(ctx, &keys).await?;
+        let output = self.process(batch, &keys)?;
+        if let Some(timestamp) = max_timestamp(batch, self.input_schema.timestamp_index) {
+            self.stored_averages
+                .insert(ctx, &self.state.averages, timestamp)
+                .await?;
+        }
+        Ok(output)
+    }
+}
+
+pub struct EmaConstructor;
+
+impl OperatorConstructor for EmaConstructor {
+    type ConfigT = api::EmaOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        if !(config.alpha > 0.0 && config.alpha <= 1.0) {
+            bail!("EMA smoothing factor must be in (0, 1]");
+        }
+
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+        let value_index = input_schema.schema.index_of(&config.value_field)?;
+
+        let mut fields = input_schema.schema.fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            config.ema_field,
+            DataType::Float64,
+            true,
+        )));
+
+        Ok(OperatorNode::from_operator(Box::new(EmaFunc {
+            name: config.name,
+            key_converter: input_schema.converter(false)?,
+            output_schema: Arc::new(Schema::new(fields)),
+            stored_averages: KeyedValues::new(
+                "a",
+                "exponential moving averages by key",
+                &input_schema,
+            )?,
+            input_schema,
+            value_index,
+            state: EmaState::new(config.alpha),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for EmaFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.stored_averages.table_config()].into_iter().collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        self.stored_averages
+            .load(ctx)
+            .await
+            .expect("should have EMA table");
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        let batch = self
+            .process_with_state(&batch, ctx)
+            .await
+            .expect("should be able to compute moving averages");
+        ctx.collect(batch).await;
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        self.stored_averages
+            .expire(ctx)
+            .await
+            .expect("should expire EMA table");
+        Some(watermark)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_initializes_to_first_value() {
+        let mut state = EmaState::new(0.3);
+        assert_eq!(state.update(b"a".to_vec(), 10.0), 10.0);
+        assert_eq!(state.update(b"b".to_vec(), -4.0), -4.0);
+        assert_eq!(state.update(b"a".to_vec(), 20.0), 13.0);
+    }
+
+    #[test]
+    fn test_matches_reference() {
+        let alpha = 0.2;
+        let records: Vec<_> = (0..50)
+            .map(|i| (if i % 3 == 0 { "a" } else { "b" }, (i * i % 23) as f64))
+            .collect();
+
+        let mut state = EmaState::new(alpha);
+        for key in ["a", "b"] {
+            let values: Vec<_> = records
+                .iter()
+                .filter(|(k, _)| *k == key)
+                .map(|(_, v)| *v)
+                .collect();
+
+            // the standard recursive definition, seeded with the first value
+            let mut expected = values[0];
+            for (i, v) in values.iter().enumerate() {
+                if i > 0 {
+                    expected = alpha * v + (1.0 - alpha) * expected;
+                }
+                let actual = state.update(key.as_bytes().to_vec(), *v);
+                assert!(
+                    (actual - expected).abs() < 1e-9,
+                    "{} != {}",
+                    actual,
+                    expected
+                );
+            }
+        }
+
+        assert_eq!(state.averages.len(), 2);
+    }
+}
@@ -0,0 +1,314 @@
+use std::collections::btree_map::Entry;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::row::SortField;
+use arrow_array::cast::AsArray;
+use arrow_array::types::TimestampNanosecondType;
+use arrow_array::RecordBatch;
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::{Converter, TIMESTAMP_FIELD};
+use arroyo_types::{to_nanos, CheckpointBarrier, Watermark};
+
+use crate::arrow::keyed_state::BufferedInput;
+use crate::arrow::keyed_windows::{window_timestamps, KeyedTumblingWindows};
+
+/// The earliest and latest values (by event time) seen for a key in a window, along with their
+/// timestamps.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct FirstLast {
+    first: (i64, Vec<u8>),
+    last: (i64, Vec<u8>),
+}
+
+/// Tracks the first and last values for each key in each tumbling window.
+pub(crate) struct FirstLastState {
+    windows: KeyedTumblingWindows<FirstLast>,
+}
+
+impl FirstLastState {
+    pub(crate) fn new(width: Duration) -> Self {
+        Self {
+            windows: KeyedTumblingWindows::new(width),
+        }
+    }
+
+    /// Adds a value to its window, ignoring values for windows that have already been emitted.
+    /// Ties on event time are broken by arrival order: the first value to arrive at the earliest
+    /// timestamp is kept as the first, and the last value to arrive at the latest timestamp
+    /// becomes the last.
+    pub(crate) fn add(&mut self, key: Vec<u8>, timestamp: i64, value: Vec<u8>) {
+        match self.windows.entry(key, timestamp) {
+            Some(Entry::Occupied(mut entry)) => {
+                let first_last = entry.get_mut();
+                if timestamp < first_last.first.0 {
+                    first_last.first = (timestamp, value.clone());
+                }
+                if timestamp >= first_last.last.0 {
+                    first_last.last = (timestamp, value);
+                }
+            }
+            Some(Entry::Vacant(entry)) => {
+                entry.insert(FirstLast {
+                    first: (timestamp, value.clone()),
+                    last: (timestamp, value),
+                });
+            }
+            None => {}
+        }
+    }
+
+    /// Removes and returns every window that ends at or before the watermark, as
+    /// `(window end, key, first and last)`.
+    pub(crate) fn advance(&mut self, watermark: i64) -> Vec<(i64, Vec<u8>, FirstLast)> {
+        self.windows.advance(watermark)
+    }
+}
+
+pub struct FirstLastFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    value_index: usize,
+    key_converter: Converter,
+    value_converter: Converter,
+    input: BufferedInput,
+    state: FirstLastState,
+}
+
+impl FirstLastFunc {
+    fn process(&mut self, batch: &RecordBatch) -> Result<()> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        let keys = self
+            .key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())?;
+        let value_column = batch.column(self.value_index).clone();
+        let values = self
+            .value_converter
+            .convert_all_columns(&[value_column], batch.num_rows())?;
+
+        let timestamps = batch
+            .column(self.input_schema.timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+
+        for i in 0..batch.num_rows() {
+            self.state.add(
+                keys.row(i).as_ref().to_vec(),
+                timestamps.value(i),
+                values.row(i).as_ref().to_vec(),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn emit(&mut self, watermark: i64) -> Result<RecordBatch> {
+        let results = self.state.advance(watermark);
+
+        let mut columns = self
+            .key_converter
+            .convert_raw_rows(results.iter().map(|(_, k, _)| k.as_slice()).collect())?;
+        columns.extend(
+            self.value_converter.convert_raw_rows(
+                results
+                    .iter()
+                    .map(|(_, _, v)| v.first.1.as_slice())
+                    .collect(),
+            )?,
+        );
+        columns.extend(
+            self.value_converter.convert_raw_rows(
+                results
+                    .iter()
+                    .map(|(_, _, v)| v.last.1.as_slice())
+                    .collect(),
+            )?,
+        );
+        columns.push(Arc::new(window_timestamps(
+            results.iter().map(|(end, _, _)| *end),
+        )));
+
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+}
+
+pub struct FirstLastConstructor;
+
+impl OperatorConstructor for FirstLastConstructor {
+    type ConfigT = api::FirstLastOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        if config.width_micros == 0 {
+            bail!("window width must be greater than 0");
+        }
+
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+        let value_index = input_schema.schema.index_of(&config.value_field)?;
+        let value_field = input_schema.schema.field(value_index);
+        let value_converter =
+            Converter::new(vec![SortField::new(value_field.data_type().clone())])?;
+
+        let mut fields: Vec<_> = input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| input_schema.schema.fields()[*i].clone())
+            .collect();
+        fields.push(Arc::new(value_field.clone().with_name(config.first_field)));
+        fields.push(Arc::new(value_field.clone().with_name(config.last_field)));
+        fields.push(Arc::new(Field::new(
+            TIMESTAMP_FIELD,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+
+        Ok(OperatorNode::from_operator(Box::new(FirstLastFunc {
+            name: config.name,
+            key_converter: input_schema.converter(false)?,
+            value_converter,
+            output_schema: Arc::new(Schema::new(fields)),
+            input: BufferedInput::new(
+                "i",
+                "first/last input",
+                Duration::from_micros(config.width_micros),
+                input_schema.clone(),
+            ),
+            input_schema,
+            value_index,
+            state: FirstLastState::new(Duration::from_micros(config.width_micros)),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for FirstLastFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.input.table_config()].into_iter().collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let batches = self
+            .input
+            .restore(ctx)
+            .await
+            .expect("should be able to restore first/last input");
+        for batch in &batches {
+            self.process(batch)
+                .expect("should be able to compute first/last values");
+        }
+        if let Some(watermark) = ctx.last_present_watermark() {
+            self.state.advance(to_nanos(watermark) as i64);
+        }
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        self.process(&batch)
+            .expect("should be able to compute first/last values");
+        self.input
+            .insert(ctx, &batch)
+            .await
+            .expect("should be able to buffer first/last input");
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        if let Watermark::EventTime(t) = watermark {
+            let batch = self
+                .emit(to_nanos(t) as i64)
+                .expect("should be able to emit first/last values");
+            ctx.collect(batch).await;
+        }
+
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+        self.input
+            .flush(ctx)
+            .await
+            .expect("should be able to flush first/last input");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: i64 = 1_000_000_000;
+
+    fn values(first_last: &FirstLast) -> (&str, &str) {
+        (
+            std::str::from_utf8(&first_last.first.1).unwrap(),
+            std::str::from_utf8(&first_last.last.1).unwrap(),
+        )
+    }
+
+    #[test]
+    fn test_out_of_order() {
+        let mut state = FirstLastState::new(Duration::from_secs(10));
+
+        for (key, t, v) in [
+            ("a", 5, "x"),
+            ("a", 2, "y"),
+            ("b", 4, "p"),
+            ("a", 8, "z"),
+            // ties keep the earlier-arriving first, and the later-arriving last
+            ("a", 2, "w"),
+            ("a", 8, "v"),
+            ("a", 12, "next"),
+            ("b", 1, "q"),
+        ] {
+            state.add(key.as_bytes().to_vec(), t * SECOND, v.as_bytes().to_vec());
+        }
+
+        let results = state.advance(10 * SECOND);
+        assert_eq!(results.len(), 2);
+        assert_eq!(
+            (results[0].0, results[0].1.as_slice()),
+            (10 * SECOND, b"a".as_slice())
+        );
+        assert_eq!(values(&results[0].2), ("y", "v"));
+        assert_eq!(results[1].1, b"b".to_vec());
+        assert_eq!(values(&results[1].2), ("q", "p"));
+
+        // the second window hasn't closed yet
+        assert_eq!(state.windows.iter().count(), 1);
+
+        let results = state.advance(20 * SECOND);
+        assert_eq!(results.len(), 1);
+        assert_eq!(values(&results[0].2), ("next", "next"));
+    }
+
+    #[test]
+    fn test_late_data_ignored() {
+        let mut state = FirstLastState::new(Duration::from_secs(10));
+        state.add(b"a".to_vec(), SECOND, b"x".to_vec());
+        assert_eq!(state.advance(10 * SECOND).len(), 1);
+
+        state.add(b"a".to_vec(), 0, b"late".to_vec());
+        assert_eq!(state.windows.iter().count(), 0);
+        assert!(state.advance(20 * SECOND).is_empty());
+    }
+}
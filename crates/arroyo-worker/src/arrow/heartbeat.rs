@@ -0,0 +1,126 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use anyhow::Result;
+use arrow_array::RecordBatch;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::grpc::api;
+use arroyo_types::{ArrowMessage, SignalMessage, Watermark};
+use tracing::debug;
+
+/// Decides when a quiet input should produce a heartbeat.
+pub(crate) struct HeartbeatState {
+    idle_threshold: Duration,
+    last_data: Instant,
+}
+
+impl HeartbeatState {
+    pub(crate) fn new(idle_threshold: Duration, now: Instant) -> Self {
+        Self {
+            idle_threshold,
+            last_data: now,
+        }
+    }
+
+    pub(crate) fn record_data(&mut self, now: Instant) {
+        self.last_data = now;
+    }
+
+    pub(crate) fn should_emit(&self, now: Instant) -> bool {
+        now.saturating_duration_since(self.last_data) >= self.idle_threshold
+    }
+}
+
+pub struct HeartbeatFunc {
+    name: String,
+    interval: Duration,
+    state: HeartbeatState,
+}
+
+pub struct HeartbeatConstructor;
+
+impl OperatorConstructor for HeartbeatConstructor {
+    type ConfigT = api::HeartbeatOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        Ok(OperatorNode::from_operator(Box::new(HeartbeatFunc {
+            name: config.name,
+            interval: Duration::from_micros(config.interval_micros),
+            state: HeartbeatState::new(
+                Duration::from_micros(config.idle_threshold_micros),
+                Instant::now(),
+            ),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for HeartbeatFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(self.interval)
+    }
+
+    async fn on_start(&mut self, _: &mut ArrowContext) {
+        self.state.record_data(Instant::now());
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        self.state.record_data(Instant::now());
+        ctx.collect(batch).await;
+    }
+
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) {
+        if !self.state.should_emit(Instant::now()) {
+            return;
+        }
+
+        debug!(
+            "[{}] no data received in {:?}, emitting heartbeat",
+            ctx.task_info.task_index, self.state.idle_threshold
+        );
+
+        // empty batches are dropped by the collector's repartitioning, so heartbeats are sent
+        // directly to every downstream partition
+        let schema = ctx.out_schema.as_ref().unwrap().schema.clone();
+        ctx.broadcast(ArrowMessage::Data(RecordBatch::new_empty(schema)))
+            .await;
+
+        if let Some(watermark) = ctx.last_present_watermark() {
+            ctx.broadcast(ArrowMessage::Signal(SignalMessage::Watermark(
+                Watermark::EventTime(watermark),
+            )))
+            .await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_heartbeats_while_idle() {
+        let start = Instant::now();
+        let mut state = HeartbeatState::new(Duration::from_secs(5), start);
+
+        // ticks every second
+        let emitted: Vec<_> = (0..10)
+            .map(|i| state.should_emit(start + Duration::from_secs(i)))
+            .collect();
+        assert_eq!(
+            emitted,
+            vec![false, false, false, false, false, true, true, true, true, true]
+        );
+
+        // data resumes
+        state.record_data(start + Duration::from_secs(10));
+        assert!(!state.should_emit(start + Duration::from_secs(11)));
+        assert!(!state.should_emit(start + Duration::from_secs(14)));
+        assert!(state.should_emit(start + Duration::from_secs(15)));
+    }
+}
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::compute::cast;
+use arrow_array::builder::{ListBuilder, UInt64Builder};
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, TimestampNanosecondType};
+use arrow_array::{Array, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::{Converter, TIMESTAMP_FIELD};
+use arroyo_types::{to_nanos, CheckpointBarrier, Watermark};
+
+use crate::arrow::keyed_state::BufferedInput;
+use crate::arrow::keyed_windows::{window_timestamps, KeyedTumblingWindows};
+
+/// Bucket counts for each key in each tumbling window. With `n` boundaries there are `n + 1`
+/// buckets: values below the first boundary, values in each `[boundary, next boundary)` range,
+/// and values at or above the last boundary.
+pub(crate) struct HistogramState {
+    boundaries: Vec<f64>,
+    windows: KeyedTumblingWindows<Vec<u64>>,
+}
+
+impl HistogramState {
+    pub(crate) fn new(width: Duration, boundaries: Vec<f64>) -> Self {
+        Self {
+            boundaries,
+            windows: KeyedTumblingWindows::new(width),
+        }
+    }
+
+    /// Counts the value in its bucket, ignoring NaNs and values for windows that have already
+    /// been emitted.
+    pub(crate) fn add(&mut self, key: Vec<u8>, timestamp: i64, value: f64) {
+        if value.is_nan() {
+            return;
+        }
+        let Some(entry) = self.windows.entry(key, timestamp) else {
+            return;
+        };
+
+        let bucket = self.boundaries.partition_point(|b| *b <= value);
+        let buckets = self.boundaries.len() + 1;
+        entry.or_insert_with(|| vec![0; buckets])[bucket] += 1;
+    }
+
+    /// Removes and returns every window that ends at or before the watermark, as
+    /// `(window end, key, bucket counts)`.
+    pub(crate) fn advance(&mut self, watermark: i64) -> Vec<(i64, Vec<u8>, Vec<u64>)> {
+        self.windows.advance(watermark)
+    }
+}
+
+pub struct HistogramFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    value_index: usize,
+    key_converter: Converter,
+    input: BufferedInput,
+    state: HistogramState,
+}
+
+impl HistogramFunc {
+    fn process(&mut self, batch: &RecordBatch) -> Result<()> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        let keys = self
+            .key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())?;
+
+        let timestamps = batch
+            .column(self.input_schema.timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+        let values = cast(batch.column(self.value_index), &DataType::Float64)?;
+        let values = values.as_primitive::<Float64Type>();
+
+        for i in 0..batch.num_rows() {
+            if values.is_null(i) {
+                continue;
+            }
+            self.state.add(
+                keys.row(i).as_ref().to_vec(),
+                timestamps.value(i),
+                values.value(i),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn emit(&mut self, watermark: i64) -> Result<RecordBatch> {
+        let results = self.state.advance(watermark);
+
+        let mut columns = self
+            .key_converter
+            .convert_raw_rows(results.iter().map(|(_, k, _)| k.as_slice()).collect())?;
+
+        let mut counts = ListBuilder::new(UInt64Builder::new());
+        for (_, _, buckets) in &results {
+            counts.values().append_slice(buckets);
+            counts.append(true);
+        }
+        columns.push(Arc::new(counts.finish()));
+
+        columns.push(Arc::new(window_timestamps(
+            results.iter().map(|(end, _, _)| *end),
+        )));
+
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+}
+
+pub struct HistogramConstructor;
+
+impl OperatorConstructor for HistogramConstructor {
+    type ConfigT = api::HistogramOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        if config.width_micros == 0 {
+            bail!("window width must be greater than 0");
+        }
+        if config.boundaries.is_empty()
+            || !config.boundaries.windows(2).all(|b| b[0] < b[1])
+            || config.boundaries.iter().any(|b| b.is_nan())
+        {
+            bail!("histogram bucket boundaries must be non-empty and strictly increasing");
+        }
+
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+        let value_index = input_schema.schema.index_of(&config.value_field)?;
+
+        let mut fields: Vec<_> = input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| input_schema.schema.fields()[*i].clone())
+            .collect();
+        fields.push(Arc::new(Field::new(
+            config.counts_field,
+            DataType::List(Arc::new(Field::new("item", DataType::UInt64, true))),
+            false,
+        )));
+        fields.push(Arc::new(Field::new(
+            TIMESTAMP_FIELD,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+
+        Ok(OperatorNode::from_operator(Box::new(HistogramFunc {
+            name: config.name,
+            key_converter: input_schema.converter(false)?,
+            output_schema: Arc::new(Schema::new(fields)),
+            input: BufferedInput::new(
+                "i",
+                "histogram input",
+                Duration::from_micros(config.width_micros),
+                input_schema.clone(),
+            ),
+            input_schema,
+            value_index,
+            state: HistogramState::new(
+                Duration::from_micros(config.width_micros),
+                config.boundaries,
+            ),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for HistogramFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.input.table_config()].into_iter().collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let batches = self
+            .input
+            .restore(ctx)
+            .await
+            .expect("should be able to restore histogram input");
+        for batch in &batches {
+            self.process(batch)
+                .expect("should be able to compute histograms");
+        }
+        if let Some(watermark) = ctx.last_present_watermark() {
+            self.state.advance(to_nanos(watermark) as i64);
+        }
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        self.process(&batch)
+            .expect("should be able to compute histograms");
+        self.input
+            .insert(ctx, &batch)
+            .await
+            .expect("should be able to buffer histogram input");
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        if let Watermark::EventTime(t) = watermark {
+            let batch = self
+                .emit(to_nanos(t) as i64)
+                .expect("should be able to emit histograms");
+            ctx.collect(batch).await;
+        }
+
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+        self.input
+            .flush(ctx)
+            .await
+            .expect("should be able to flush histogram input");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: i64 = 1_000_000_000;
+
+    fn state() -> HistogramState {
+        HistogramState::new(Duration::from_secs(10), vec![10.0, 50.0, 100.0])
+    }
+
+    #[test]
+    fn test_bucket_counts() {
+        let mut state = state();
+
+        // 0..200 by 5s: 2 below 10, 8 in [10, 50), 10 in [50, 100), and 20 at or above 100
+        for (i, v) in (0..200).step_by(5).enumerate() {
+            state.add(b"a".to_vec(), (i as i64 % 10) * SECOND, v as f64);
+        }
+        state.add(b"b".to_vec(), SECOND, 50.0);
+        state.add(b"a".to_vec(), 12 * SECOND, 1.0);
+
+        let results = state.advance(10 * SECOND);
+        assert_eq!(
+            results,
+            vec![
+                (10 * SECOND, b"a".to_vec(), vec![2, 8, 10, 20]),
+                (10 * SECOND, b"b".to_vec(), vec![0, 0, 1, 0]),
+            ]
+        );
+
+        assert_eq!(
+            state.advance(20 * SECOND),
+            vec![(20 * SECOND, b"a".to_vec(), vec![1, 0, 0, 0])]
+        );
+    }
+
+    #[test]
+    fn test_survives_checkpoint() {
+        let input = [(SECOND, 5.0), (2 * SECOND, 75.0), (15 * SECOND, 500.0)];
+        let mut state = state();
+        for (timestamp, value) in input {
+            state.add(b"a".to_vec(), timestamp, value);
+        }
+        state.advance(10 * SECOND);
+
+        // on restore the buffered input is replayed and the restored watermark passed again
+        let mut restored = state();
+        for (timestamp, value) in input {
+            restored.add(b"a".to_vec(), timestamp, value);
+        }
+        assert_eq!(restored.advance(10 * SECOND).len(), 1);
+        restored.add(b"a".to_vec(), 16 * SECOND, 20.0);
+        // the first window has already fired, so this is dropped
+        restored.add(b"a".to_vec(), 3 * SECOND, 20.0);
+
+        assert_eq!(
+            restored.advance(20 * SECOND),
+            vec![(20 * SECOND, b"a".to_vec(), vec![0, 1, 0, 1])]
+        );
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::compute::take;
+use arrow_array::cast::AsArray;
+use arrow_array::types::TimestampNanosecondType;
+use arrow_array::{RecordBatch, TimestampNanosecondArray, UInt32Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::Converter;
+use arroyo_types::{to_nanos, CheckpointBarrier, Watermark};
+
+use crate::arrow::keyed_state::BufferedInput;
+
+/// The keys seen in each open hopping window, by window start.
+pub(crate) struct HoppingDedupState {
+    width: i64,
+    slide: i64,
+    windows: BTreeMap<i64, HashSet<Vec<u8>>>,
+    closed_through: Option<i64>,
+}
+
+impl HoppingDedupState {
+    pub(crate) fn new(width: Duration, slide: Duration) -> Self {
+        Self {
+            width: width.as_nanos() as i64,
+            slide: slide.as_nanos() as i64,
+            windows: BTreeMap::new(),
+            closed_through: None,
+        }
+    }
+
+    /// Records an occurrence of the key, returning the starts of the windows containing the
+    /// timestamp in which this is the key's first occurrence. Windows that have already closed
+    /// are skipped.
+    pub(crate) fn check(&mut self, key: &[u8], timestamp: i64) -> Vec<i64> {
+        let mut start = timestamp - timestamp.rem_euclid(self.slide);
+        let mut firsts = vec![];
+        while start > timestamp - self.width {
+            let closed = self
+                .closed_through
+                .is_some_and(|closed| start + self.width <= closed);
+            if !closed && self.windows.entry(start).or_default().insert(key.to_vec()) {
+                firsts.push(start);
+            }
+            start -= self.slide;
+        }
+        firsts.reverse();
+        firsts
+    }
+
+    /// Drops the seen keys for every window that ends at or before the watermark.
+    pub(crate) fn advance(&mut self, watermark: i64) {
+        self.windows = self.windows.split_off(&(watermark - self.width + 1));
+        self.closed_through = Some(self.closed_through.unwrap_or(i64::MIN).max(watermark));
+    }
+}
+
+/// Emits only the first occurrence of each key within each hopping window. As records belong to
+/// several overlapping windows, a record is emitted once for each window in which it's the first
+/// for its key, with the start of that window appended.
+pub struct HoppingDedupFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    key_converter: Converter,
+    input: BufferedInput,
+    state: HoppingDedupState,
+}
+
+impl HoppingDedupFunc {
+    fn process(&mut self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        let keys = self
+            .key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())?;
+        let timestamps = batch
+            .column(self.input_schema.timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+
+        let mut indices = vec![];
+        let mut starts = vec![];
+        for i in 0..batch.num_rows() {
+            for start in self.state.check(keys.row(i).as_ref(), timestamps.value(i)) {
+                indices.push(i as u32);
+                starts.push(start);
+            }
+        }
+
+        let indices = UInt32Array::from(indices);
+        let mut columns = batch
+            .columns()
+            .iter()
+            .map(|c| take(c, &indices, None))
+            .collect::<Result<Vec<_>, _>>()?;
+        columns.push(Arc::new(TimestampNanosecondArray::from(starts)));
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+}
+
+pub struct HoppingDedupConstructor;
+
+impl OperatorConstructor for HoppingDedupConstructor {
+    type ConfigT = api::HoppingDedupOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        if config.slide_micros == 0 || config.width_micros % config.slide_micros != 0 {
+            bail!("hopping window width must be a non-zero multiple of the slide");
+        }
+
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+
+        let mut fields = input_schema.schema.fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            config.window_field,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+
+        Ok(OperatorNode::from_operator(Box::new(HoppingDedupFunc {
+            name: config.name,
+            key_converter: input_schema.converter(false)?,
+            output_schema: Arc::new(Schema::new(fields)),
+            input: BufferedInput::new(
+                "i",
+                "hopping dedup input",
+                Duration::from_micros(config.width_micros),
+                input_schema.clone(),
+            ),
+            input_schema,
+            state: HoppingDedupState::new(
+                Duration::from_micros(config.width_micros),
+                Duration::from_micros(config.slide_micros),
+            ),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for HoppingDedupFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.input.table_config()].into_iter().collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let batches = self
+            .input
+            .restore(ctx)
+            .await
+            .expect("should be able to restore hopping dedup input");
+        for batch in &batches {
+            self.process(batch)
+                .expect("should be able to deduplicate batch");
+        }
+        if let Some(watermark) = ctx.last_present_watermark() {
+            self.state.advance(to_nanos(watermark) as i64);
+        }
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        let output = self
+            .process(&batch)
+            .expect("should be able to deduplicate batch");
+        self.input
+            .insert(ctx, &batch)
+            .await
+            .expect("should be able to buffer hopping dedup input");
+        if output.num_rows() > 0 {
+            ctx.collect(output).await;
+        }
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        _: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        if let Watermark::EventTime(t) = watermark {
+            self.state.advance(to_nanos(t) as i64);
+        }
+
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+        self.input
+            .flush(ctx)
+            .await
+            .expect("should be able to flush hopping dedup input");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: i64 = 1_000_000_000;
+
+    #[test]
+    fn test_duplicates_suppressed() {
+        // windows are 10s wide, starting every 5s
+        let mut state = HoppingDedupState::new(Duration::from_secs(10), Duration::from_secs(5));
+
+        assert_eq!(state.check(b"a", 7 * SECOND), vec![0, 5 * SECOND]);
+        // a duplicate in both of the same windows
+        assert!(state.check(b"a", 8 * SECOND).is_empty());
+        // a different key is unaffected
+        assert_eq!(state.check(b"b", 8 * SECOND), vec![0, 5 * SECOND]);
+        // only the later window ([10s, 20s)) is new for this key
+        assert_eq!(state.check(b"a", 12 * SECOND), vec![10 * SECOND]);
+        assert!(state.check(b"a", 9 * SECOND).is_empty());
+    }
+
+    #[test]
+    fn test_later_window_passes() {
+        let mut state = HoppingDedupState::new(Duration::from_secs(10), Duration::from_secs(5));
+        assert_eq!(state.check(b"a", SECOND), vec![-5 * SECOND, 0]);
+
+        state.advance(10 * SECOND);
+        assert!(state.windows.is_empty());
+
+        assert_eq!(
+            state.check(b"a", 21 * SECOND),
+            vec![15 * SECOND, 20 * SECOND]
+        );
+        // records for windows that have closed are dropped
+        assert!(state.check(b"a", 2 * SECOND).is_empty());
+        // but still count towards windows that haven't
+        assert_eq!(state.check(b"c", 6 * SECOND), vec![5 * SECOND]);
+    }
+}
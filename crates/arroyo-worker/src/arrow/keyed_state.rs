@@ -0,0 +1,272 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use arrow::compute::kernels::aggregate;
+use arrow::row::Rows;
+use arrow_array::cast::AsArray;
+use arrow_array::types::TimestampNanosecondType;
+use arrow_array::{BinaryArray, RecordBatch, TimestampNanosecondArray};
+use arrow_schema::{DataType, Field, Schema, TimeUnit};
+use arroyo_operator::context::ArrowContext;
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::TableConfig;
+use arroyo_rpc::Converter;
+use arroyo_state::timestamp_table_config;
+use arroyo_types::{from_nanos, to_nanos};
+use bincode::{config, Decode, Encode};
+
+/// How long, in event time, the state for a key is kept after it was last updated. This is the
+/// same retention that updating aggregates use for their per-key state.
+pub(crate) const KEYED_STATE_TTL: Duration = Duration::from_secs(60 * 60 * 24);
+
+/// Returns the latest timestamp in the batch, or `None` if it's empty.
+pub(crate) fn max_timestamp(batch: &RecordBatch, timestamp_index: usize) -> Option<SystemTime> {
+    aggregate::max(
+        batch
+            .column(timestamp_index)
+            .as_primitive::<TimestampNanosecondType>(),
+    )
+    .map(|nanos| from_nanos(nanos as u128))
+}
+
+/// A table holding one encoded value for each key of an operator's input. Rows are partitioned by
+/// the hash of the key columns, like the input, so each subtask restores only the keys routed to
+/// it, and only the keys updated in an epoch are written in its checkpoint. Keys that haven't been
+/// updated for [`KEYED_STATE_TTL`] before the watermark are expired.
+pub(crate) struct KeyedValues {
+    table: String,
+    description: String,
+    schema: ArroyoSchema,
+    key_converter: Converter,
+}
+
+impl KeyedValues {
+    pub(crate) fn new(table: &str, description: &str, input_schema: &ArroyoSchema) -> Result<Self> {
+        let key_indices = input_schema.key_indices.clone().unwrap_or_default();
+        let mut fields: Vec<_> = key_indices
+            .iter()
+            .map(|i| input_schema.schema.fields()[*i].clone())
+            .collect();
+        fields.push(Arc::new(Field::new("_value", DataType::Binary, false)));
+        fields.push(Arc::new(Field::new(
+            "_timestamp",
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+
+        let schema = ArroyoSchema::new_keyed(
+            Arc::new(Schema::new(fields)),
+            key_indices.len() + 1,
+            (0..key_indices.len()).collect(),
+        );
+
+        Ok(Self {
+            table: table.to_string(),
+            description: description.to_string(),
+            key_converter: schema.converter(false)?,
+            schema,
+        })
+    }
+
+    pub(crate) fn table_config(&self) -> (String, TableConfig) {
+        (
+            self.table.clone(),
+            timestamp_table_config(
+                &self.table,
+                &self.description,
+                KEYED_STATE_TTL,
+                true,
+                self.schema.clone(),
+            ),
+        )
+    }
+
+    /// Loads the table, so that restoring doesn't wait for the first batch.
+    pub(crate) async fn load(&self, ctx: &mut ArrowContext) -> Result<()> {
+        ctx.table_manager
+            .get_last_key_value_table(&self.table, ctx.last_present_watermark())
+            .await?;
+        Ok(())
+    }
+
+    /// Returns the stored values for the distinct keys among `keys`, which are encoded with the
+    /// input's key converter. Keys without a value are left out.
+    pub(crate) async fn get<T: Decode>(
+        &self,
+        ctx: &mut ArrowContext,
+        keys: &Rows,
+    ) -> Result<HashMap<Vec<u8>, T>> {
+        let mut distinct: Vec<_> = keys.iter().map(|row| row.as_ref()).collect();
+        distinct.sort_unstable();
+        distinct.dedup();
+        if distinct.is_empty() {
+            return Ok(HashMap::new());
+        }
+
+        let table = ctx
+            .table_manager
+            .get_last_key_value_table(&self.table, ctx.last_present_watermark())
+            .await?;
+        let placeholders = vec![&[][..]; distinct.len()];
+        let probe = self.batch(distinct, placeholders, 0)?;
+        let Some((values, _)) = table.get_current_matching_values(&probe)? else {
+            return Ok(HashMap::new());
+        };
+
+        self.decode(&values)
+    }
+
+    /// Returns the stored values for all of this subtask's keys.
+    pub(crate) async fn get_all<T: Decode>(
+        &self,
+        ctx: &mut ArrowContext,
+    ) -> Result<HashMap<Vec<u8>, T>> {
+        let values = ctx
+            .table_manager
+            .get_last_key_value_table(&self.table, ctx.last_present_watermark())
+            .await?
+            .get_all_values()?;
+        self.decode(&values)
+    }
+
+    /// Stores the values, which expire [`KEYED_STATE_TTL`] after `timestamp` unless they're
+    /// updated again.
+    pub(crate) async fn insert<T: Encode>(
+        &self,
+        ctx: &mut ArrowContext,
+        values: &HashMap<Vec<u8>, T>,
+        timestamp: SystemTime,
+    ) -> Result<()> {
+        if values.is_empty() {
+            return Ok(());
+        }
+
+        let mut keys = Vec::with_capacity(values.len());
+        let mut encoded = Vec::with_capacity(values.len());
+        for (key, value) in values {
+            keys.push(key.as_slice());
+            encoded.push(bincode::encode_to_vec(value, config::standard())?);
+        }
+        let batch = self.batch(
+            keys,
+            encoded.iter().map(|v| v.as_slice()).collect(),
+            to_nanos(timestamp) as i64,
+        )?;
+
+        ctx.table_manager
+            .get_last_key_value_table(&self.table, ctx.last_present_watermark())
+            .await?
+            .insert_batch(batch)
+            .await
+    }
+
+    /// Drops the values that haven't been updated within [`KEYED_STATE_TTL`] of the watermark.
+    pub(crate) async fn expire(&self, ctx: &mut ArrowContext) -> Result<()> {
+        let watermark = ctx.last_present_watermark();
+        ctx.table_manager
+            .get_last_key_value_table(&self.table, watermark)
+            .await?
+            .expire(watermark)
+    }
+
+    fn decode<T: Decode>(&self, values: &RecordBatch) -> Result<HashMap<Vec<u8>, T>> {
+        let key_count = self.schema.timestamp_index - 1;
+        let keys = self
+            .key_converter
+            .convert_all_columns(&values.columns()[..key_count], values.num_rows())?;
+        let encoded = values.column(key_count).as_binary::<i32>();
+        (0..values.num_rows())
+            .map(|i| {
+                let (value, _) = bincode::decode_from_slice(encoded.value(i), config::standard())?;
+                Ok((keys.row(i).as_ref().to_vec(), value))
+            })
+            .collect()
+    }
+
+    fn batch(&self, keys: Vec<&[u8]>, values: Vec<&[u8]>, timestamp: i64) -> Result<RecordBatch> {
+        let num_rows = keys.len();
+        let mut columns = self.key_converter.convert_raw_rows(keys)?;
+        columns.push(Arc::new(BinaryArray::from(values)));
+        columns.push(Arc::new(TimestampNanosecondArray::from(vec![
+            timestamp;
+            num_rows
+        ])));
+        Ok(RecordBatch::try_new(self.schema.schema.clone(), columns)?)
+    }
+}
+
+/// An expiring table of an operator's input, for operators whose windowed state isn't written
+/// incrementally. The input is kept for `retention` of event time, and on restore the state is
+/// rebuilt by replaying the batches that are still within it, like `window_fn` does.
+pub(crate) struct BufferedInput {
+    table: String,
+    description: String,
+    retention: Duration,
+    schema: ArroyoSchema,
+}
+
+impl BufferedInput {
+    pub(crate) fn new(
+        table: &str,
+        description: &str,
+        retention: Duration,
+        input_schema: ArroyoSchema,
+    ) -> Self {
+        Self {
+            table: table.to_string(),
+            description: description.to_string(),
+            retention,
+            schema: input_schema,
+        }
+    }
+
+    pub(crate) fn table_config(&self) -> (String, TableConfig) {
+        (
+            self.table.clone(),
+            timestamp_table_config(
+                &self.table,
+                &self.description,
+                self.retention,
+                false,
+                self.schema.clone(),
+            ),
+        )
+    }
+
+    /// Returns the restored batches, oldest first.
+    pub(crate) async fn restore(&self, ctx: &mut ArrowContext) -> Result<Vec<RecordBatch>> {
+        let watermark = ctx.last_present_watermark();
+        let table = ctx
+            .table_manager
+            .get_expiring_time_key_table(&self.table, watermark)
+            .await?;
+        Ok(table
+            .all_batches_for_watermark(watermark)
+            .flat_map(|(_, batches)| batches.iter().cloned())
+            .collect())
+    }
+
+    pub(crate) async fn insert(&self, ctx: &mut ArrowContext, batch: &RecordBatch) -> Result<()> {
+        let Some(timestamp) = max_timestamp(batch, self.schema.timestamp_index) else {
+            return Ok(());
+        };
+        ctx.table_manager
+            .get_expiring_time_key_table(&self.table, ctx.last_present_watermark())
+            .await?
+            .insert(timestamp, batch.clone());
+        Ok(())
+    }
+
+    /// Writes the batches inserted since the last checkpoint, and drops those older than the
+    /// retention.
+    pub(crate) async fn flush(&self, ctx: &mut ArrowContext) -> Result<()> {
+        let watermark = ctx.last_present_watermark();
+        ctx.table_manager
+            .get_expiring_time_key_table(&self.table, watermark)
+            .await?
+            .flush(watermark)
+            .await
+    }
+}
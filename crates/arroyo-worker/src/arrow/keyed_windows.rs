@@ -0,0 +1,70 @@
+use std::collections::btree_map::Entry;
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use arrow_array::TimestampNanosecondArray;
+
+/// The state for each key in each tumbling window that hasn't been emitted yet, keyed by
+/// `(window start, key)` so that closing windows can be split off in order of their end.
+pub(crate) struct KeyedTumblingWindows<A> {
+    width: i64,
+    windows: BTreeMap<(i64, Vec<u8>), A>,
+    emitted_through: Option<i64>,
+}
+
+impl<A> KeyedTumblingWindows<A> {
+    pub(crate) fn new(width: Duration) -> Self {
+        Self {
+            width: width.as_nanos() as i64,
+            windows: BTreeMap::new(),
+            emitted_through: None,
+        }
+    }
+
+    /// Returns the entry for the key in the window containing the timestamp, or `None` if that
+    /// window has already been emitted.
+    pub(crate) fn entry(
+        &mut self,
+        key: Vec<u8>,
+        timestamp: i64,
+    ) -> Option<Entry<'_, (i64, Vec<u8>), A>> {
+        let start = timestamp - timestamp.rem_euclid(self.width);
+        if self
+            .emitted_through
+            .is_some_and(|emitted| start + self.width <= emitted)
+        {
+            return None;
+        }
+
+        Some(self.windows.entry((start, key)))
+    }
+
+    /// Removes and returns every window that ends at or before the watermark, as
+    /// `(window end, key, state)`.
+    pub(crate) fn advance(&mut self, watermark: i64) -> Vec<(i64, Vec<u8>, A)> {
+        let remaining = self
+            .windows
+            .split_off(&(watermark - self.width + 1, vec![]));
+        let fired = std::mem::replace(&mut self.windows, remaining);
+
+        self.emitted_through = Some(self.emitted_through.unwrap_or(i64::MIN).max(watermark));
+
+        fired
+            .into_iter()
+            .map(|((start, key), state)| (start + self.width, key, state))
+            .collect()
+    }
+
+    /// The windows that are still open, as `(window end, key, state)`, in order of their end.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (i64, &[u8], &A)> {
+        self.windows
+            .iter()
+            .map(|((start, key), state)| (start + self.width, key.as_slice(), state))
+    }
+}
+
+/// The timestamps for records emitted for windows with the given ends. As with other windows,
+/// records are timestamped at the last instant of their window.
+pub(crate) fn window_timestamps(ends: impl IntoIterator<Item = i64>) -> TimestampNanosecondArray {
+    TimestampNanosecondArray::from_iter_values(ends.into_iter().map(|end| end - 1))
+}
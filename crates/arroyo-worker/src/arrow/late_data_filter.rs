@@ -0,0 +1,161 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use arrow_array::cast::AsArray;
+use arrow_array::types::TimestampNanosecondType;
+use arrow_array::{BooleanArray, RecordBatch};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::api;
+use arroyo_types::to_nanos;
+
+use super::split::split;
+
+/// The output that on-time rows are sent to; late rows, if they're kept, go to the other output
+const ON_TIME_OUTPUT: usize = 0;
+const LATE_OUTPUT: usize = 1;
+
+/// Marks the rows whose timestamps are more than `allowed_lateness` behind the watermark. Nothing
+/// is late before the first watermark.
+pub(crate) fn late_rows(
+    batch: &RecordBatch,
+    timestamp_index: usize,
+    watermark: Option<i64>,
+    allowed_lateness: i64,
+) -> BooleanArray {
+    let timestamps = batch
+        .column(timestamp_index)
+        .as_primitive::<TimestampNanosecondType>();
+
+    match watermark {
+        Some(watermark) => {
+            let cutoff = watermark.saturating_sub(allowed_lateness);
+            timestamps
+                .values()
+                .iter()
+                .map(|t| Some(*t < cutoff))
+                .collect()
+        }
+        None => BooleanArray::from(vec![false; batch.num_rows()]),
+    }
+}
+
+/// Drops records whose event time is further behind the current watermark than the allowed
+/// lateness, so that downstream operators can assume their input is on time. If
+/// `side_output_late` is set, late records are sent to a second output instead of being dropped.
+/// Watermarks are forwarded unchanged.
+pub struct LateDataFilterFunc {
+    name: String,
+    timestamp_index: usize,
+    allowed_lateness: Duration,
+    side_output_late: bool,
+}
+
+pub struct LateDataFilterConstructor;
+
+impl OperatorConstructor for LateDataFilterConstructor {
+    type ConfigT = api::LateDataFilterOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+
+        Ok(OperatorNode::from_operator(Box::new(LateDataFilterFunc {
+            name: config.name,
+            timestamp_index: input_schema.timestamp_index,
+            allowed_lateness: Duration::from_micros(config.allowed_lateness_micros),
+            side_output_late: config.side_output_late,
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for LateDataFilterFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        let late = late_rows(
+            &batch,
+            self.timestamp_index,
+            ctx.last_present_watermark().map(|w| to_nanos(w) as i64),
+            self.allowed_lateness.as_nanos() as i64,
+        );
+
+        let (late, on_time) = split(&batch, &late, false).expect("should be able to split batch");
+
+        if on_time.num_rows() > 0 {
+            ctx.collect_output(ON_TIME_OUTPUT, on_time).await;
+        }
+        if self.side_output_late && late.num_rows() > 0 {
+            ctx.collect_output(LATE_OUTPUT, late).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Int64Array, TimestampNanosecondArray};
+    use arrow_schema::{DataType, Field, Schema, TimeUnit};
+
+    const SECOND: i64 = 1_000_000_000;
+
+    fn batch(seconds: &[i64]) -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int64, false),
+                Field::new(
+                    "_timestamp",
+                    DataType::Timestamp(TimeUnit::Nanosecond, None),
+                    false,
+                ),
+            ])),
+            vec![
+                Arc::new(Int64Array::from_iter_values(0..seconds.len() as i64)),
+                Arc::new(TimestampNanosecondArray::from_iter_values(
+                    seconds.iter().map(|s| s * SECOND),
+                )),
+            ],
+        )
+        .unwrap()
+    }
+
+    fn ids(batch: &RecordBatch) -> Vec<i64> {
+        batch
+            .column(0)
+            .as_primitive::<arrow_array::types::Int64Type>()
+            .values()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_late_records_split() {
+        // with a watermark of 100s and 10s of allowed lateness, anything before 90s is late
+        let batch = batch(&[120, 100, 95, 90, 89, 10]);
+        let late = late_rows(&batch, 1, Some(100 * SECOND), 10 * SECOND);
+
+        let (late, on_time) = split(&batch, &late, false).unwrap();
+        assert_eq!(ids(&on_time), vec![0, 1, 2, 3]);
+        assert_eq!(ids(&late), vec![4, 5]);
+    }
+
+    #[test]
+    fn test_no_allowed_lateness() {
+        let batch = batch(&[100, 99]);
+        let late = late_rows(&batch, 1, Some(100 * SECOND), 0);
+        assert_eq!(late, BooleanArray::from(vec![false, true]));
+    }
+
+    #[test]
+    fn test_nothing_late_before_watermark() {
+        let batch = batch(&[0, 1]);
+        let late = late_rows(&batch, 1, None, 0);
+        assert_eq!(late, BooleanArray::from(vec![false, false]));
+    }
+}
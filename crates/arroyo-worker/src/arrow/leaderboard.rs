@@ -0,0 +1,366 @@
+use std::cmp::Reverse;
+use std::collections::{BTreeSet, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::compute::cast;
+use arrow::row::SortField;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, TimestampNanosecondType};
+use arrow_array::{Array, Float64Array, RecordBatch, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::{Converter, TIMESTAMP_FIELD};
+use arroyo_types::{to_nanos, CheckpointBarrier, Watermark};
+use ordered_float::OrderedFloat;
+
+use crate::arrow::keyed_state::BufferedInput;
+use crate::arrow::keyed_windows::{window_timestamps, KeyedTumblingWindows};
+
+/// The total score of every member of a leaderboard, along with an index of the members ordered
+/// by descending score, and by member for ties, which is kept up to date as scores change.
+///
+/// Every member's score is kept, not just the top `k`: since scores are sums, any member that
+/// has been seen can still reach the top with later values (or the leaders can drop with
+/// negative ones), so no member can be dropped while its window is open without risking a
+/// wrong result. Memory therefore grows with the number of distinct members per key and
+/// window, and is only released when the window closes.
+#[derive(Default)]
+pub(crate) struct Leaderboard {
+    scores: HashMap<Vec<u8>, f64>,
+    ranking: BTreeSet<(Reverse<OrderedFloat<f64>>, Vec<u8>)>,
+}
+
+impl Leaderboard {
+    pub(crate) fn add(&mut self, member: Vec<u8>, value: f64) {
+        let score = self.scores.entry(member.clone()).or_insert(0.0);
+        self.ranking
+            .remove(&(Reverse(OrderedFloat(*score)), member.clone()));
+        *score += value;
+        self.ranking.insert((Reverse(OrderedFloat(*score)), member));
+    }
+
+    /// The `k` highest-scoring members and their scores, in rank order.
+    pub(crate) fn top(&self, k: usize) -> Vec<(Vec<u8>, f64)> {
+        self.ranking
+            .iter()
+            .take(k)
+            .map(|(Reverse(score), member)| (member.clone(), score.0))
+            .collect()
+    }
+}
+
+/// Maintains a leaderboard for each key in each tumbling window.
+pub(crate) struct LeaderboardState {
+    k: usize,
+    windows: KeyedTumblingWindows<Leaderboard>,
+}
+
+impl LeaderboardState {
+    pub(crate) fn new(width: Duration, k: usize) -> Self {
+        Self {
+            k,
+            windows: KeyedTumblingWindows::new(width),
+        }
+    }
+
+    /// Adds a value to the member's score in its window, ignoring values for windows that have
+    /// already been emitted.
+    pub(crate) fn add(&mut self, key: Vec<u8>, timestamp: i64, member: Vec<u8>, value: f64) {
+        if let Some(entry) = self.windows.entry(key, timestamp) {
+            entry.or_default().add(member, value);
+        }
+    }
+
+    /// Removes every window that ends at or before the watermark, returning the top members of
+    /// each as `(window end, key, members and scores in rank order)`.
+    pub(crate) fn advance(&mut self, watermark: i64) -> Vec<(i64, Vec<u8>, Vec<(Vec<u8>, f64)>)> {
+        self.windows
+            .advance(watermark)
+            .into_iter()
+            .map(|(end, key, board)| (end, key, board.top(self.k)))
+            .collect()
+    }
+}
+
+/// Ranks members (for example, products) by the total of a value column (for example, sales)
+/// within each tumbling window and key, emitting the top `k` members of each leaderboard, with
+/// their scores and ranks, when the window closes. Ties are ranked by the member's value.
+pub struct LeaderboardFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    member_index: usize,
+    value_index: usize,
+    key_converter: Converter,
+    member_converter: Converter,
+    input: BufferedInput,
+    state: LeaderboardState,
+}
+
+impl LeaderboardFunc {
+    fn process(&mut self, batch: &RecordBatch) -> Result<()> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        let keys = self
+            .key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())?;
+        let members = self
+            .member_converter
+            .convert_all_columns(&[batch.column(self.member_index).clone()], batch.num_rows())?;
+        let values = cast(batch.column(self.value_index), &DataType::Float64)?;
+        let values = values.as_primitive::<Float64Type>();
+        let timestamps = batch
+            .column(self.input_schema.timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+
+        for i in 0..batch.num_rows() {
+            if !values.is_null(i) {
+                self.state.add(
+                    keys.row(i).as_ref().to_vec(),
+                    timestamps.value(i),
+                    members.row(i).as_ref().to_vec(),
+                    values.value(i),
+                );
+            }
+        }
+
+        Ok(())
+    }
+
+    fn emit(&mut self, watermark: i64) -> Result<RecordBatch> {
+        let rows: Vec<_> = self
+            .state
+            .advance(watermark)
+            .into_iter()
+            .flat_map(|(end, key, top)| {
+                top.into_iter()
+                    .enumerate()
+                    .map(move |(rank, (member, score))| (end, key.clone(), member, score, rank))
+            })
+            .collect();
+
+        let mut columns = self
+            .key_converter
+            .convert_raw_rows(rows.iter().map(|(_, k, _, _, _)| k.as_slice()).collect())?;
+        columns.extend(
+            self.member_converter
+                .convert_raw_rows(rows.iter().map(|(_, _, m, _, _)| m.as_slice()).collect())?,
+        );
+        columns.push(Arc::new(Float64Array::from_iter_values(
+            rows.iter().map(|(_, _, _, score, _)| *score),
+        )));
+        columns.push(Arc::new(UInt64Array::from_iter_values(
+            rows.iter().map(|(_, _, _, _, rank)| *rank as u64 + 1),
+        )));
+        columns.push(Arc::new(window_timestamps(
+            rows.iter().map(|(end, _, _, _, _)| *end),
+        )));
+
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+}
+
+pub struct LeaderboardConstructor;
+
+impl OperatorConstructor for LeaderboardConstructor {
+    type ConfigT = api::LeaderboardOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        if config.width_micros == 0 {
+            bail!("window width must be greater than 0");
+        }
+        if config.k == 0 {
+            bail!("leaderboard size must be greater than 0");
+        }
+
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+        let member_index = input_schema.schema.index_of(&config.member_field)?;
+        let value_index = input_schema.schema.index_of(&config.value_field)?;
+        let member_field = input_schema.schema.field(member_index);
+
+        let mut fields: Vec<_> = input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| input_schema.schema.fields()[*i].clone())
+            .collect();
+        fields.push(Arc::new(member_field.clone()));
+        fields.push(Arc::new(Field::new(
+            config.score_field,
+            DataType::Float64,
+            false,
+        )));
+        fields.push(Arc::new(Field::new(
+            config.rank_field,
+            DataType::UInt64,
+            false,
+        )));
+        fields.push(Arc::new(Field::new(
+            TIMESTAMP_FIELD,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+
+        Ok(OperatorNode::from_operator(Box::new(LeaderboardFunc {
+            name: config.name,
+            key_converter: input_schema.converter(false)?,
+            member_converter: Converter::new(vec![SortField::new(
+                member_field.data_type().clone(),
+            )])?,
+            output_schema: Arc::new(Schema::new(fields)),
+            input: BufferedInput::new(
+                "i",
+                "leaderboard input",
+                Duration::from_micros(config.width_micros),
+                input_schema.clone(),
+            ),
+            input_schema,
+            member_index,
+            value_index,
+            state: LeaderboardState::new(
+                Duration::from_micros(config.width_micros),
+                config.k as usize,
+            ),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for LeaderboardFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.input.table_config()].into_iter().collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let batches = self
+            .input
+            .restore(ctx)
+            .await
+            .expect("should be able to restore leaderboard input");
+        for batch in &batches {
+            self.process(batch)
+                .expect("should be able to update leaderboards");
+        }
+        if let Some(watermark) = ctx.last_present_watermark() {
+            self.state.advance(to_nanos(watermark) as i64);
+        }
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        self.process(&batch)
+            .expect("should be able to update leaderboards");
+        self.input
+            .insert(ctx, &batch)
+            .await
+            .expect("should be able to buffer leaderboard input");
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        if let Watermark::EventTime(t) = watermark {
+            let batch = self
+                .emit(to_nanos(t) as i64)
+                .expect("should be able to emit leaderboards");
+            if batch.num_rows() > 0 {
+                ctx.collect(batch).await;
+            }
+        }
+
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+        self.input
+            .flush(ctx)
+            .await
+            .expect("should be able to flush leaderboard input");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: i64 = 1_000_000_000;
+
+    fn names(top: &[(Vec<u8>, f64)]) -> Vec<(&str, f64)> {
+        top.iter()
+            .map(|(m, s)| (std::str::from_utf8(m).unwrap(), *s))
+            .collect()
+    }
+
+    #[test]
+    fn test_incremental_updates() {
+        let mut board = Leaderboard::default();
+        board.add(b"apple".to_vec(), 10.0);
+        board.add(b"pear".to_vec(), 5.0);
+        board.add(b"plum".to_vec(), 7.0);
+        assert_eq!(names(&board.top(2)), vec![("apple", 10.0), ("plum", 7.0)]);
+
+        // pear overtakes both
+        board.add(b"pear".to_vec(), 8.0);
+        assert_eq!(
+            names(&board.top(3)),
+            vec![("pear", 13.0), ("apple", 10.0), ("plum", 7.0)]
+        );
+
+        // a negative value drops apple below plum
+        board.add(b"apple".to_vec(), -4.0);
+        assert_eq!(
+            names(&board.top(3)),
+            vec![("pear", 13.0), ("plum", 7.0), ("apple", 6.0)]
+        );
+        assert_eq!(board.ranking.len(), 3);
+    }
+
+    #[test]
+    fn test_ties_ranked_by_member() {
+        let mut board = Leaderboard::default();
+        for member in ["c", "a", "b"] {
+            board.add(member.as_bytes().to_vec(), 1.0);
+        }
+        assert_eq!(
+            names(&board.top(3)),
+            vec![("a", 1.0), ("b", 1.0), ("c", 1.0)]
+        );
+    }
+
+    #[test]
+    fn test_emitted_per_window() {
+        let mut state = LeaderboardState::new(Duration::from_secs(10), 2);
+        for (t, member, value) in [(1, "x", 1.0), (2, "y", 3.0), (3, "z", 2.0), (12, "x", 9.0)] {
+            state.add(vec![], t * SECOND, member.as_bytes().to_vec(), value);
+        }
+
+        let results = state.advance(10 * SECOND);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, 10 * SECOND);
+        assert_eq!(names(&results[0].2), vec![("y", 3.0), ("z", 2.0)]);
+
+        // late values for the emitted window are ignored
+        state.add(vec![], 5 * SECOND, b"x".to_vec(), 100.0);
+        let results = state.advance(20 * SECOND);
+        assert_eq!(results.len(), 1);
+        assert_eq!(names(&results[0].2), vec![("x", 9.0)]);
+    }
+}
@@ -0,0 +1,401 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::compute::{cast, filter_record_batch};
+use arrow::row::{Rows, SortField};
+use arrow_array::{Array, BooleanArray, RecordBatch, StringArray};
+use arrow_schema::DataType;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::Converter;
+use arroyo_types::{UserError, Watermark};
+
+use crate::arrow::keyed_state::{max_timestamp, KeyedValues};
+
+/// The operation described by a CDC record, using Debezium's op codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum ChangeOp {
+    Upsert,
+    Delete,
+}
+
+/// The op code used for rows emitted as part of a snapshot of the view.
+const SNAPSHOT_OP: &str = "r";
+
+impl TryFrom<&str> for ChangeOp {
+    type Error = anyhow::Error;
+
+    fn try_from(value: &str) -> Result<Self> {
+        match value {
+            "c" | "r" | "u" => Ok(ChangeOp::Upsert),
+            "d" => Ok(ChangeOp::Delete),
+            op => bail!("unknown CDC op '{}'", op),
+        }
+    }
+}
+
+/// The latest encoded row for each primary key. As with updating aggregates, a key that isn't
+/// changed for [`KEYED_STATE_TTL`](crate::arrow::keyed_state::KEYED_STATE_TTL) of event time is
+/// dropped from the view.
+#[derive(Default)]
+pub(crate) struct MaterializedViewState {
+    rows: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl MaterializedViewState {
+    /// Applies a change to the view, returning whether it modified the view (deleting a key that
+    /// isn't present does not).
+    pub(crate) fn apply(&mut self, key: Vec<u8>, op: ChangeOp, row: Vec<u8>) -> bool {
+        match op {
+            ChangeOp::Upsert => {
+                self.rows.insert(key, row);
+                true
+            }
+            ChangeOp::Delete => self.rows.remove(&key).is_some(),
+        }
+    }
+}
+
+pub struct MaterializedViewFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    op_index: usize,
+    emit_changelog: bool,
+    snapshot_interval: Option<Duration>,
+    key_converter: Converter,
+    row_converter: Converter,
+    stored_rows: KeyedValues,
+    state: MaterializedViewState,
+}
+
+impl MaterializedViewFunc {
+    fn keys(&self, batch: &RecordBatch) -> Result<Rows> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        self.key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())
+    }
+
+    /// Applies the batch's changes to the view, returning the rows that changed it. Rows with a
+    /// missing or unrecognized op are skipped, and the reasons are returned alongside.
+    fn process(&mut self, batch: &RecordBatch, keys: &Rows) -> Result<(RecordBatch, Vec<String>)> {
+        let rows = self
+            .row_converter
+            .convert_all_columns(batch.columns(), batch.num_rows())?;
+
+        let ops = cast(batch.column(self.op_index), &DataType::Utf8)?;
+        let ops = ops
+            .as_any()
+            .downcast_ref::<StringArray>()
+            .ok_or_else(|| anyhow!("failed to cast op column to string"))?;
+
+        let mut changed = Vec::with_capacity(batch.num_rows());
+        let mut invalid = vec![];
+        for i in 0..batch.num_rows() {
+            let op = if ops.is_null(i) {
+                Err(anyhow!("CDC op column must not be null"))
+            } else {
+                ChangeOp::try_from(ops.value(i))
+            };
+
+            match op {
+                Ok(op) => changed.push(self.state.apply(
+                    keys.row(i).as_ref().to_vec(),
+                    op,
+                    rows.row(i).as_ref().to_vec(),
+                )),
+                Err(e) => {
+                    invalid.push(e.to_string());
+                    changed.push(false);
+                }
+            }
+        }
+
+        Ok((
+            filter_record_batch(batch, &BooleanArray::from(changed))?,
+            invalid,
+        ))
+    }
+
+    /// Applies the batch's changes to the stored rows for its keys, and stores the updated rows.
+    /// Deleted keys are stored without a row, as the table has no way to remove them.
+    async fn process_with_state(
+        &mut self,
+        batch: &RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> Result<(RecordBatch, Vec<String>)> {
+        let keys = self.keys(batch)?;
+        let stored: HashMap<Vec<u8>, Option<Vec<u8>>> = self.stored_rows.get(ctx, &keys).await?;
+        self.state.rows = stored
+            .into_iter()
+            .filter_map(|(key, row)| Some((key, row?)))
+            .collect();
+
+        let (changelog, invalid) = self.process(batch, &keys)?;
+
+        let updated: HashMap<_, _> = keys
+            .iter()
+            .map(|key| {
+                (
+                    key.as_ref().to_vec(),
+                    self.state.rows.get(key.as_ref()).cloned(),
+                )
+            })
+            .collect();
+        if let Some(timestamp) = max_timestamp(batch, self.input_schema.timestamp_index) {
+            self.stored_rows.insert(ctx, &updated, timestamp).await?;
+        }
+        Ok((changelog, invalid))
+    }
+
+    async fn snapshot(&self, ctx: &mut ArrowContext) -> Result<RecordBatch> {
+        let stored: HashMap<Vec<u8>, Option<Vec<u8>>> = self.stored_rows.get_all(ctx).await?;
+        let rows: Vec<_> = stored.values().flatten().map(|r| r.as_slice()).collect();
+
+        let ops = StringArray::from(vec![SNAPSHOT_OP; rows.len()]);
+        let mut columns = self.row_converter.convert_raw_rows(rows)?;
+        columns[self.op_index] = cast(&ops, columns[self.op_index].data_type())?;
+
+        Ok(RecordBatch::try_new(
+            self.input_schema.schema.clone(),
+            columns,
+        )?)
+    }
+}
+
+pub struct MaterializedViewConstructor;
+
+impl OperatorConstructor for MaterializedViewConstructor {
+    type ConfigT = api::MaterializedViewOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+        let op_index = input_schema.schema.index_of(&config.op_field)?;
+
+        let row_converter = Converter::new(
+            input_schema
+                .schema
+                .fields()
+                .iter()
+                .map(|f| SortField::new(f.data_type().clone()))
+                .collect(),
+        )?;
+
+        Ok(OperatorNode::from_operator(Box::new(
+            MaterializedViewFunc {
+                name: config.name,
+                key_converter: input_schema.converter(false)?,
+                row_converter,
+                stored_rows: KeyedValues::new("m", "materialized view rows by key", &input_schema)?,
+                input_schema,
+                op_index,
+                emit_changelog: config.emit_changelog,
+                snapshot_interval: (config.snapshot_interval_micros > 0)
+                    .then(|| Duration::from_micros(config.snapshot_interval_micros)),
+                state: MaterializedViewState::default(),
+            },
+        )))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for MaterializedViewFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.stored_rows.table_config()].into_iter().collect()
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        self.snapshot_interval
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        self.stored_rows
+            .load(ctx)
+            .await
+            .expect("should have materialized view table");
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        let (changelog, invalid) = self
+            .process_with_state(&batch, ctx)
+            .await
+            .expect("should be able to apply changes to materialized view");
+
+        if let Some(reason) = invalid.first() {
+            ctx.report_user_error(UserError::new(
+                "invalid CDC op; dropping rows",
+                format!(
+                    "skipped {} rows, the first because: {}",
+                    invalid.len(),
+                    reason
+                ),
+            ))
+            .await;
+        }
+
+        if self.emit_changelog {
+            ctx.collect(changelog).await;
+        }
+    }
+
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) {
+        if self.snapshot_interval.is_none() {
+            return;
+        }
+
+        let snapshot = self
+            .snapshot(ctx)
+            .await
+            .expect("should be able to snapshot materialized view");
+        ctx.collect(snapshot).await;
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        self.stored_rows
+            .expire(ctx)
+            .await
+            .expect("should expire materialized view table");
+        Some(watermark)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(k: &str) -> Vec<u8> {
+        k.as_bytes().to_vec()
+    }
+
+    fn row(r: &str) -> Vec<u8> {
+        r.as_bytes().to_vec()
+    }
+
+    #[test]
+    fn test_upserts_and_deletes() {
+        let mut state = MaterializedViewState::default();
+
+        let changes = [
+            ("a", "c", "a1"),
+            ("b", "c", "b1"),
+            ("a", "u", "a2"),
+            ("c", "r", "c1"),
+            ("b", "d", "b1"),
+            ("d", "d", "d1"),
+            ("c", "u", "c2"),
+            ("b", "c", "b2"),
+            ("a", "d", "a2"),
+        ];
+
+        let changed: Vec<_> = changes
+            .iter()
+            .map(|(k, op, r)| state.apply(key(k), ChangeOp::try_from(*op).unwrap(), row(r)))
+            .collect();
+
+        // deleting a key that was never present doesn't produce a change
+        assert_eq!(
+            changed,
+            vec![true, true, true, true, true, false, true, true, true]
+        );
+
+        assert_eq!(
+            state.rows,
+            HashMap::from([(key("b"), row("b2")), (key("c"), row("c2"))])
+        );
+    }
+
+    #[test]
+    fn test_restore() {
+        let mut state = MaterializedViewState {
+            rows: HashMap::from([(key("b"), row("b1"))]),
+        };
+
+        assert!(!state.apply(key("a"), ChangeOp::Delete, row("a1")));
+        assert!(state.apply(key("b"), ChangeOp::Delete, row("b1")));
+        assert!(state.rows.is_empty());
+    }
+
+    #[test]
+    fn test_unknown_op() {
+        assert!(ChangeOp::try_from("x").is_err());
+    }
+
+    #[test]
+    fn test_invalid_ops_are_skipped() {
+        let schema = Arc::new(arrow_schema::Schema::new(vec![
+            arrow_schema::Field::new("k", DataType::Utf8, false),
+            arrow_schema::Field::new("op", DataType::Utf8, true),
+            arrow_schema::Field::new(
+                "_timestamp",
+                DataType::Timestamp(arrow_schema::TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+        let input_schema = ArroyoSchema::new_keyed(schema.clone(), 2, vec![0]);
+        let row_converter = Converter::new(
+            schema
+                .fields()
+                .iter()
+                .map(|f| SortField::new(f.data_type().clone()))
+                .collect(),
+        )
+        .unwrap();
+
+        let mut func = MaterializedViewFunc {
+            name: "materialized_view".to_string(),
+            key_converter: input_schema.converter(false).unwrap(),
+            row_converter,
+            stored_rows: KeyedValues::new("m", "rows", &input_schema).unwrap(),
+            input_schema,
+            op_index: 1,
+            emit_changelog: true,
+            snapshot_interval: None,
+            state: MaterializedViewState::default(),
+        };
+
+        let batch = RecordBatch::try_new(
+            schema,
+            vec![
+                Arc::new(StringArray::from(vec!["a", "b", "c", "d"])),
+                Arc::new(StringArray::from(vec![
+                    Some("c"),
+                    None,
+                    Some("x"),
+                    Some("u"),
+                ])),
+                Arc::new(arrow_array::TimestampNanosecondArray::from(vec![0; 4])),
+            ],
+        )
+        .unwrap();
+        let keys = func.keys(&batch).unwrap();
+
+        let (changelog, invalid) = func.process(&batch, &keys).unwrap();
+
+        // the valid rows on either side of the invalid ones are still applied
+        assert_eq!(changelog.num_rows(), 2);
+        assert_eq!(invalid.len(), 2);
+        assert_eq!(func.state.rows.len(), 2);
+        assert!(func.state.rows.contains_key(keys.row(0).as_ref()));
+        assert!(func.state.rows.contains_key(keys.row(3).as_ref()));
+    }
+}
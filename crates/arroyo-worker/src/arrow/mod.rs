@@ -21,16 +21,44 @@ use prost::Message as ProstMessage;
 use std::sync::Arc;
 use std::sync::RwLock;
 
+pub mod async_batch_transform;
 pub mod async_udf;
+pub mod bloom_filter;
+pub mod boundary_flush;
+pub mod correlation;
+pub mod count_window;
+pub mod delta;
+pub mod early_results;
+pub mod ema;
+pub mod first_last;
+pub mod heartbeat;
+pub mod histogram;
+pub mod hopping_dedup;
 pub mod instant_join;
 pub mod join_with_expiration;
+pub(crate) mod keyed_state;
+pub(crate) mod keyed_windows;
+pub mod late_data_filter;
+pub mod leaderboard;
+pub mod materialized_view;
+pub mod project_filter;
+pub mod retract;
+pub mod rolling_average;
 pub mod session_aggregating_window;
 pub mod sliding_aggregating_window;
+pub mod snapshot_diff;
+pub mod split;
 pub(crate) mod sync;
+pub mod temporal_join;
+pub mod throughput;
 pub mod tumbling_aggregating_window;
+pub mod union;
 pub mod updating_aggregator;
 pub mod watermark_generator;
+pub mod watermark_stall;
+pub mod window_assigner;
 pub mod window_fn;
+pub mod z_score;
 
 pub struct ValueExecutionOperator {
     name: String,
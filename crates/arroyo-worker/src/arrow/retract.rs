@@ -0,0 +1,222 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use arrow::row::{Rows, SortField};
+use arrow_array::{BooleanArray, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::{Converter, IS_RETRACT_FIELD};
+use arroyo_types::Watermark;
+
+use crate::arrow::keyed_state::{max_timestamp, KeyedValues};
+
+/// Tracks the latest encoded row for each key, turning a stream of values into a changelog. A key
+/// that sees no records for [`KEYED_STATE_TTL`](crate::arrow::keyed_state::KEYED_STATE_TTL) of
+/// event time is forgotten, so its next row is emitted as an append without a retraction.
+#[derive(Default)]
+pub(crate) struct RetractState {
+    previous: HashMap<Vec<u8>, Vec<u8>>,
+}
+
+impl RetractState {
+    /// Returns the changelog entries for a new row as `(row, is_retract)` pairs: an append for the
+    /// first row for a key, and a retraction of the previous row followed by an append when the
+    /// value changes. An unchanged value produces no entries.
+    pub(crate) fn update(&mut self, key: Vec<u8>, row: Vec<u8>) -> Vec<(Vec<u8>, bool)> {
+        match self.previous.insert(key, row.clone()) {
+            None => vec![(row, false)],
+            Some(previous) if previous == row => vec![],
+            Some(previous) => vec![(previous, true), (row, false)],
+        }
+    }
+}
+
+pub struct RetractFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    key_converter: Converter,
+    row_converter: Converter,
+    previous: KeyedValues,
+    state: RetractState,
+}
+
+impl RetractFunc {
+    fn keys(&self, batch: &RecordBatch) -> Result<Rows> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        self.key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())
+    }
+
+    fn process(&mut self, batch: &RecordBatch, keys: &Rows) -> Result<RecordBatch> {
+        let rows = self
+            .row_converter
+            .convert_all_columns(batch.columns(), batch.num_rows())?;
+
+        let mut changes = vec![];
+        for i in 0..batch.num_rows() {
+            changes.extend(
+                self.state
+                    .update(keys.row(i).as_ref().to_vec(), rows.row(i).as_ref().to_vec()),
+            );
+        }
+
+        let mut columns = self
+            .row_converter
+            .convert_raw_rows(changes.iter().map(|(row, _)| row.as_slice()).collect())?;
+        columns.push(Arc::new(BooleanArray::from(
+            changes
+                .iter()
+                .map(|(_, retract)| *retract)
+                .collect::<Vec<_>>(),
+        )));
+
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+
+    /// Computes the batch's changelog against the previous rows stored for its keys, and stores
+    /// the new ones.
+    async fn process_with_state(
+        &mut self,
+        batch: &RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> Result<RecordBatch> {
+        let keys = self.keys(batch)?;
+        self.state.previous = self.previous.get(ctx, &keys).await?;
+        let changelog = self.process(batch, &keys)?;
+        if let Some(timestamp) = max_timestamp(batch, self.input_schema.timestamp_index) {
+            self.previous
+                .insert(ctx, &self.state.previous, timestamp)
+                .await?;
+        }
+        Ok(changelog)
+    }
+}
+
+pub struct RetractConstructor;
+
+impl OperatorConstructor for RetractConstructor {
+    type ConfigT = api::RetractOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+
+        let row_converter = Converter::new(
+            input_schema
+                .schema
+                .fields()
+                .iter()
+                .map(|f| SortField::new(f.data_type().clone()))
+                .collect(),
+        )?;
+
+        let mut fields = input_schema.schema.fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            IS_RETRACT_FIELD,
+            DataType::Boolean,
+            false,
+        )));
+
+        Ok(OperatorNode::from_operator(Box::new(RetractFunc {
+            name: config.name,
+            key_converter: input_schema.converter(false)?,
+            row_converter,
+            output_schema: Arc::new(Schema::new(fields)),
+            previous: KeyedValues::new("r", "latest row by key", &input_schema)?,
+            input_schema,
+            state: RetractState::default(),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for RetractFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.previous.table_config()].into_iter().collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        self.previous
+            .load(ctx)
+            .await
+            .expect("should have retract table");
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        let changelog = self
+            .process_with_state(&batch, ctx)
+            .await
+            .expect("should be able to compute retractions");
+        ctx.collect(changelog).await;
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        self.previous
+            .expire(ctx)
+            .await
+            .expect("should expire retract table");
+        Some(watermark)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn bytes(s: &str) -> Vec<u8> {
+        s.as_bytes().to_vec()
+    }
+
+    #[test]
+    fn test_append_on_first_insert() {
+        let mut state = RetractState::default();
+
+        assert_eq!(
+            state.update(bytes("a"), bytes("a1")),
+            vec![(bytes("a1"), false)]
+        );
+        assert_eq!(
+            state.update(bytes("b"), bytes("b1")),
+            vec![(bytes("b1"), false)]
+        );
+    }
+
+    #[test]
+    fn test_retraction_on_update() {
+        let mut state = RetractState::default();
+        state.update(bytes("a"), bytes("a1"));
+
+        assert_eq!(
+            state.update(bytes("a"), bytes("a2")),
+            vec![(bytes("a1"), true), (bytes("a2"), false)]
+        );
+        assert_eq!(
+            state.update(bytes("a"), bytes("a3")),
+            vec![(bytes("a2"), true), (bytes("a3"), false)]
+        );
+
+        // an unchanged value doesn't produce a changelog entry
+        assert_eq!(state.update(bytes("a"), bytes("a3")), vec![]);
+    }
+}
@@ -0,0 +1,395 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::compute::cast;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, TimestampNanosecondType};
+use arrow_array::{Array, Float64Array, RecordBatch, TimestampNanosecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::{Converter, TIMESTAMP_FIELD};
+use arroyo_types::{to_nanos, CheckpointBarrier, Watermark};
+
+use crate::arrow::keyed_state::BufferedInput;
+
+/// Per-key partial sums and counts for each pane (a `slide`-wide slice of event time). A sliding
+/// window of `width` is the combination of `width / slide` consecutive panes.
+pub(crate) struct RollingAverageState {
+    width: i64,
+    slide: i64,
+    panes: HashMap<Vec<u8>, BTreeMap<i64, (f64, u64)>>,
+    emitted_through: Option<i64>,
+}
+
+impl RollingAverageState {
+    pub(crate) fn new(width: Duration, slide: Duration) -> Self {
+        Self {
+            width: width.as_nanos() as i64,
+            slide: slide.as_nanos() as i64,
+            panes: HashMap::new(),
+            emitted_through: None,
+        }
+    }
+
+    /// Adds a value to its pane, ignoring values that are too late to contribute to any window
+    /// that has yet to be emitted.
+    pub(crate) fn add(&mut self, key: Vec<u8>, timestamp: i64, value: f64) {
+        let pane = timestamp - timestamp.rem_euclid(self.slide);
+        if let Some(emitted) = self.emitted_through {
+            if pane + self.width <= emitted {
+                return;
+            }
+        }
+
+        let (sum, count) = self.panes.entry(key).or_default().entry(pane).or_default();
+        *sum += value;
+        *count += 1;
+    }
+
+    /// Emits the average for every key in each window that ends at or before the watermark, as
+    /// `(window end, key, average)`, then drops panes that can't contribute to later windows.
+    pub(crate) fn advance(&mut self, watermark: i64) -> Vec<(i64, Vec<u8>, f64)> {
+        let Some(first_pane) = self
+            .panes
+            .values()
+            .filter_map(|p| p.keys().next())
+            .min()
+            .copied()
+        else {
+            return vec![];
+        };
+        let last_pane = self
+            .panes
+            .values()
+            .filter_map(|p| p.keys().next_back())
+            .max()
+            .copied()
+            .unwrap();
+
+        let mut end = first_pane + self.slide;
+        if let Some(emitted) = self.emitted_through {
+            end = end.max(emitted + self.slide);
+        }
+
+        let mut results = vec![];
+        // stop once windows no longer contain any panes
+        while end <= watermark && end - self.width <= last_pane {
+            for (key, panes) in &self.panes {
+                let (sum, count) = panes
+                    .range(end - self.width..end)
+                    .fold((0.0, 0), |(s, c), (_, (sum, count))| (s + sum, c + count));
+                if count > 0 {
+                    results.push((end, key.clone(), sum / count as f64));
+                }
+            }
+            self.emitted_through = Some(end);
+            end += self.slide;
+        }
+
+        self.drop_emitted_panes();
+        results
+    }
+
+    /// Treats every window that ends at or before the watermark as emitted, discarding their
+    /// results. This is used after replaying restored input, as those windows were emitted
+    /// before the restore.
+    pub(crate) fn skip_to(&mut self, watermark: i64) {
+        self.advance(watermark);
+        let end = watermark - watermark.rem_euclid(self.slide);
+        self.emitted_through = Some(self.emitted_through.map_or(end, |e| e.max(end)));
+        self.drop_emitted_panes();
+    }
+
+    fn drop_emitted_panes(&mut self) {
+        if let Some(emitted) = self.emitted_through {
+            let retain_from = emitted + self.slide - self.width;
+            self.panes.retain(|_, panes| {
+                *panes = panes.split_off(&retain_from);
+                !panes.is_empty()
+            });
+        }
+    }
+}
+
+pub struct RollingAverageFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    value_index: usize,
+    key_converter: Converter,
+    input: BufferedInput,
+    state: RollingAverageState,
+}
+
+impl RollingAverageFunc {
+    fn process(&mut self, batch: &RecordBatch) -> Result<()> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        let keys = self
+            .key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())?;
+
+        let timestamps = batch
+            .column(self.input_schema.timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+        let values = cast(batch.column(self.value_index), &DataType::Float64)?;
+        let values = values.as_primitive::<Float64Type>();
+
+        for i in 0..batch.num_rows() {
+            if values.is_null(i) {
+                continue;
+            }
+            self.state.add(
+                keys.row(i).as_ref().to_vec(),
+                timestamps.value(i),
+                values.value(i),
+            );
+        }
+
+        Ok(())
+    }
+
+    fn emit(&mut self, watermark: i64) -> Result<RecordBatch> {
+        let results = self.state.advance(watermark);
+
+        let mut columns = self
+            .key_converter
+            .convert_raw_rows(results.iter().map(|(_, k, _)| k.as_slice()).collect())?;
+        columns.push(Arc::new(Float64Array::from_iter_values(
+            results.iter().map(|(_, _, avg)| *avg),
+        )));
+        // as with other windows, records are timestamped at the last instant of their window
+        columns.push(Arc::new(TimestampNanosecondArray::from_iter_values(
+            results.iter().map(|(end, _, _)| end - 1),
+        )));
+
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+}
+
+pub struct RollingAverageConstructor;
+
+impl OperatorConstructor for RollingAverageConstructor {
+    type ConfigT = api::RollingAverageOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        if config.slide_micros == 0 || config.width_micros % config.slide_micros != 0 {
+            bail!("rolling average width must be a non-zero multiple of the slide");
+        }
+
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+        let value_index = input_schema.schema.index_of(&config.value_field)?;
+
+        let mut fields: Vec<_> = input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| input_schema.schema.fields()[*i].clone())
+            .collect();
+        fields.push(Arc::new(Field::new(
+            config.average_field,
+            DataType::Float64,
+            false,
+        )));
+        fields.push(Arc::new(Field::new(
+            TIMESTAMP_FIELD,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+
+        Ok(OperatorNode::from_operator(Box::new(RollingAverageFunc {
+            name: config.name,
+            key_converter: input_schema.converter(false)?,
+            output_schema: Arc::new(Schema::new(fields)),
+            input: BufferedInput::new(
+                "i",
+                "rolling average input",
+                Duration::from_micros(config.width_micros),
+                input_schema.clone(),
+            ),
+            input_schema,
+            value_index,
+            state: RollingAverageState::new(
+                Duration::from_micros(config.width_micros),
+                Duration::from_micros(config.slide_micros),
+            ),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for RollingAverageFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.input.table_config()].into_iter().collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let batches = self
+            .input
+            .restore(ctx)
+            .await
+            .expect("should be able to restore rolling average input");
+        for batch in &batches {
+            self.process(batch)
+                .expect("should be able to compute rolling average");
+        }
+        if let Some(watermark) = ctx.last_present_watermark() {
+            self.state.skip_to(to_nanos(watermark) as i64);
+        }
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        self.process(&batch)
+            .expect("should be able to compute rolling average");
+        self.input
+            .insert(ctx, &batch)
+            .await
+            .expect("should be able to buffer rolling average input");
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        if let Watermark::EventTime(t) = watermark {
+            let batch = self
+                .emit(to_nanos(t) as i64)
+                .expect("should be able to emit rolling averages");
+            ctx.collect(batch).await;
+        }
+
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+        self.input
+            .flush(ctx)
+            .await
+            .expect("should be able to flush rolling average input");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: i64 = 1_000_000_000;
+
+    fn records() -> Vec<(&'static str, i64, f64)> {
+        (0..40)
+            .map(|i| {
+                let key = if i % 3 == 0 { "a" } else { "b" };
+                (key, i * SECOND / 2 + (i % 5) * 7, (i * i % 17) as f64)
+            })
+            .collect()
+    }
+
+    fn reference(records: &[(&str, i64, f64)], key: &str, start: i64, end: i64) -> Option<f64> {
+        let values: Vec<_> = records
+            .iter()
+            .filter(|(k, t, _)| *k == key && *t >= start && *t < end)
+            .map(|(_, _, v)| *v)
+            .collect();
+        (!values.is_empty()).then(|| values.iter().sum::<f64>() / values.len() as f64)
+    }
+
+    #[test]
+    fn test_matches_reference() {
+        let width = 6 * SECOND;
+        let slide = 2 * SECOND;
+        let mut state = RollingAverageState::new(Duration::from_secs(6), Duration::from_secs(2));
+
+        let records = records();
+        let mut results = vec![];
+        // feed the records in chunks, advancing the watermark as we go
+        for chunk in records.chunks(7) {
+            for (key, t, v) in chunk {
+                state.add(key.as_bytes().to_vec(), *t, *v);
+            }
+            let watermark = chunk.iter().map(|(_, t, _)| *t).min().unwrap();
+            results.extend(state.advance(watermark));
+        }
+        results.extend(state.advance(i64::MAX / 2));
+
+        let mut expected = vec![];
+        let mut end = slide;
+        while end - width <= 20 * SECOND {
+            for key in ["a", "b"] {
+                if let Some(avg) = reference(&records, key, end - width, end) {
+                    expected.push((end, key.as_bytes().to_vec(), avg));
+                }
+            }
+            end += slide;
+        }
+
+        results.sort_by(|a, b| (a.0, &a.1).cmp(&(b.0, &b.1)));
+        assert_eq!(results.len(), expected.len());
+        for ((end, key, avg), (e_end, e_key, e_avg)) in results.iter().zip(&expected) {
+            assert_eq!((end, key), (e_end, e_key));
+            assert!((avg - e_avg).abs() < 1e-9, "{} != {}", avg, e_avg);
+        }
+
+        // all panes have been dropped once every window has been emitted
+        assert!(state.panes.is_empty());
+    }
+
+    #[test]
+    fn test_late_data_ignored() {
+        let mut state = RollingAverageState::new(Duration::from_secs(4), Duration::from_secs(2));
+        state.add(b"a".to_vec(), 0, 1.0);
+        state.add(b"a".to_vec(), 3 * SECOND, 3.0);
+
+        assert_eq!(
+            state.advance(4 * SECOND),
+            vec![
+                (2 * SECOND, b"a".to_vec(), 1.0),
+                (4 * SECOND, b"a".to_vec(), 2.0)
+            ]
+        );
+
+        // too late for the next window (2s..6s), so it's dropped
+        state.add(b"a".to_vec(), SECOND, 100.0);
+        // still contributes to the next window
+        state.add(b"a".to_vec(), 2 * SECOND, 5.0);
+
+        assert_eq!(
+            state.advance(6 * SECOND),
+            vec![(6 * SECOND, b"a".to_vec(), 4.0)]
+        );
+    }
+
+    #[test]
+    fn test_skip_to_after_replay() {
+        let mut state = RollingAverageState::new(Duration::from_secs(4), Duration::from_secs(2));
+        // replayed input, of which the window ending at 4s was emitted before the restore
+        state.add(b"a".to_vec(), 3 * SECOND, 3.0);
+        state.skip_to(5 * SECOND);
+
+        // too late for any window that hasn't been emitted
+        state.add(b"a".to_vec(), SECOND, 100.0);
+        state.add(b"a".to_vec(), 5 * SECOND, 5.0);
+
+        assert_eq!(
+            state.advance(6 * SECOND),
+            vec![(6 * SECOND, b"a".to_vec(), 4.0)]
+        );
+    }
+}
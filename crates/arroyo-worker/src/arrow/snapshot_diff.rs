@@ -0,0 +1,484 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use arrow::row::SortField;
+use arrow_array::cast::AsArray;
+use arrow_array::types::TimestampNanosecondType;
+use arrow_array::{ArrayRef, RecordBatch, StringArray, TimestampNanosecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::Converter;
+use arroyo_types::{from_nanos, to_nanos, CheckpointBarrier, Watermark};
+
+use crate::arrow::keyed_state::{BufferedInput, KeyedValues};
+
+/// A change between two snapshots, using Debezium's op codes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Change {
+    Insert,
+    Update,
+    Delete,
+}
+
+impl Change {
+    fn op(&self) -> &'static str {
+        match self {
+            Change::Insert => "c",
+            Change::Update => "u",
+            Change::Delete => "d",
+        }
+    }
+}
+
+/// Compares two snapshots of encoded rows by primary key, returning the changes from the previous
+/// to the current in key order, as `(key, change, row)`. Deletes carry the previous row for the
+/// key.
+pub(crate) fn diff(
+    previous: &BTreeMap<Vec<u8>, Vec<u8>>,
+    current: &BTreeMap<Vec<u8>, Vec<u8>>,
+) -> Vec<(Vec<u8>, Change, Vec<u8>)> {
+    let mut changes: Vec<_> = current
+        .iter()
+        .filter_map(|(key, row)| match previous.get(key) {
+            None => Some((key, Change::Insert, row)),
+            Some(old) if old != row => Some((key, Change::Update, row)),
+            Some(_) => None,
+        })
+        .chain(
+            previous
+                .iter()
+                .filter(|(key, _)| !current.contains_key(*key))
+                .map(|(key, row)| (key, Change::Delete, row)),
+        )
+        .collect();
+
+    changes.sort_by(|a, b| a.0.cmp(b.0));
+    changes
+        .into_iter()
+        .map(|(key, change, row)| (key.clone(), change, row.clone()))
+        .collect()
+}
+
+/// Buffers each snapshot until the watermark passes its timestamp, then diffs it against the
+/// previous complete snapshot. When snapshots are taken at a known interval, a boundary that the
+/// watermark passes without any rows is an empty snapshot, in which every key has been deleted.
+pub(crate) struct SnapshotDiffState {
+    interval: Option<i64>,
+    previous: BTreeMap<Vec<u8>, Vec<u8>>,
+    pending: BTreeMap<i64, BTreeMap<Vec<u8>, Vec<u8>>>,
+    completed_through: Option<i64>,
+}
+
+impl SnapshotDiffState {
+    pub(crate) fn new(interval: Option<Duration>) -> Self {
+        Self {
+            interval: interval.map(|interval| interval.as_nanos() as i64),
+            previous: BTreeMap::new(),
+            pending: BTreeMap::new(),
+            completed_through: None,
+        }
+    }
+
+    /// Adds a row to the snapshot taken at the timestamp, ignoring rows for snapshots that have
+    /// already been diffed.
+    pub(crate) fn add(&mut self, snapshot: i64, key: Vec<u8>, row: Vec<u8>) {
+        if self.completed_through.is_some_and(|c| snapshot <= c) {
+            return;
+        }
+        self.pending.entry(snapshot).or_default().insert(key, row);
+    }
+
+    /// Diffs every snapshot at or before the watermark against its predecessor, in order,
+    /// returning the changes for each as `(snapshot, changes)`.
+    pub(crate) fn advance(
+        &mut self,
+        watermark: i64,
+    ) -> Vec<(i64, Vec<(Vec<u8>, Change, Vec<u8>)>)> {
+        let remaining = self.pending.split_off(&watermark.saturating_add(1));
+        let complete = std::mem::replace(&mut self.pending, remaining);
+
+        let mut results = vec![];
+        let mut last = self.completed_through;
+        for (snapshot, rows) in complete {
+            results.extend(self.empty_snapshot(last, snapshot));
+            let changes = diff(&self.previous, &rows);
+            self.previous = rows;
+            results.push((snapshot, changes));
+            last = Some(snapshot);
+        }
+        results.extend(self.empty_snapshot(last, watermark.saturating_add(1)));
+
+        self.completed_through = Some(self.completed_through.unwrap_or(i64::MIN).max(watermark));
+        results
+    }
+
+    /// Diffs an empty snapshot at the first interval boundary after `after`, if there is one
+    /// before `before`. Only the first is needed, as later empty snapshots can't change anything.
+    fn empty_snapshot(
+        &mut self,
+        after: Option<i64>,
+        before: i64,
+    ) -> Option<(i64, Vec<(Vec<u8>, Change, Vec<u8>)>)> {
+        let (after, interval) = (after?, self.interval?);
+        let boundary = (after - after.rem_euclid(interval)).saturating_add(interval);
+        if boundary >= before || self.previous.is_empty() {
+            return None;
+        }
+
+        let changes = diff(&std::mem::take(&mut self.previous), &BTreeMap::new());
+        Some((boundary, changes))
+    }
+}
+
+/// Turns a stream of periodic full snapshots, keyed by primary key, into a CDC stream. All rows of
+/// a snapshot share its timestamp; once the watermark passes it the snapshot is complete and is
+/// compared with the previous one, emitting a row for each insert (`c`), update (`u`) and delete
+/// (`d`). Each subtask diffs only the keys partitioned to it, so a snapshot that has no rows for a
+/// subtask is only seen if the snapshot interval is configured; otherwise it's indistinguishable
+/// from no snapshot at all. Keys that haven't been in a snapshot for
+/// [`KEYED_STATE_TTL`](crate::arrow::keyed_state::KEYED_STATE_TTL) of event time are forgotten.
+pub struct SnapshotDiffFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    key_converter: Converter,
+    row_converter: Converter,
+    stored_rows: KeyedValues,
+    input: BufferedInput,
+    state: SnapshotDiffState,
+}
+
+impl SnapshotDiffFunc {
+    /// The columns that make up a row, which are every column but the timestamp.
+    fn row_columns(&self, batch: &RecordBatch) -> Vec<ArrayRef> {
+        batch
+            .columns()
+            .iter()
+            .enumerate()
+            .filter(|(i, _)| *i != self.input_schema.timestamp_index)
+            .map(|(_, c)| c.clone())
+            .collect()
+    }
+
+    fn process(&mut self, batch: &RecordBatch) -> Result<()> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        let keys = self
+            .key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())?;
+        let rows = self
+            .row_converter
+            .convert_all_columns(&self.row_columns(batch), batch.num_rows())?;
+        let timestamps = batch
+            .column(self.input_schema.timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+
+        for i in 0..batch.num_rows() {
+            self.state.add(
+                timestamps.value(i),
+                keys.row(i).as_ref().to_vec(),
+                rows.row(i).as_ref().to_vec(),
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Stores the latest complete snapshot, with tombstones for the keys deleted from the ones
+    /// before it. Every row is written, not just the changed ones, so that keys which are in every
+    /// snapshot don't expire.
+    async fn store(
+        &self,
+        results: &[(i64, Vec<(Vec<u8>, Change, Vec<u8>)>)],
+        ctx: &mut ArrowContext,
+    ) -> Result<()> {
+        let Some((latest, _)) = results.last() else {
+            return Ok(());
+        };
+
+        let mut rows: HashMap<_, _> = results
+            .iter()
+            .flat_map(|(_, changes)| changes)
+            .filter(|(_, change, _)| *change == Change::Delete)
+            .map(|(key, _, _)| (key.clone(), None))
+            .collect();
+        rows.extend(
+            self.state
+                .previous
+                .iter()
+                .map(|(key, row)| (key.clone(), Some(row.clone()))),
+        );
+
+        self.stored_rows
+            .insert(ctx, &rows, from_nanos(*latest as u128))
+            .await
+    }
+
+    fn changelog(
+        &self,
+        snapshot: i64,
+        changes: &[(Vec<u8>, Change, Vec<u8>)],
+    ) -> Result<RecordBatch> {
+        let mut columns = self
+            .row_converter
+            .convert_raw_rows(changes.iter().map(|(_, _, row)| row.as_slice()).collect())?;
+        columns.insert(
+            self.input_schema.timestamp_index,
+            Arc::new(TimestampNanosecondArray::from(vec![
+                snapshot;
+                changes.len()
+            ])),
+        );
+        columns.push(Arc::new(StringArray::from_iter_values(
+            changes.iter().map(|(_, change, _)| change.op()),
+        )));
+
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+}
+
+pub struct SnapshotDiffConstructor;
+
+impl OperatorConstructor for SnapshotDiffConstructor {
+    type ConfigT = api::SnapshotDiffOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+
+        let row_converter = Converter::new(
+            input_schema
+                .schema
+                .fields()
+                .iter()
+                .enumerate()
+                .filter(|(i, _)| *i != input_schema.timestamp_index)
+                .map(|(_, f)| SortField::new(f.data_type().clone()))
+                .collect(),
+        )?;
+
+        let mut fields = input_schema.schema.fields().to_vec();
+        fields.push(Arc::new(Field::new(config.op_field, DataType::Utf8, false)));
+
+        Ok(OperatorNode::from_operator(Box::new(SnapshotDiffFunc {
+            name: config.name,
+            key_converter: input_schema.converter(false)?,
+            row_converter,
+            output_schema: Arc::new(Schema::new(fields)),
+            stored_rows: KeyedValues::new("p", "previous snapshot rows by key", &input_schema)?,
+            input: BufferedInput::new(
+                "s",
+                "incomplete snapshot rows",
+                Duration::ZERO,
+                input_schema.clone(),
+            ),
+            input_schema,
+            state: SnapshotDiffState::new(
+                (config.snapshot_interval_micros > 0)
+                    .then(|| Duration::from_micros(config.snapshot_interval_micros)),
+            ),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for SnapshotDiffFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.stored_rows.table_config(), self.input.table_config()]
+            .into_iter()
+            .collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let stored: HashMap<Vec<u8>, Option<Vec<u8>>> = self
+            .stored_rows
+            .get_all(ctx)
+            .await
+            .expect("should be able to restore previous snapshot");
+        self.state.previous = stored
+            .into_iter()
+            .filter_map(|(key, row)| Some((key, row?)))
+            .collect();
+        self.state.completed_through = ctx
+            .last_present_watermark()
+            .map(|watermark| to_nanos(watermark) as i64);
+
+        // rows of snapshots that were already diffed are ignored
+        let batches = self
+            .input
+            .restore(ctx)
+            .await
+            .expect("should be able to restore incomplete snapshots");
+        for batch in &batches {
+            self.process(batch)
+                .expect("should be able to buffer snapshot");
+        }
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        self.process(&batch)
+            .expect("should be able to buffer snapshot");
+        self.input
+            .insert(ctx, &batch)
+            .await
+            .expect("should be able to store incomplete snapshot");
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        if let Watermark::EventTime(t) = watermark {
+            let results = self.state.advance(to_nanos(t) as i64);
+            self.store(&results, ctx)
+                .await
+                .expect("should be able to store snapshot");
+
+            for (snapshot, changes) in results {
+                if !changes.is_empty() {
+                    let batch = self
+                        .changelog(snapshot, &changes)
+                        .expect("should be able to emit snapshot changes");
+                    ctx.collect(batch).await;
+                }
+            }
+        }
+
+        self.stored_rows
+            .expire(ctx)
+            .await
+            .expect("should expire previous snapshot rows");
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+        self.input
+            .flush(ctx)
+            .await
+            .expect("should be able to flush incomplete snapshots");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn snapshot(rows: &[(&str, &str)]) -> BTreeMap<Vec<u8>, Vec<u8>> {
+        rows.iter()
+            .map(|(k, v)| (k.as_bytes().to_vec(), v.as_bytes().to_vec()))
+            .collect()
+    }
+
+    fn changes(changes: Vec<(Vec<u8>, Change, Vec<u8>)>) -> Vec<(Change, String)> {
+        changes
+            .into_iter()
+            .map(|(_, c, row)| (c, String::from_utf8(row).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_diff() {
+        let previous = snapshot(&[("1", "alice"), ("2", "bob"), ("3", "carol")]);
+        let current = snapshot(&[("1", "alice"), ("2", "robert"), ("4", "dave")]);
+
+        assert_eq!(
+            changes(diff(&previous, &current)),
+            vec![
+                (Change::Update, "robert".to_string()),
+                (Change::Delete, "carol".to_string()),
+                (Change::Insert, "dave".to_string()),
+            ]
+        );
+
+        assert!(diff(&current, &current).is_empty());
+    }
+
+    #[test]
+    fn test_snapshots_diffed_in_order() {
+        let mut state = SnapshotDiffState::new(None);
+        for (k, v) in [("1", "a"), ("2", "b")] {
+            state.add(10, k.as_bytes().to_vec(), v.as_bytes().to_vec());
+        }
+        state.add(20, b"1".to_vec(), b"a2".to_vec());
+
+        // the second snapshot isn't complete until the watermark passes it
+        let results = state.advance(15);
+        assert_eq!(results.len(), 1);
+        assert_eq!(
+            changes(results[0].1.clone()),
+            vec![
+                (Change::Insert, "a".to_string()),
+                (Change::Insert, "b".to_string()),
+            ]
+        );
+
+        state.add(20, b"3".to_vec(), b"c".to_vec());
+        // rows for a snapshot that's already been diffed are ignored
+        state.add(10, b"5".to_vec(), b"late".to_vec());
+
+        let results = state.advance(20);
+        assert_eq!(results[0].0, 20);
+        assert_eq!(
+            changes(results[0].1.clone()),
+            vec![
+                (Change::Update, "a2".to_string()),
+                (Change::Delete, "b".to_string()),
+                (Change::Insert, "c".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_empty_snapshot_deletes_every_key() {
+        let mut state = SnapshotDiffState::new(Some(Duration::from_nanos(10)));
+        for (k, v) in [("1", "a"), ("2", "b")] {
+            state.add(10, k.as_bytes().to_vec(), v.as_bytes().to_vec());
+        }
+        assert_eq!(state.advance(15).len(), 1);
+
+        // the snapshot at 20 had no rows for this subtask, so all of its keys were deleted
+        let results = state.advance(25);
+        assert_eq!(results.len(), 1);
+        assert_eq!(results[0].0, 20);
+        assert_eq!(
+            changes(results[0].1.clone()),
+            vec![
+                (Change::Delete, "a".to_string()),
+                (Change::Delete, "b".to_string()),
+            ]
+        );
+
+        // later empty snapshots have nothing left to delete
+        assert!(state.advance(35).is_empty());
+
+        state.add(40, b"1".to_vec(), b"a".to_vec());
+        let results = state.advance(45);
+        assert_eq!(
+            changes(results[0].1.clone()),
+            vec![(Change::Insert, "a".to_string())]
+        );
+
+        // without an interval, an empty snapshot can't be told apart from no snapshot
+        let mut state = SnapshotDiffState::new(None);
+        state.add(10, b"1".to_vec(), b"a".to_vec());
+        state.advance(15);
+        assert!(state.advance(25).is_empty());
+    }
+}
@@ -0,0 +1,143 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, Result};
+use arrow::compute::{filter_record_batch, not};
+use arrow_array::cast::AsArray;
+use arrow_array::{BooleanArray, RecordBatch};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::api;
+use datafusion::physical_expr::PhysicalExpr;
+use datafusion_proto::physical_plan::from_proto::parse_physical_expr;
+use datafusion_proto::physical_plan::DefaultPhysicalExtensionCodec;
+use datafusion_proto::protobuf::PhysicalExprNode;
+use prost::Message;
+
+/// The output that rows matching the predicate are sent to; all other rows go to the other output
+const MATCHING_OUTPUT: usize = 0;
+const NON_MATCHING_OUTPUT: usize = 1;
+
+/// Partitions a batch into the rows for which the predicate holds and those for which it does
+/// not. Rows where the predicate is null are treated as matching if `nulls_match` is set.
+pub(crate) fn split(
+    batch: &RecordBatch,
+    predicate: &BooleanArray,
+    nulls_match: bool,
+) -> Result<(RecordBatch, RecordBatch)> {
+    let mask: BooleanArray = predicate
+        .iter()
+        .map(|v| Some(v.unwrap_or(nulls_match)))
+        .collect();
+
+    Ok((
+        filter_record_batch(batch, &mask)?,
+        filter_record_batch(batch, &not(&mask)?)?,
+    ))
+}
+
+/// Routes each row to one of two outputs depending on a predicate. Watermarks are forwarded to
+/// both outputs.
+pub struct SplitFunc {
+    name: String,
+    predicate: Arc<dyn PhysicalExpr>,
+    nulls_match: bool,
+}
+
+pub struct SplitConstructor;
+
+impl OperatorConstructor for SplitConstructor {
+    type ConfigT = api::SplitOperator;
+
+    fn with_config(&self, config: Self::ConfigT, registry: Arc<Registry>) -> Result<OperatorNode> {
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+
+        let predicate = parse_physical_expr(
+            &PhysicalExprNode::decode(&mut config.predicate.as_slice())?,
+            registry.as_ref(),
+            &input_schema.schema,
+            &DefaultPhysicalExtensionCodec {},
+        )?;
+
+        Ok(OperatorNode::from_operator(Box::new(SplitFunc {
+            name: config.name,
+            predicate,
+            nulls_match: config.nulls_match,
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for SplitFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        let predicate = self
+            .predicate
+            .evaluate(&batch)
+            .expect("should be able to evaluate split predicate")
+            .into_array(batch.num_rows())
+            .expect("should be able to convert split predicate to array");
+
+        let (matching, non_matching) = split(&batch, predicate.as_boolean(), self.nulls_match)
+            .expect("should be able to split batch");
+
+        if matching.num_rows() > 0 {
+            ctx.collect_output(MATCHING_OUTPUT, matching).await;
+        }
+        if non_matching.num_rows() > 0 {
+            ctx.collect_output(NON_MATCHING_OUTPUT, non_matching).await;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::types::Int64Type;
+    use arrow_array::Int64Array;
+    use arrow_schema::{DataType, Field, Schema};
+
+    fn batch() -> RecordBatch {
+        RecordBatch::try_new(
+            Arc::new(Schema::new(vec![Field::new("x", DataType::Int64, false)])),
+            vec![Arc::new(Int64Array::from(vec![1, 2, 3, 4]))],
+        )
+        .unwrap()
+    }
+
+    fn values(batch: &RecordBatch) -> Vec<i64> {
+        batch
+            .column(0)
+            .as_primitive::<Int64Type>()
+            .values()
+            .to_vec()
+    }
+
+    #[test]
+    fn test_routing() {
+        let predicate = BooleanArray::from(vec![true, false, false, true]);
+        let (matching, non_matching) = split(&batch(), &predicate, false).unwrap();
+
+        assert_eq!(values(&matching), vec![1, 4]);
+        assert_eq!(values(&non_matching), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_null_predicate() {
+        let predicate = BooleanArray::from(vec![Some(true), None, Some(false), None]);
+
+        let (matching, non_matching) = split(&batch(), &predicate, false).unwrap();
+        assert_eq!(values(&matching), vec![1]);
+        assert_eq!(values(&non_matching), vec![2, 3, 4]);
+
+        let (matching, non_matching) = split(&batch(), &predicate, true).unwrap();
+        assert_eq!(values(&matching), vec![1, 2, 4]);
+        assert_eq!(values(&non_matching), vec![3]);
+    }
+}
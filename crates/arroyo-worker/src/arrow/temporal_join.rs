@@ -0,0 +1,507 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::row::SortField;
+use arrow_array::cast::AsArray;
+use arrow_array::types::TimestampNanosecondType;
+use arrow_array::RecordBatch;
+use arrow_schema::{Schema, SchemaRef};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::Converter;
+use arroyo_types::{to_nanos, CheckpointBarrier, Watermark};
+
+use crate::arrow::keyed_state::{max_timestamp, BufferedInput, KeyedValues};
+
+/// The versions of a lookup table and the stream records waiting to be joined against them. Each
+/// version of a key is valid from its timestamp until the next version of that key.
+pub(crate) struct TemporalJoinState {
+    versions: HashMap<Vec<u8>, BTreeMap<i64, Vec<u8>>>,
+    pending: BTreeMap<(i64, u64), (Vec<u8>, Vec<u8>)>,
+    next_seq: u64,
+    watermark: Option<i64>,
+}
+
+impl TemporalJoinState {
+    pub(crate) fn new() -> Self {
+        Self {
+            versions: HashMap::new(),
+            pending: BTreeMap::new(),
+            next_seq: 0,
+            watermark: None,
+        }
+    }
+
+    /// Adds a version of the table row for a key; a later version with the same validity start
+    /// replaces the earlier one.
+    pub(crate) fn add_version(&mut self, key: Vec<u8>, valid_from: i64, row: Vec<u8>) {
+        self.versions
+            .entry(key)
+            .or_default()
+            .insert(valid_from, row);
+    }
+
+    /// The table row for the key that was valid at the timestamp, if any.
+    fn lookup(&self, key: &[u8], timestamp: i64) -> Option<&Vec<u8>> {
+        self.versions
+            .get(key)?
+            .range(..=timestamp)
+            .next_back()
+            .map(|(_, row)| row)
+    }
+
+    /// Adds a stream record. Records at or before the watermark can be joined immediately, as
+    /// every table version that could apply to them has arrived; the joined `(record, table row)`
+    /// is returned. Later records are buffered until the watermark passes them.
+    pub(crate) fn add_record(
+        &mut self,
+        key: Vec<u8>,
+        timestamp: i64,
+        row: Vec<u8>,
+    ) -> Option<(Vec<u8>, Vec<u8>)> {
+        if self.watermark.is_some_and(|w| timestamp <= w) {
+            let version = self.lookup(&key, timestamp)?.clone();
+            return Some((row, version));
+        }
+
+        self.pending.insert((timestamp, self.next_seq), (key, row));
+        self.next_seq += 1;
+        None
+    }
+
+    /// Joins every buffered record at or before the watermark, returning `(record, table row)`
+    /// pairs in event-time order; records with no valid table version are dropped. Versions that
+    /// can no longer apply to any record are then evicted, keeping for each key the version valid
+    /// at the watermark and any later ones.
+    pub(crate) fn advance(&mut self, watermark: i64) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let remaining = self.pending.split_off(&(watermark.saturating_add(1), 0));
+        let ready = std::mem::replace(&mut self.pending, remaining);
+
+        let joined = ready
+            .into_iter()
+            .filter_map(|((timestamp, _), (key, row))| {
+                self.lookup(&key, timestamp)
+                    .map(|version| (row, version.clone()))
+            })
+            .collect();
+
+        for versions in self.versions.values_mut() {
+            if let Some(current) = versions.range(..=watermark).next_back().map(|(t, _)| *t) {
+                *versions = versions.split_off(&current);
+            }
+        }
+
+        self.watermark = Some(self.watermark.unwrap_or(i64::MIN).max(watermark));
+        joined
+    }
+}
+
+/// Joins a stream (the first input) against a versioned lookup table (the second input), where
+/// each table row is valid from its event time until the next row for the same key. Each stream
+/// record is joined with the table version that was valid at the record's event time, rather than
+/// the latest version, which allows joining against slowly-changing dimensions with history.
+/// Stream records without a valid table version are dropped, as are the versions of keys with no
+/// new table rows for [`KEYED_STATE_TTL`](crate::arrow::keyed_state::KEYED_STATE_TTL) of event
+/// time.
+pub struct TemporalJoinFunc {
+    name: String,
+    left_schema: ArroyoSchema,
+    right_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    right_value_indices: Vec<usize>,
+    key_converter: Converter,
+    left_converter: Converter,
+    right_converter: Converter,
+    stored_versions: KeyedValues,
+    buffered_stream: BufferedInput,
+    state: TemporalJoinState,
+}
+
+impl TemporalJoinFunc {
+    fn keys(&self, schema: &ArroyoSchema, batch: &RecordBatch) -> Result<Vec<Vec<u8>>> {
+        let key_columns: Vec<_> = schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        let keys = self
+            .key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())?;
+        Ok((0..batch.num_rows())
+            .map(|i| keys.row(i).as_ref().to_vec())
+            .collect())
+    }
+
+    fn process_left(&mut self, batch: &RecordBatch) -> Result<Option<RecordBatch>> {
+        let keys = self.keys(&self.left_schema, batch)?;
+        let rows = self
+            .left_converter
+            .convert_all_columns(batch.columns(), batch.num_rows())?;
+        let timestamps = batch
+            .column(self.left_schema.timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+
+        let joined: Vec<_> = keys
+            .into_iter()
+            .enumerate()
+            .filter_map(|(i, key)| {
+                self.state
+                    .add_record(key, timestamps.value(i), rows.row(i).as_ref().to_vec())
+            })
+            .collect();
+
+        self.output(joined)
+    }
+
+    fn process_right(&mut self, batch: &RecordBatch) -> Result<Vec<Vec<u8>>> {
+        let keys = self.keys(&self.right_schema, batch)?;
+        let value_columns: Vec<_> = self
+            .right_value_indices
+            .iter()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        let rows = self
+            .right_converter
+            .convert_all_columns(&value_columns, batch.num_rows())?;
+        let timestamps = batch
+            .column(self.right_schema.timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+
+        for (i, key) in keys.iter().enumerate() {
+            self.state.add_version(
+                key.clone(),
+                timestamps.value(i),
+                rows.row(i).as_ref().to_vec(),
+            );
+        }
+
+        Ok(keys)
+    }
+
+    /// Adds the batch's table versions, and stores the versions of each key it updated.
+    async fn process_right_with_state(
+        &mut self,
+        batch: &RecordBatch,
+        ctx: &mut ArrowContext,
+    ) -> Result<()> {
+        let keys = self.process_right(batch)?;
+        let Some(timestamp) = max_timestamp(batch, self.right_schema.timestamp_index) else {
+            return Ok(());
+        };
+
+        let updated: HashMap<_, _> = keys
+            .into_iter()
+            .filter_map(|key| {
+                let versions = self.state.versions.get(&key)?.clone();
+                Some((key, versions))
+            })
+            .collect();
+        self.stored_versions.insert(ctx, &updated, timestamp).await
+    }
+
+    fn output(&self, joined: Vec<(Vec<u8>, Vec<u8>)>) -> Result<Option<RecordBatch>> {
+        if joined.is_empty() {
+            return Ok(None);
+        }
+
+        let mut columns = self
+            .left_converter
+            .convert_raw_rows(joined.iter().map(|(l, _)| l.as_slice()).collect())?;
+        columns.extend(
+            self.right_converter
+                .convert_raw_rows(joined.iter().map(|(_, r)| r.as_slice()).collect())?,
+        );
+
+        Ok(Some(RecordBatch::try_new(
+            self.output_schema.clone(),
+            columns,
+        )?))
+    }
+}
+
+pub struct TemporalJoinConstructor;
+
+impl OperatorConstructor for TemporalJoinConstructor {
+    type ConfigT = api::TemporalJoinOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        let left_schema: ArroyoSchema = config
+            .left_schema
+            .ok_or_else(|| anyhow!("missing left schema"))?
+            .try_into()?;
+        let right_schema: ArroyoSchema = config
+            .right_schema
+            .ok_or_else(|| anyhow!("missing right schema"))?
+            .try_into()?;
+
+        let key_types = |schema: &ArroyoSchema| {
+            schema
+                .key_indices
+                .iter()
+                .flatten()
+                .map(|i| schema.schema.field(*i).data_type().clone())
+                .collect::<Vec<_>>()
+        };
+        let left_key_types = key_types(&left_schema);
+        if left_key_types.is_empty() {
+            bail!("temporal join requires a key");
+        }
+        if left_key_types != key_types(&right_schema) {
+            bail!("temporal join keys must have the same types on both sides");
+        }
+
+        let right_value_indices = right_schema.value_indices(false);
+        let right_converter = Converter::new(
+            right_value_indices
+                .iter()
+                .map(|i| SortField::new(right_schema.schema.field(*i).data_type().clone()))
+                .collect(),
+        )?;
+        let left_converter = Converter::new(
+            left_schema
+                .schema
+                .fields()
+                .iter()
+                .map(|f| SortField::new(f.data_type().clone()))
+                .collect(),
+        )?;
+
+        let mut fields = left_schema.schema.fields().to_vec();
+        fields.extend(
+            right_value_indices
+                .iter()
+                .map(|i| right_schema.schema.fields()[*i].clone()),
+        );
+
+        Ok(OperatorNode::from_operator(Box::new(TemporalJoinFunc {
+            name: config.name,
+            key_converter: left_schema.converter(false)?,
+            left_converter,
+            right_converter,
+            output_schema: Arc::new(Schema::new(fields)),
+            stored_versions: KeyedValues::new("v", "temporal join table versions", &right_schema)?,
+            buffered_stream: BufferedInput::new(
+                "p",
+                "temporal join stream input",
+                Duration::ZERO,
+                left_schema.clone(),
+            ),
+            left_schema,
+            right_schema,
+            right_value_indices,
+            state: TemporalJoinState::new(),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for TemporalJoinFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [
+            self.stored_versions.table_config(),
+            self.buffered_stream.table_config(),
+        ]
+        .into_iter()
+        .collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        self.state.versions = self
+            .stored_versions
+            .get_all(ctx)
+            .await
+            .expect("should be able to restore temporal join versions");
+        if let Some(watermark) = ctx.last_present_watermark() {
+            self.state.advance(to_nanos(watermark) as i64);
+        }
+
+        // the stream records that were still pending are those after the restored watermark;
+        // the rest were already joined, so their results are discarded
+        let batches = self
+            .buffered_stream
+            .restore(ctx)
+            .await
+            .expect("should be able to restore temporal join stream input");
+        for batch in &batches {
+            self.process_left(batch)
+                .expect("should be able to process temporal join stream");
+        }
+    }
+
+    async fn process_batch(&mut self, _: RecordBatch, _: &mut ArrowContext) {
+        unreachable!();
+    }
+
+    async fn process_batch_index(
+        &mut self,
+        index: usize,
+        total_inputs: usize,
+        batch: RecordBatch,
+        ctx: &mut ArrowContext,
+    ) {
+        match index / (total_inputs / 2) {
+            0 => {
+                if let Some(joined) = self
+                    .process_left(&batch)
+                    .expect("should be able to process temporal join stream")
+                {
+                    ctx.collect(joined).await;
+                }
+                self.buffered_stream
+                    .insert(ctx, &batch)
+                    .await
+                    .expect("should be able to buffer temporal join stream");
+            }
+            1 => self
+                .process_right_with_state(&batch, ctx)
+                .await
+                .expect("should be able to process temporal join table"),
+            _ => unreachable!(),
+        }
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        ctx: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        if let Watermark::EventTime(t) = watermark {
+            let joined = self.state.advance(to_nanos(t) as i64);
+            if let Some(batch) = self
+                .output(joined)
+                .expect("should be able to emit temporal join results")
+            {
+                ctx.collect(batch).await;
+            }
+        }
+
+        self.stored_versions
+            .expire(ctx)
+            .await
+            .expect("should expire temporal join versions");
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+        self.buffered_stream
+            .flush(ctx)
+            .await
+            .expect("should be able to flush temporal join stream input");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rows(joined: Vec<(Vec<u8>, Vec<u8>)>) -> Vec<(String, String)> {
+        joined
+            .into_iter()
+            .map(|(l, r)| (String::from_utf8(l).unwrap(), String::from_utf8(r).unwrap()))
+            .collect()
+    }
+
+    #[test]
+    fn test_joins_version_valid_at_event_time() {
+        let mut state = TemporalJoinState::new();
+        state.add_version(b"usd".to_vec(), 0, b"rate 1.0".to_vec());
+        state.add_version(b"usd".to_vec(), 100, b"rate 1.1".to_vec());
+        state.add_version(b"usd".to_vec(), 200, b"rate 1.2".to_vec());
+
+        assert!(state
+            .add_record(b"usd".to_vec(), 150, b"order a".to_vec())
+            .is_none());
+        assert!(state
+            .add_record(b"usd".to_vec(), 50, b"order b".to_vec())
+            .is_none());
+        assert!(state
+            .add_record(b"usd".to_vec(), 250, b"order c".to_vec())
+            .is_none());
+        // no version of the key is valid this early
+        assert!(state
+            .add_record(b"usd".to_vec(), -10, b"order d".to_vec())
+            .is_none());
+        assert!(state
+            .add_record(b"eur".to_vec(), 150, b"order e".to_vec())
+            .is_none());
+
+        assert_eq!(
+            rows(state.advance(300)),
+            vec![
+                ("order b".to_string(), "rate 1.0".to_string()),
+                ("order a".to_string(), "rate 1.1".to_string()),
+                ("order c".to_string(), "rate 1.2".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_waits_for_watermark() {
+        let mut state = TemporalJoinState::new();
+        state.add_version(b"k".to_vec(), 0, b"v1".to_vec());
+        state.add_record(b"k".to_vec(), 150, b"r".to_vec());
+
+        // a newer version could still arrive for the record's time, so it isn't joined yet
+        assert!(state.advance(100).is_empty());
+        state.add_version(b"k".to_vec(), 120, b"v2".to_vec());
+
+        assert_eq!(
+            rows(state.advance(200)),
+            vec![("r".to_string(), "v2".to_string())]
+        );
+
+        // records behind the watermark are joined as they arrive
+        assert_eq!(
+            state.add_record(b"k".to_vec(), 180, b"s".to_vec()),
+            Some((b"s".to_vec(), b"v2".to_vec()))
+        );
+    }
+
+    #[test]
+    fn test_evicts_superseded_versions() {
+        let mut state = TemporalJoinState::new();
+        for (t, v) in [(0, "v1"), (100, "v2"), (200, "v3"), (300, "v4")] {
+            state.add_version(b"k".to_vec(), t, v.as_bytes().to_vec());
+        }
+
+        state.advance(250);
+        assert_eq!(
+            state.versions[b"k".as_slice()]
+                .keys()
+                .copied()
+                .collect::<Vec<_>>(),
+            vec![200, 300]
+        );
+    }
+
+    #[test]
+    fn test_replay_after_restore() {
+        let mut restored = TemporalJoinState::new();
+        restored.add_version(b"k".to_vec(), 0, b"v1".to_vec());
+        restored.add_version(b"k".to_vec(), 120, b"v2".to_vec());
+        restored.advance(100);
+
+        // records up to the restored watermark were joined before the restore, so replaying
+        // them joins them again immediately, and those results are discarded
+        assert!(restored
+            .add_record(b"k".to_vec(), 90, b"r".to_vec())
+            .is_some());
+        assert!(restored
+            .add_record(b"k".to_vec(), 150, b"s".to_vec())
+            .is_none());
+
+        assert_eq!(
+            rows(restored.advance(200)),
+            vec![("s".to_string(), "v2".to_string())]
+        );
+    }
+}
@@ -0,0 +1,191 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::{bail, Result};
+use arrow_array::{Float64Array, RecordBatch, TimestampNanosecondArray, UInt64Array};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::grpc::api;
+use arroyo_rpc::TIMESTAMP_FIELD;
+use arroyo_types::to_nanos;
+
+/// The records and bytes seen over one interval, and the rates they correspond to.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub(crate) struct Throughput {
+    records: u64,
+    bytes: u64,
+    records_per_second: f64,
+    bytes_per_second: f64,
+}
+
+/// Counts records and bytes since the start of the current interval.
+pub(crate) struct ThroughputState {
+    records: u64,
+    bytes: u64,
+    since: Instant,
+}
+
+impl ThroughputState {
+    pub(crate) fn new(now: Instant) -> Self {
+        Self {
+            records: 0,
+            bytes: 0,
+            since: now,
+        }
+    }
+
+    pub(crate) fn record(&mut self, records: usize, bytes: usize) {
+        self.records += records as u64;
+        self.bytes += bytes as u64;
+    }
+
+    /// Returns the throughput since the start of the interval and starts a new one.
+    pub(crate) fn finish_interval(&mut self, now: Instant) -> Throughput {
+        let elapsed = now.saturating_duration_since(self.since).as_secs_f64();
+        let rate = |count: u64| {
+            if elapsed > 0.0 {
+                count as f64 / elapsed
+            } else {
+                0.0
+            }
+        };
+
+        let throughput = Throughput {
+            records: self.records,
+            bytes: self.bytes,
+            records_per_second: rate(self.records),
+            bytes_per_second: rate(self.bytes),
+        };
+
+        *self = Self::new(now);
+        throughput
+    }
+}
+
+/// Measures the throughput of its input, emitting a row each `tick_interval` with the number of
+/// records and bytes (by in-memory size) received during the interval and the corresponding
+/// per-second rates. The input itself isn't forwarded, so the operator is attached to a fork of
+/// the stream being measured.
+pub struct ThroughputFunc {
+    name: String,
+    interval: Duration,
+    schema: SchemaRef,
+    state: ThroughputState,
+}
+
+impl ThroughputFunc {
+    fn to_batch(&self, task_index: usize, throughput: Throughput) -> Result<RecordBatch> {
+        Ok(RecordBatch::try_new(
+            self.schema.clone(),
+            vec![
+                Arc::new(UInt64Array::from(vec![task_index as u64])),
+                Arc::new(UInt64Array::from(vec![throughput.records])),
+                Arc::new(UInt64Array::from(vec![throughput.bytes])),
+                Arc::new(Float64Array::from(vec![throughput.records_per_second])),
+                Arc::new(Float64Array::from(vec![throughput.bytes_per_second])),
+                Arc::new(TimestampNanosecondArray::from(vec![
+                    to_nanos(SystemTime::now()) as i64,
+                ])),
+            ],
+        )?)
+    }
+}
+
+pub struct ThroughputConstructor;
+
+impl OperatorConstructor for ThroughputConstructor {
+    type ConfigT = api::ThroughputOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        if config.interval_micros == 0 {
+            bail!("throughput interval must be greater than 0");
+        }
+
+        let schema = Arc::new(Schema::new(vec![
+            Field::new("subtask", DataType::UInt64, false),
+            Field::new("records", DataType::UInt64, false),
+            Field::new("bytes", DataType::UInt64, false),
+            Field::new("records_per_second", DataType::Float64, false),
+            Field::new("bytes_per_second", DataType::Float64, false),
+            Field::new(
+                TIMESTAMP_FIELD,
+                DataType::Timestamp(TimeUnit::Nanosecond, None),
+                false,
+            ),
+        ]));
+
+        Ok(OperatorNode::from_operator(Box::new(ThroughputFunc {
+            name: config.name,
+            interval: Duration::from_micros(config.interval_micros),
+            schema,
+            state: ThroughputState::new(Instant::now()),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for ThroughputFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(self.interval)
+    }
+
+    async fn on_start(&mut self, _: &mut ArrowContext) {
+        self.state = ThroughputState::new(Instant::now());
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, _: &mut ArrowContext) {
+        self.state
+            .record(batch.num_rows(), batch.get_array_memory_size());
+    }
+
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) {
+        let throughput = self.state.finish_interval(Instant::now());
+        let batch = self
+            .to_batch(ctx.task_info.task_index, throughput)
+            .expect("should be able to build throughput batch");
+        ctx.collect(batch).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_rates_per_interval() {
+        let start = Instant::now();
+        let mut state = ThroughputState::new(start);
+
+        state.record(100, 4000);
+        state.record(50, 2000);
+        assert_eq!(
+            state.finish_interval(start + Duration::from_secs(2)),
+            Throughput {
+                records: 150,
+                bytes: 6000,
+                records_per_second: 75.0,
+                bytes_per_second: 3000.0,
+            }
+        );
+
+        // counters are reset for the next interval
+        state.record(10, 100);
+        assert_eq!(
+            state.finish_interval(start + Duration::from_millis(2500)),
+            Throughput {
+                records: 10,
+                bytes: 100,
+                records_per_second: 20.0,
+                bytes_per_second: 200.0,
+            }
+        );
+
+        let idle = state.finish_interval(start + Duration::from_secs(4));
+        assert_eq!((idle.records, idle.records_per_second), (0, 0.0));
+    }
+}
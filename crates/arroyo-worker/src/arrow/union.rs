@@ -0,0 +1,251 @@
+use std::sync::Arc;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::compute::{can_cast_types, cast};
+use arrow_array::{Array, RecordBatch};
+use arrow_schema::{Schema, SchemaRef};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::api;
+use arroyo_types::UserError;
+
+/// Checks that batches with the input schema can be reconciled to the output schema, which
+/// requires every output column to be present in the input with a type that can be cast to the
+/// output's type. Whether the values themselves fit can only be checked as batches arrive.
+pub(crate) fn check_compatible(input: &Schema, output: &Schema) -> Result<()> {
+    for field in output.fields() {
+        let Ok(input_field) = input.field_with_name(field.name()) else {
+            bail!("union input is missing column '{}'", field.name());
+        };
+
+        if !can_cast_types(input_field.data_type(), field.data_type()) {
+            bail!(
+                "union input column '{}' has type {}, which can't be cast to the output type {}",
+                field.name(),
+                input_field.data_type(),
+                field.data_type()
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Reconciles a batch from any of the inputs to the common output schema, matching columns by
+/// name so that inputs may order their columns differently, and casting columns whose types or
+/// nullability differ from the output.
+pub(crate) fn reconcile(batch: &RecordBatch, schema: &SchemaRef) -> Result<RecordBatch> {
+    if batch.schema() == *schema {
+        return Ok(batch.clone());
+    }
+
+    let columns = schema
+        .fields()
+        .iter()
+        .map(|field| {
+            let column = batch
+                .column_by_name(field.name())
+                .ok_or_else(|| anyhow!("union input is missing column '{}'", field.name()))?;
+
+            if !field.is_nullable() && column.null_count() > 0 {
+                bail!(
+                    "union input has nulls in column '{}', which is not nullable in the output",
+                    field.name()
+                );
+            }
+
+            if column.data_type() == field.data_type() {
+                Ok(column.clone())
+            } else {
+                Ok(cast(column, field.data_type())?)
+            }
+        })
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(RecordBatch::try_new(schema.clone(), columns)?)
+}
+
+pub struct UnionFunc {
+    name: String,
+    output_schema: SchemaRef,
+}
+
+pub struct UnionConstructor;
+
+impl OperatorConstructor for UnionConstructor {
+    type ConfigT = api::UnionOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        let output_schema: ArroyoSchema = config
+            .output_schema
+            .ok_or_else(|| anyhow!("missing output schema"))?
+            .try_into()?;
+
+        for input_schema in config.input_schemas {
+            let input_schema: ArroyoSchema = input_schema.try_into()?;
+            check_compatible(&input_schema.schema, &output_schema.schema)?;
+        }
+
+        Ok(OperatorNode::from_operator(Box::new(UnionFunc {
+            name: config.name,
+            output_schema: output_schema.schema,
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for UnionFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    // watermarks are merged across all inputs by the context's watermark holder, which only
+    // advances to the minimum of the inputs, so the default watermark handling is sufficient
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        // the schemas were checked up front, so this only fails on the data itself (like nulls in
+        // a column that isn't nullable in the output, or values that don't fit the output type)
+        match reconcile(&batch, &self.output_schema) {
+            Ok(batch) => ctx.collect(batch).await,
+            Err(e) => {
+                ctx.report_user_error(UserError::new(
+                    "union input does not match the output schema; dropping batch",
+                    format!("{:?}", e),
+                ))
+                .await;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use arrow_array::{Int32Array, Int64Array, StringArray};
+    use arrow_schema::{DataType, Field, Schema};
+    use arroyo_operator::context::WatermarkHolder;
+    use arroyo_types::Watermark;
+    use std::time::{Duration, SystemTime};
+
+    fn output_schema() -> SchemaRef {
+        Arc::new(Schema::new(vec![
+            Field::new("id", DataType::Int64, false),
+            Field::new("name", DataType::Utf8, true),
+        ]))
+    }
+
+    #[test]
+    fn test_reconcile_reordered_inputs() {
+        let schema = output_schema();
+
+        let left = RecordBatch::try_new(
+            schema.clone(),
+            vec![
+                Arc::new(Int64Array::from(vec![1, 2])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        )
+        .unwrap();
+
+        // columns in a different order, with a narrower id type and non-nullable name
+        let right = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("name", DataType::Utf8, false),
+                Field::new("id", DataType::Int32, false),
+            ])),
+            vec![
+                Arc::new(StringArray::from(vec!["c"])),
+                Arc::new(Int32Array::from(vec![3])),
+            ],
+        )
+        .unwrap();
+
+        let left = reconcile(&left, &schema).unwrap();
+        let right = reconcile(&right, &schema).unwrap();
+
+        assert_eq!(right.schema(), schema);
+        let merged = arrow::compute::concat_batches(&schema, [&left, &right]).unwrap();
+        assert_eq!(
+            merged,
+            RecordBatch::try_new(
+                schema.clone(),
+                vec![
+                    Arc::new(Int64Array::from(vec![1, 2, 3])),
+                    Arc::new(StringArray::from(vec!["a", "b", "c"])),
+                ],
+            )
+            .unwrap()
+        );
+    }
+
+    #[test]
+    fn test_reconcile_rejects_nulls_in_required_column() {
+        let batch = RecordBatch::try_new(
+            Arc::new(Schema::new(vec![
+                Field::new("id", DataType::Int64, true),
+                Field::new("name", DataType::Utf8, true),
+            ])),
+            vec![
+                Arc::new(Int64Array::from(vec![Some(1), None])),
+                Arc::new(StringArray::from(vec!["a", "b"])),
+            ],
+        )
+        .unwrap();
+
+        assert!(reconcile(&batch, &output_schema()).is_err());
+    }
+
+    #[test]
+    fn test_check_compatible() {
+        let schema = output_schema();
+
+        // reordered columns and a narrower type are fine, as are extra columns
+        let input = Schema::new(vec![
+            Field::new("extra", DataType::Boolean, true),
+            Field::new("name", DataType::Utf8, true),
+            Field::new("id", DataType::Int32, true),
+        ]);
+        assert!(check_compatible(&input, &schema).is_ok());
+
+        let missing = Schema::new(vec![Field::new("id", DataType::Int64, false)]);
+        assert!(check_compatible(&missing, &schema).is_err());
+
+        let uncastable = Schema::new(vec![
+            Field::new(
+                "id",
+                DataType::List(Arc::new(Field::new("item", DataType::Int64, true))),
+                false,
+            ),
+            Field::new("name", DataType::Utf8, true),
+        ]);
+        assert!(check_compatible(&uncastable, &schema).is_err());
+    }
+
+    #[test]
+    fn test_merged_watermark() {
+        let start = SystemTime::UNIX_EPOCH;
+        let mut watermarks = WatermarkHolder::new(vec![None, None]);
+
+        // no watermark until every input has reported one
+        assert_eq!(
+            watermarks.set(0, Watermark::EventTime(start + Duration::from_secs(10))),
+            Some(None)
+        );
+
+        assert_eq!(
+            watermarks.set(1, Watermark::EventTime(start + Duration::from_secs(5))),
+            Some(Some(Watermark::EventTime(start + Duration::from_secs(5))))
+        );
+
+        // the slower input holds back the merged watermark
+        assert_eq!(
+            watermarks.set(0, Watermark::EventTime(start + Duration::from_secs(20))),
+            Some(Some(Watermark::EventTime(start + Duration::from_secs(5))))
+        );
+
+        assert_eq!(
+            watermarks.set(1, Watermark::EventTime(start + Duration::from_secs(15))),
+            Some(Some(Watermark::EventTime(start + Duration::from_secs(15))))
+        );
+    }
+}
@@ -0,0 +1,177 @@
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use anyhow::Result;
+use arrow_array::RecordBatch;
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::grpc::api;
+use arroyo_types::{print_time, Watermark};
+use tracing::warn;
+
+/// Tracks when the watermark last advanced, to detect inputs that are stuck (or idle without
+/// having declared themselves idle).
+pub(crate) struct WatermarkStallState {
+    timeout: Duration,
+    watermark: Option<SystemTime>,
+    last_advanced: Instant,
+    idle: bool,
+    alerted: bool,
+}
+
+impl WatermarkStallState {
+    pub(crate) fn new(timeout: Duration, now: Instant) -> Self {
+        Self {
+            timeout,
+            watermark: None,
+            last_advanced: now,
+            idle: false,
+            alerted: false,
+        }
+    }
+
+    pub(crate) fn observe(&mut self, watermark: Watermark, now: Instant) {
+        match watermark {
+            Watermark::EventTime(t) => {
+                self.idle = false;
+                if self.watermark.map(|w| t > w).unwrap_or(true) {
+                    self.watermark = Some(t);
+                    self.last_advanced = now;
+                    self.alerted = false;
+                }
+            }
+            Watermark::Idle => {
+                // an input that has declared itself idle is not stalled
+                self.idle = true;
+                self.last_advanced = now;
+                self.alerted = false;
+            }
+        }
+    }
+
+    /// Returns how long the watermark has been stalled, if that exceeds the timeout and the stall
+    /// has not already been alerted on.
+    pub(crate) fn check(&mut self, now: Instant) -> Option<Duration> {
+        let stalled_for = now.saturating_duration_since(self.last_advanced);
+        if self.idle || self.alerted || stalled_for < self.timeout {
+            return None;
+        }
+
+        self.alerted = true;
+        Some(stalled_for)
+    }
+}
+
+pub struct WatermarkStallFunc {
+    name: String,
+    check_interval: Duration,
+    state: WatermarkStallState,
+}
+
+pub struct WatermarkStallConstructor;
+
+impl OperatorConstructor for WatermarkStallConstructor {
+    type ConfigT = api::WatermarkStallOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        let timeout = Duration::from_micros(config.timeout_micros);
+
+        Ok(OperatorNode::from_operator(Box::new(WatermarkStallFunc {
+            name: config.name,
+            check_interval: (timeout / 10).max(Duration::from_millis(100)),
+            state: WatermarkStallState::new(timeout, Instant::now()),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for WatermarkStallFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tick_interval(&self) -> Option<Duration> {
+        Some(self.check_interval)
+    }
+
+    async fn on_start(&mut self, _: &mut ArrowContext) {
+        self.state.last_advanced = Instant::now();
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        ctx.collect(batch).await;
+    }
+
+    async fn handle_tick(&mut self, _: u64, ctx: &mut ArrowContext) {
+        let Some(stalled_for) = self.state.check(Instant::now()) else {
+            return;
+        };
+
+        let details = format!(
+            "watermark has not advanced in {:?} (current watermark: {})",
+            stalled_for,
+            self.state
+                .watermark
+                .map(print_time)
+                .unwrap_or_else(|| "none".to_string())
+        );
+        warn!("[{}] {}", ctx.task_info.task_index, details);
+        ctx.report_error("Watermark stalled", details).await;
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        _: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        self.state.observe(watermark, Instant::now());
+        Some(watermark)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_alert_after_timeout() {
+        let start = Instant::now();
+        let secs = |s| start + Duration::from_secs(s);
+        let wm = |s| Watermark::EventTime(SystemTime::UNIX_EPOCH + Duration::from_secs(s));
+
+        let mut state = WatermarkStallState::new(Duration::from_secs(10), start);
+        state.observe(wm(100), secs(0));
+        assert_eq!(state.check(secs(5)), None);
+
+        // a repeated watermark doesn't count as progress
+        state.observe(wm(100), secs(6));
+        assert_eq!(state.check(secs(9)), None);
+        assert_eq!(state.check(secs(10)), Some(Duration::from_secs(10)));
+
+        // only alerts once per stall
+        assert_eq!(state.check(secs(15)), None);
+
+        // advancing resets the stall
+        state.observe(wm(101), secs(20));
+        assert_eq!(state.check(secs(25)), None);
+        assert_eq!(state.check(secs(31)), Some(Duration::from_secs(11)));
+    }
+
+    #[test]
+    fn test_idle_is_not_stalled() {
+        let start = Instant::now();
+        let mut state = WatermarkStallState::new(Duration::from_secs(10), start);
+
+        state.observe(Watermark::Idle, start);
+        assert_eq!(state.check(start + Duration::from_secs(60)), None);
+
+        state.observe(
+            Watermark::EventTime(SystemTime::UNIX_EPOCH),
+            start + Duration::from_secs(60),
+        );
+        assert_eq!(
+            state.check(start + Duration::from_secs(70)),
+            Some(Duration::from_secs(10))
+        );
+    }
+}
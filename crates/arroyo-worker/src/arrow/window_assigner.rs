@@ -0,0 +1,116 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::compute::kernels::arity::unary;
+use arrow_array::cast::AsArray;
+use arrow_array::types::TimestampNanosecondType;
+use arrow_array::{RecordBatch, TimestampNanosecondArray};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::api;
+
+/// Computes the start of the tumbling window containing each timestamp.
+pub(crate) fn window_starts(
+    timestamps: &TimestampNanosecondArray,
+    width: Duration,
+) -> TimestampNanosecondArray {
+    let width = width.as_nanos() as i64;
+    unary(timestamps, |t| t - t.rem_euclid(width))
+}
+
+/// Assigns each record to a tumbling window by appending the window's start time, without
+/// holding any state.
+pub struct WindowAssignerFunc {
+    name: String,
+    width: Duration,
+    timestamp_index: usize,
+    output_schema: SchemaRef,
+}
+
+pub struct WindowAssignerConstructor;
+
+impl OperatorConstructor for WindowAssignerConstructor {
+    type ConfigT = api::WindowAssignerOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        if config.width_micros == 0 {
+            bail!("window width must be greater than 0");
+        }
+
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+
+        let mut fields = input_schema.schema.fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            config.window_field,
+            DataType::Timestamp(TimeUnit::Nanosecond, None),
+            false,
+        )));
+
+        Ok(OperatorNode::from_operator(Box::new(WindowAssignerFunc {
+            name: config.name,
+            width: Duration::from_micros(config.width_micros),
+            timestamp_index: input_schema.timestamp_index,
+            output_schema: Arc::new(Schema::new(fields)),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for WindowAssignerFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        let timestamps = batch
+            .column(self.timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(window_starts(timestamps, self.width)));
+
+        ctx.collect(
+            RecordBatch::try_new(self.output_schema.clone(), columns)
+                .expect("should be able to construct window assignment batch"),
+        )
+        .await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_window_boundaries() {
+        let second = 1_000_000_000;
+        let timestamps = TimestampNanosecondArray::from(vec![
+            0,
+            1,
+            10 * second - 1,
+            10 * second,
+            10 * second + 1,
+            25 * second,
+            -1,
+        ]);
+
+        assert_eq!(
+            window_starts(&timestamps, Duration::from_secs(10)),
+            TimestampNanosecondArray::from(vec![
+                0,
+                0,
+                0,
+                10 * second,
+                10 * second,
+                20 * second,
+                -10 * second,
+            ])
+        );
+    }
+}
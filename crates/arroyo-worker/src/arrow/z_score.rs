@@ -0,0 +1,331 @@
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail, Result};
+use arrow::compute::cast;
+use arrow_array::cast::AsArray;
+use arrow_array::types::{Float64Type, TimestampNanosecondType};
+use arrow_array::{Array, BooleanArray, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef};
+use arroyo_operator::context::ArrowContext;
+use arroyo_operator::operator::{ArrowOperator, OperatorConstructor, OperatorNode, Registry};
+use arroyo_rpc::df::ArroyoSchema;
+use arroyo_rpc::grpc::{api, TableConfig};
+use arroyo_rpc::Converter;
+use arroyo_types::{to_nanos, CheckpointBarrier, Watermark};
+
+use crate::arrow::keyed_state::BufferedInput;
+
+/// The values for a key within the window, ordered by timestamp (with an arrival sequence number
+/// to keep duplicate timestamps distinct), along with their running sum and sum of squares.
+#[derive(Default)]
+pub(crate) struct WindowStats {
+    values: BTreeMap<(i64, u64), f64>,
+    sum: f64,
+    sum_of_squares: f64,
+    next_seq: u64,
+}
+
+impl WindowStats {
+    fn insert(&mut self, timestamp: i64, value: f64) {
+        self.values.insert((timestamp, self.next_seq), value);
+        self.next_seq += 1;
+        self.sum += value;
+        self.sum_of_squares += value * value;
+    }
+
+    /// Drops all values with timestamps before `start`.
+    fn evict(&mut self, start: i64) {
+        let retained = self.values.split_off(&(start, 0));
+        for value in std::mem::replace(&mut self.values, retained).into_values() {
+            self.sum -= value;
+            self.sum_of_squares -= value * value;
+        }
+    }
+
+    /// The z-score of `value` relative to the values in the window, if there are enough values
+    /// with non-zero variance to compute one.
+    fn z_score(&self, value: f64) -> Option<f64> {
+        let n = self.values.len() as f64;
+        if n < 2.0 {
+            return None;
+        }
+        let mean = self.sum / n;
+        let variance = (self.sum_of_squares / n - mean * mean).max(0.0);
+        (variance > 0.0).then(|| (value - mean) / variance.sqrt())
+    }
+}
+
+pub(crate) struct ZScoreState {
+    width: i64,
+    threshold: f64,
+    windows: HashMap<Vec<u8>, WindowStats>,
+}
+
+impl ZScoreState {
+    pub(crate) fn new(width: Duration, threshold: f64) -> Self {
+        Self {
+            width: width.as_nanos() as i64,
+            threshold,
+            windows: HashMap::new(),
+        }
+    }
+
+    /// Scores the value against the key's window of preceding values, then adds it to the window.
+    /// Returns whether the value's absolute z-score exceeds the threshold.
+    pub(crate) fn check(&mut self, key: Vec<u8>, timestamp: i64, value: f64) -> bool {
+        let stats = self.windows.entry(key).or_default();
+        let latest = stats
+            .values
+            .keys()
+            .next_back()
+            .map(|(t, _)| *t)
+            .unwrap_or(i64::MIN)
+            .max(timestamp);
+        stats.evict(latest.saturating_sub(self.width) + 1);
+
+        let anomaly = stats
+            .z_score(value)
+            .is_some_and(|z| z.abs() > self.threshold);
+        stats.insert(timestamp, value);
+        anomaly
+    }
+
+    /// Drops the values that have fallen out of the window as of the watermark, along with the
+    /// windows of keys that have no values left.
+    pub(crate) fn expire(&mut self, watermark: i64) {
+        let start = watermark.saturating_sub(self.width) + 1;
+        self.windows.retain(|_, stats| {
+            stats.evict(start);
+            !stats.values.is_empty()
+        });
+    }
+}
+
+/// Flags values whose z-score, relative to the other values for their key in a sliding window of
+/// event time, exceeds a threshold. The flag is appended to each record as a new column; records
+/// with a null value are never flagged, and don't contribute to the window. Values also leave the
+/// window once the watermark has passed it, so late records are only scored against the values
+/// that remain.
+pub struct ZScoreFunc {
+    name: String,
+    input_schema: ArroyoSchema,
+    output_schema: SchemaRef,
+    value_index: usize,
+    key_converter: Converter,
+    input: BufferedInput,
+    state: ZScoreState,
+}
+
+impl ZScoreFunc {
+    fn process(&mut self, batch: &RecordBatch) -> Result<RecordBatch> {
+        let key_columns: Vec<_> = self
+            .input_schema
+            .key_indices
+            .iter()
+            .flatten()
+            .map(|i| batch.column(*i).clone())
+            .collect();
+        let keys = self
+            .key_converter
+            .convert_all_columns(&key_columns, batch.num_rows())?;
+
+        let timestamps = batch
+            .column(self.input_schema.timestamp_index)
+            .as_primitive::<TimestampNanosecondType>();
+        let values = cast(batch.column(self.value_index), &DataType::Float64)?;
+        let values = values.as_primitive::<Float64Type>();
+
+        let anomalies: BooleanArray = (0..batch.num_rows())
+            .map(|i| {
+                Some(
+                    !values.is_null(i)
+                        && self.state.check(
+                            keys.row(i).as_ref().to_vec(),
+                            timestamps.value(i),
+                            values.value(i),
+                        ),
+                )
+            })
+            .collect();
+
+        let mut columns = batch.columns().to_vec();
+        columns.push(Arc::new(anomalies));
+        Ok(RecordBatch::try_new(self.output_schema.clone(), columns)?)
+    }
+}
+
+pub struct ZScoreConstructor;
+
+impl OperatorConstructor for ZScoreConstructor {
+    type ConfigT = api::ZScoreOperator;
+
+    fn with_config(&self, config: Self::ConfigT, _registry: Arc<Registry>) -> Result<OperatorNode> {
+        if config.width_micros == 0 {
+            bail!("z-score window width must be greater than 0");
+        }
+        if !(config.threshold > 0.0 && config.threshold.is_finite()) {
+            bail!("z-score threshold must be a positive number");
+        }
+
+        let input_schema: ArroyoSchema = config
+            .input_schema
+            .ok_or_else(|| anyhow!("missing input schema"))?
+            .try_into()?;
+        let value_index = input_schema.schema.index_of(&config.value_field)?;
+
+        let mut fields = input_schema.schema.fields().to_vec();
+        fields.push(Arc::new(Field::new(
+            config.anomaly_field,
+            DataType::Boolean,
+            false,
+        )));
+
+        Ok(OperatorNode::from_operator(Box::new(ZScoreFunc {
+            name: config.name,
+            key_converter: input_schema.converter(false)?,
+            output_schema: Arc::new(Schema::new(fields)),
+            input: BufferedInput::new(
+                "i",
+                "z-score input",
+                Duration::from_micros(config.width_micros),
+                input_schema.clone(),
+            ),
+            input_schema,
+            value_index,
+            state: ZScoreState::new(Duration::from_micros(config.width_micros), config.threshold),
+        })))
+    }
+}
+
+#[async_trait::async_trait]
+impl ArrowOperator for ZScoreFunc {
+    fn name(&self) -> String {
+        self.name.clone()
+    }
+
+    fn tables(&self) -> HashMap<String, TableConfig> {
+        [self.input.table_config()].into_iter().collect()
+    }
+
+    async fn on_start(&mut self, ctx: &mut ArrowContext) {
+        let batches = self
+            .input
+            .restore(ctx)
+            .await
+            .expect("should be able to restore z-score input");
+        for batch in &batches {
+            self.process(batch)
+                .expect("should be able to compute z-scores");
+        }
+        if let Some(watermark) = ctx.last_present_watermark() {
+            self.state.expire(to_nanos(watermark) as i64);
+        }
+    }
+
+    async fn process_batch(&mut self, batch: RecordBatch, ctx: &mut ArrowContext) {
+        let output = self
+            .process(&batch)
+            .expect("should be able to compute z-scores");
+        self.input
+            .insert(ctx, &batch)
+            .await
+            .expect("should be able to buffer z-score input");
+        ctx.collect(output).await;
+    }
+
+    async fn handle_watermark(
+        &mut self,
+        watermark: Watermark,
+        _: &mut ArrowContext,
+    ) -> Option<Watermark> {
+        if let Watermark::EventTime(t) = watermark {
+            self.state.expire(to_nanos(t) as i64);
+        }
+        Some(watermark)
+    }
+
+    async fn handle_checkpoint(&mut self, _: CheckpointBarrier, ctx: &mut ArrowContext) {
+        self.input
+            .flush(ctx)
+            .await
+            .expect("should be able to flush z-score input");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SECOND: i64 = 1_000_000_000;
+
+    #[test]
+    fn test_outlier_flagged() {
+        let mut state = ZScoreState::new(Duration::from_secs(30), 3.0);
+
+        let flagged: Vec<_> = (0..60)
+            .map(|i| {
+                let value = if i == 45 {
+                    100.0
+                } else {
+                    [10.0, 11.0, 10.5, 11.5][i as usize % 4]
+                };
+                state.check(b"a".to_vec(), i * SECOND, value)
+            })
+            .collect();
+
+        assert_eq!(
+            flagged
+                .iter()
+                .enumerate()
+                .filter(|(_, f)| **f)
+                .map(|(i, _)| i)
+                .collect::<Vec<_>>(),
+            vec![45]
+        );
+    }
+
+    #[test]
+    fn test_window_slides() {
+        let mut state = ZScoreState::new(Duration::from_secs(10), 2.0);
+
+        for i in 0..10 {
+            state.check(b"a".to_vec(), i * SECOND, 100.0 + (i % 2) as f64);
+        }
+        // the earlier values are still in the window, so this is an outlier
+        assert!(state.check(b"a".to_vec(), 10 * SECOND, 0.0));
+
+        // once the window has moved past the earlier values, lower values are normal
+        for i in 30..40 {
+            state.check(b"a".to_vec(), i * SECOND, (i % 2) as f64);
+        }
+        assert!(!state.check(b"a".to_vec(), 40 * SECOND, 1.0));
+        assert_eq!(state.windows[b"a".as_slice()].values.len(), 10);
+    }
+
+    #[test]
+    fn test_keys_independent() {
+        let mut state = ZScoreState::new(Duration::from_secs(60), 3.0);
+        for i in 0..20 {
+            state.check(b"a".to_vec(), i * SECOND, (i % 3) as f64);
+            state.check(b"b".to_vec(), i * SECOND, 1000.0 + (i % 3) as f64);
+        }
+
+        assert!(!state.check(b"b".to_vec(), 20 * SECOND, 1001.0));
+        assert!(state.check(b"a".to_vec(), 20 * SECOND, 1001.0));
+    }
+
+    #[test]
+    fn test_watermark_expires_windows() {
+        let mut state = ZScoreState::new(Duration::from_secs(10), 2.0);
+        for i in 0..5 {
+            state.check(b"a".to_vec(), i * SECOND, 100.0 + (i % 2) as f64);
+        }
+        state.check(b"b".to_vec(), 8 * SECOND, 1.0);
+
+        state.expire(15 * SECOND);
+        assert!(!state.windows.contains_key(b"a".as_slice()));
+        assert_eq!(state.windows[b"b".as_slice()].values.len(), 1);
+    }
+}
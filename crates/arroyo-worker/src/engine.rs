@@ -12,15 +12,41 @@ use futures::stream::FuturesUnordered;
 use futures::StreamExt;
 use tracing::{info, warn};
 
+use crate::arrow::async_batch_transform::AsyncBatchTransformConstructor;
 use crate::arrow::async_udf::AsyncUdfConstructor;
+use crate::arrow::bloom_filter::BloomFilterConstructor;
+use crate::arrow::boundary_flush::BoundaryFlushConstructor;
+use crate::arrow::correlation::CorrelationConstructor;
+use crate::arrow::count_window::CountWindowConstructor;
+use crate::arrow::delta::DeltaConstructor;
+use crate::arrow::early_results::EarlyResultsConstructor;
+use crate::arrow::ema::EmaConstructor;
+use crate::arrow::first_last::FirstLastConstructor;
+use crate::arrow::heartbeat::HeartbeatConstructor;
+use crate::arrow::histogram::HistogramConstructor;
+use crate::arrow::hopping_dedup::HoppingDedupConstructor;
 use crate::arrow::instant_join::InstantJoinConstructor;
 use crate::arrow::join_with_expiration::JoinWithExpirationConstructor;
+use crate::arrow::late_data_filter::LateDataFilterConstructor;
+use crate::arrow::leaderboard::LeaderboardConstructor;
+use crate::arrow::materialized_view::MaterializedViewConstructor;
+use crate::arrow::project_filter::ProjectFilterConstructor;
+use crate::arrow::retract::RetractConstructor;
+use crate::arrow::rolling_average::RollingAverageConstructor;
 use crate::arrow::session_aggregating_window::SessionAggregatingWindowConstructor;
 use crate::arrow::sliding_aggregating_window::SlidingAggregatingWindowConstructor;
+use crate::arrow::snapshot_diff::SnapshotDiffConstructor;
+use crate::arrow::split::SplitConstructor;
+use crate::arrow::temporal_join::TemporalJoinConstructor;
+use crate::arrow::throughput::ThroughputConstructor;
 use crate::arrow::tumbling_aggregating_window::TumblingAggregateWindowConstructor;
+use crate::arrow::union::UnionConstructor;
 use crate::arrow::updating_aggregator::UpdatingAggregatingConstructor;
 use crate::arrow::watermark_generator::WatermarkGeneratorConstructor;
+use crate::arrow::watermark_stall::WatermarkStallConstructor;
+use crate::arrow::window_assigner::WindowAssignerConstructor;
 use crate::arrow::window_fn::WindowFunctionConstructor;
+use crate::arrow::z_score::ZScoreConstructor;
 use crate::arrow::{KeyExecutionConstructor, ValueExecutionConstructor};
 use crate::network_manager::{NetworkManager, Quad, Senders};
 use arroyo_datastream::logical::{
@@ -774,6 +800,32 @@ pub fn construct_operator(
         OperatorName::Join => Box::new(JoinWithExpirationConstructor),
         OperatorName::InstantJoin => Box::new(InstantJoinConstructor),
         OperatorName::WindowFunction => Box::new(WindowFunctionConstructor),
+        OperatorName::Delta => Box::new(DeltaConstructor),
+        OperatorName::Heartbeat => Box::new(HeartbeatConstructor),
+        OperatorName::CountWindow => Box::new(CountWindowConstructor),
+        OperatorName::Union => Box::new(UnionConstructor),
+        OperatorName::AsyncBatchTransform => Box::new(AsyncBatchTransformConstructor),
+        OperatorName::MaterializedView => Box::new(MaterializedViewConstructor),
+        OperatorName::WatermarkStall => Box::new(WatermarkStallConstructor),
+        OperatorName::WindowAssigner => Box::new(WindowAssignerConstructor),
+        OperatorName::RollingAverage => Box::new(RollingAverageConstructor),
+        OperatorName::Retract => Box::new(RetractConstructor),
+        OperatorName::BloomFilter => Box::new(BloomFilterConstructor),
+        OperatorName::Split => Box::new(SplitConstructor),
+        OperatorName::FirstLast => Box::new(FirstLastConstructor),
+        OperatorName::BoundaryFlush => Box::new(BoundaryFlushConstructor),
+        OperatorName::Ema => Box::new(EmaConstructor),
+        OperatorName::ZScore => Box::new(ZScoreConstructor),
+        OperatorName::HoppingDedup => Box::new(HoppingDedupConstructor),
+        OperatorName::Histogram => Box::new(HistogramConstructor),
+        OperatorName::TemporalJoin => Box::new(TemporalJoinConstructor),
+        OperatorName::EarlyResults => Box::new(EarlyResultsConstructor),
+        OperatorName::SnapshotDiff => Box::new(SnapshotDiffConstructor),
+        OperatorName::ProjectFilter => Box::new(ProjectFilterConstructor),
+        OperatorName::Throughput => Box::new(ThroughputConstructor),
+        OperatorName::Leaderboard => Box::new(LeaderboardConstructor),
+        OperatorName::Correlation => Box::new(CorrelationConstructor),
+        OperatorName::LateDataFilter => Box::new(LateDataFilterConstructor),
         OperatorName::ConnectorSource | OperatorName::ConnectorSink => {
             let op: api::ConnectorOp = prost::Message::decode(&mut config.as_slice()).unwrap();
             return connectors()
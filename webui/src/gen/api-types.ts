@@ -128,12 +128,56 @@ export type webhooks = Record<string, never>;
 export interface components {
   schemas: {
     AvroFormat: {
+      caseInsensitiveFields?: boolean;
       confluentSchemaRegistry?: boolean;
+      decimalSeparator?: components["schemas"]["DecimalSeparator"];
+      epochOffsets?: {
+        [key: string]: number | undefined;
+      };
+      fallbackSchema?: string;
+      fieldNameCollisions?: components["schemas"]["FieldNameCollisionPolicy"];
+      fieldTiming?: boolean;
+      fieldTransforms?: {
+        [key: string]: (components["schemas"]["FieldTransform"])[] | undefined;
+      };
+      floatPrecisionLoss?: components["schemas"]["PrecisionLossPolicy"];
+      integerOverflow?: components["schemas"]["IntegerOverflowPolicy"];
       intoUnstructuredJson?: boolean;
+      logicalTypeMappings?: {
+        [key: string]: components["schemas"]["LogicalTypeMapping"] | undefined;
+      };
+      mapNullValues?: components["schemas"]["MapNullValuePolicy"];
+      /** Format: int64 */
+      maxArrayLength?: number | null;
+      /** Format: int64 */
+      maxBatchBytes?: number | null;
+      /** Format: int32 */
+      maxBatchErrorPercent?: number | null;
+      /** Format: int64 */
+      maxBatchErrors?: number | null;
+      /** Format: int32 */
+      maxNestingDepth?: number | null;
+      nestingOverflow?: components["schemas"]["NestingOverflowPolicy"];
+      nonFiniteDecimals?: components["schemas"]["NonFiniteDecimalPolicy"];
+      nullSentinels?: {
+        [key: string]: string | undefined;
+      };
+      outOfRangeTimes?: components["schemas"]["OutOfRangeTimePolicy"];
+      oversizedArrays?: components["schemas"]["OversizedArrayPolicy"];
+      parallelDecode?: boolean;
       rawDatums?: boolean;
       readerSchema?: string;
+      reportUnexpectedFields?: boolean;
+      requiredFields?: {
+        [key: string]: (string)[] | undefined;
+      };
       /** Format: int32 */
       schemaId?: number | null;
+      singleObjectEncoding?: boolean;
+      stringifyScalars?: boolean;
+      suppressEmptyBatches?: boolean;
+      unframedMessages?: components["schemas"]["UnframedMessagePolicy"];
+      unknownEnumSymbols?: components["schemas"]["UnknownEnumSymbolPolicy"];
     };
     BadData: OneOf<[{
       fail: Record<string, never>;
@@ -234,9 +278,12 @@ export interface components {
     ConnectorCollection: {
       data: (components["schemas"]["Connector"])[];
     };
+    DecimalSeparator: "period" | "comma";
     ErrorResp: {
       error: string;
     };
+    FieldNameCollisionPolicy: "error" | "first_wins";
+    FieldTransform: "trim" | "lowercase" | "uppercase" | "hash";
     FieldType: OneOf<[{
       primitive: components["schemas"]["PrimitiveType"];
     }, {
@@ -276,6 +323,7 @@ export interface components {
     GlobalUdfCollection: {
       data: (components["schemas"]["GlobalUdf"])[];
     };
+    IntegerOverflowPolicy: "error" | "null" | "saturate";
     Job: {
       /** Format: int64 */
       createdAt: number;
@@ -321,6 +369,8 @@ export interface components {
       timestampFormat?: components["schemas"]["TimestampFormat"];
       unstructured?: boolean;
     };
+    LogicalTypeMapping: "timestamp_millis" | "timestamp_micros" | "timestamp_nanos" | "int64" | "float64" | "binary" | "decimal" | "fixed_size_binary";
+    MapNullValuePolicy: "preserve" | "drop";
     Metric: {
       /** Format: int64 */
       time: number;
@@ -333,10 +383,12 @@ export interface components {
     };
     /** @enum {string} */
     MetricNames: "bytes_recv" | "bytes_sent" | "messages_recv" | "messages_sent" | "backpressure";
+    NestingOverflowPolicy: "truncate" | "drop" | "error";
     NewlineDelimitedFraming: {
       /** Format: int64 */
       maxLineLength?: number | null;
     };
+    NonFiniteDecimalPolicy: "error" | "null";
     OperatorCheckpointGroup: {
       /** Format: int64 */
       bytes: number;
@@ -353,12 +405,14 @@ export interface components {
     OperatorMetricGroupCollection: {
       data: (components["schemas"]["OperatorMetricGroup"])[];
     };
+    OutOfRangeTimePolicy: "error" | "null";
     OutputData: {
       operatorId: string;
       /** Format: int64 */
       timestamp: number;
       value: string;
     };
+    OversizedArrayPolicy: "truncate" | "reject";
     PaginationQueryParams: {
       /** Format: int32 */
       limit?: number | null;
@@ -424,6 +478,7 @@ export interface components {
       force?: boolean | null;
     };
     /** @enum {string} */
+    PrecisionLossPolicy: "truncate" | "error";
     PrimitiveType: "Int32" | "Int64" | "UInt32" | "UInt64" | "F32" | "F64" | "Bool" | "String" | "Bytes" | "UnixMillis" | "UnixMicros" | "UnixNanos" | "DateTime" | "Json";
     QueryValidationResult: {
       errors: (string)[];
@@ -486,6 +541,8 @@ export interface components {
       errors: (string)[];
       udfName?: string | null;
     };
+    UnframedMessagePolicy: "error" | "reader_schema";
+    UnknownEnumSymbolPolicy: "error" | "null";
     ValidateQueryPost: {
       query: string;
       udfs?: (components["schemas"]["Udf"])[] | null;